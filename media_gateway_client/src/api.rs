@@ -0,0 +1,87 @@
+//! HTTP API of the media gateway client.
+use std::collections::HashMap;
+
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use savant_core::message::Message;
+use serde::Serialize;
+
+use media_gateway_common::model::Media;
+
+use crate::ingest::IngestAccess;
+use crate::service::GatewayClientService;
+
+/// The state of the queue of messages.
+#[derive(Debug, Serialize)]
+pub struct QueueState {
+    /// The number of messages in the queue by sources
+    pub sources: HashMap<String, usize>,
+}
+
+pub async fn queue(service: web::Data<GatewayClientService>) -> impl Responder {
+    let queue_state = QueueState {
+        sources: service.queue_depths(),
+    };
+    let body = serde_json::to_string(&queue_state).unwrap();
+
+    HttpResponse::Ok()
+        .content_type(ContentType::json())
+        .body(body)
+}
+
+pub async fn shaper(service: web::Data<GatewayClientService>) -> impl Responder {
+    match service.shaper_stats() {
+        Some(stats) => {
+            let body = serde_json::to_string(&stats).unwrap();
+            HttpResponse::Ok()
+                .content_type(ContentType::json())
+                .body(body)
+        }
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+pub async fn congestion(service: web::Data<GatewayClientService>) -> impl Responder {
+    match service.congestion_stats() {
+        Some(stats) => {
+            let body = serde_json::to_string(&stats).unwrap();
+            HttpResponse::Ok()
+                .content_type(ContentType::json())
+                .body(body)
+        }
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+pub async fn ingest(
+    request: HttpRequest,
+    body: web::Bytes,
+    service: web::Data<GatewayClientService>,
+    access: web::Data<Option<IngestAccess>>,
+) -> impl Responder {
+    let access = match access.get_ref() {
+        Some(access) => access,
+        None => return HttpResponse::NotFound().finish(),
+    };
+    if let Err(status) = access.authorize(&request) {
+        return HttpResponse::build(status).finish();
+    }
+    let media = match Media::from_proto(&body) {
+        Ok(media) => media,
+        Err(e) => {
+            log::warn!("Error while decoding ingested message: {:?}", e);
+            return HttpResponse::BadRequest().finish();
+        }
+    };
+    let message = match media.message.as_ref().map(Message::try_from) {
+        Some(Ok(message)) => message,
+        _ => return HttpResponse::BadRequest().finish(),
+    };
+    match service.ingest(media, &message).await {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(e) => {
+            log::warn!("Error while ingesting message: {:?}", e);
+            HttpResponse::ServiceUnavailable().finish()
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use tokio::sync::mpsc::Receiver;
+use tokio_timerfd::sleep;
+
+use media_gateway_common::model::Media;
+
+use crate::configuration::BatchConfiguration;
+
+/// Receives messages until one of the batch limits is reached. Returns [`None`] if the channel
+/// is closed and no messages are left.
+pub async fn next_batch<T>(
+    receiver: &mut Receiver<(T, Media)>,
+    configuration: &BatchConfiguration,
+) -> Option<Vec<(T, Media)>> {
+    let first = receiver.recv().await?;
+    let mut size = first.1.proto_len();
+    let mut batch = vec![first];
+
+    let linger = sleep(configuration.max_linger);
+    tokio::pin!(linger);
+
+    while batch.len() < configuration.max_count && size < configuration.max_bytes {
+        tokio::select! {
+            _ = &mut linger => break,
+            next = receiver.recv() => match next {
+                Some(item) => {
+                    size += item.1.proto_len();
+                    batch.push(item);
+                }
+                None => break,
+            }
+        }
+    }
+    Some(batch)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::sync::mpsc;
+
+    use media_gateway_common::model::Media;
+
+    use crate::batch::next_batch;
+    use crate::configuration::BatchConfiguration;
+
+    #[tokio::test]
+    async fn next_batch_max_count() {
+        let (sender, mut receiver) = mpsc::channel(10);
+        for i in 0..5 {
+            sender.send((i, new_media(10))).await.unwrap();
+        }
+        let configuration = new_configuration(3, 1000, Duration::from_secs(10));
+
+        let batch = next_batch(&mut receiver, &configuration).await.unwrap();
+
+        assert_eq!(
+            batch.iter().map(|e| e.0).collect::<Vec<i32>>(),
+            vec![0, 1, 2]
+        );
+    }
+
+    #[tokio::test]
+    async fn next_batch_max_bytes() {
+        let (sender, mut receiver) = mpsc::channel(10);
+        for i in 0..5 {
+            sender.send((i, new_media(10))).await.unwrap();
+        }
+        let media_size = new_media(10).proto_len();
+        let configuration = new_configuration(10, 2 * media_size, Duration::from_secs(10));
+
+        let batch = next_batch(&mut receiver, &configuration).await.unwrap();
+
+        assert_eq!(batch.iter().map(|e| e.0).collect::<Vec<i32>>(), vec![0, 1]);
+    }
+
+    #[tokio::test]
+    async fn next_batch_max_linger() {
+        let (sender, mut receiver) = mpsc::channel(10);
+        sender.send((0, new_media(10))).await.unwrap();
+        let configuration = new_configuration(10, 1000, Duration::from_millis(10));
+
+        let batch = next_batch(&mut receiver, &configuration).await.unwrap();
+
+        assert_eq!(batch.iter().map(|e| e.0).collect::<Vec<i32>>(), vec![0]);
+        drop(sender);
+    }
+
+    #[tokio::test]
+    async fn next_batch_closed() {
+        let (sender, mut receiver) = mpsc::channel::<(i32, Media)>(10);
+        drop(sender);
+        let configuration = new_configuration(10, 1000, Duration::from_millis(10));
+
+        let batch = next_batch(&mut receiver, &configuration).await;
+
+        assert!(batch.is_none());
+    }
+
+    fn new_configuration(
+        max_count: usize,
+        max_bytes: usize,
+        max_linger: Duration,
+    ) -> BatchConfiguration {
+        BatchConfiguration {
+            max_count,
+            max_bytes,
+            max_linger,
+        }
+    }
+
+    fn new_media(data_size: usize) -> Media {
+        Media {
+            message: None,
+            topic: "topic".as_bytes().to_vec(),
+            data: vec![vec![0; data_size]],
+        }
+    }
+}
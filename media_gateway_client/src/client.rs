@@ -2,15 +2,36 @@
 //!
 //! The module provides [`GatewayClient`] and [`ForwardResult`].
 use std::fs;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use anyhow::anyhow;
+use anyhow::{anyhow, bail};
+use futures_util::future::join_all;
 use http_auth_basic::Credentials;
-use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
-use reqwest::{Certificate, Client, Identity, StatusCode};
+use reqwest::header::{
+    HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_ENCODING, CONTENT_TYPE, RETRY_AFTER,
+};
+use reqwest::{Certificate, Client, Identity, RequestBuilder, Response, StatusCode};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::Connector;
+use tonic::metadata::MetadataValue;
+use tonic::transport::{ClientTlsConfig, Endpoint};
 
-use media_gateway_common::model::Media;
+use media_gateway_common::configuration::ClientTlsConfiguration;
+use media_gateway_common::model::{Media, MediaBatch, MediaBatchResult, MediaStatus};
 
-use crate::configuration::GatewayClientConfiguration;
+use crate::compression::Compressor;
+use crate::configuration::{
+    AuthConfiguration, EndpointsConfiguration, GatewayClientConfiguration, RouteConfiguration,
+    Transport,
+};
+use crate::endpoint::{EndpointSelector, HealthCheck};
+use crate::grpc::GrpcClient;
+use crate::websocket::WebSocketClient;
+
+const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+const DEFAULT_HEALTH_CHECK_PERIOD: Duration = Duration::from_secs(5);
+const DEFAULT_RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
 
 /// The result of [`GatewayClient::forward_message`] method.
 #[derive(Debug)]
@@ -23,6 +44,69 @@ pub enum ForwardResult {
     /// Represents the error caused by
     /// [`WriterResult::AckTimeout`](savant_core::transport::zeromq::WriterResult::AckTimeout)
     AckTimeout,
+    /// Represents other unsuccessful HTTP status. For streaming transports the HTTP status
+    /// corresponding to [`MediaStatus`] is used.
+    HttpStatus(StatusCode),
+    /// Represents the server overload (HTTP status 429 or 503) with the delay from `Retry-After`
+    /// header
+    Overloaded {
+        status: StatusCode,
+        retry_after: Option<Duration>,
+    },
+}
+
+impl ForwardResult {
+    /// Returns the delay suggested by the server before the next attempt.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ForwardResult::Overloaded { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if the result indicates a failure of the endpoint (server errors). An
+    /// overloaded endpoint is not failed.
+    fn is_endpoint_failure(result: &anyhow::Result<ForwardResult>) -> bool {
+        match result {
+            Ok(ForwardResult::HttpStatus(status)) => status.is_server_error(),
+            Ok(_) => false,
+            Err(_) => true,
+        }
+    }
+
+    fn from_response(response: &Response) -> Self {
+        match response.status() {
+            StatusCode::OK => ForwardResult::Success,
+            StatusCode::GATEWAY_TIMEOUT => ForwardResult::SendTimeout,
+            StatusCode::BAD_GATEWAY => ForwardResult::AckTimeout,
+            status @ (StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE) => {
+                ForwardResult::Overloaded {
+                    status,
+                    retry_after: retry_after(response),
+                }
+            }
+            status => ForwardResult::HttpStatus(status),
+        }
+    }
+}
+
+impl From<MediaStatus> for ForwardResult {
+    fn from(status: MediaStatus) -> Self {
+        match status {
+            MediaStatus::Success => ForwardResult::Success,
+            MediaStatus::SendTimeout => ForwardResult::SendTimeout,
+            MediaStatus::AckTimeout => ForwardResult::AckTimeout,
+            MediaStatus::BadRequest => ForwardResult::HttpStatus(StatusCode::BAD_REQUEST),
+            MediaStatus::Unauthorized => ForwardResult::HttpStatus(StatusCode::UNAUTHORIZED),
+            MediaStatus::InternalError => {
+                ForwardResult::HttpStatus(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+            MediaStatus::Overloaded => ForwardResult::Overloaded {
+                status: StatusCode::SERVICE_UNAVAILABLE,
+                retry_after: None,
+            },
+        }
+    }
 }
 
 /// The client for the media gateway server.
@@ -42,8 +126,19 @@ pub enum ForwardResult {
 /// The main method is [`GatewayClient::forward_message`]. After usage of the client
 /// [`GatewayClient::shutdown`] method should be called to release resources.
 pub struct GatewayClient {
-    url: String,
-    client: Client,
+    transports: Vec<ClientTransport>,
+    selector: Arc<EndpointSelector>,
+}
+
+enum ClientTransport {
+    Http {
+        url: String,
+        batch_url: String,
+        client: Client,
+        compressor: Option<Compressor>,
+    },
+    WebSocket(WebSocketClient),
+    Grpc(GrpcClient),
 }
 
 impl GatewayClient {
@@ -54,100 +149,486 @@ impl GatewayClient {
     /// * `reader` - a reader for messages to be forwarded to the media gateway server
     /// * `client` - a client to forward messages
     /// * `url` - an endpoint of the media gateway service to accept messages
+    /// * `compressor` - a compressor for request bodies
     ///
     /// # Details
     ///
     /// Before calling this method the reader must be started and the client must be fully
     /// configured (SSL certificates, [`AUTHORIZATION`] header as a default headers).
-    pub fn new(client: Client, url: String) -> Self {
-        Self { client, url }
+    ///
+    /// Batches are sent to `<url>/batch` endpoint.
+    pub fn new(client: Client, url: String, compressor: Option<Compressor>) -> Self {
+        let batch_url = format!("{}/batch", url.trim_end_matches('/'));
+        Self::with_transport(ClientTransport::Http {
+            client,
+            url,
+            batch_url,
+            compressor,
+        })
+    }
+
+    /// Constructs a new instance of the client that streams messages over WebSocket.
+    pub fn with_websocket(client: WebSocketClient) -> Self {
+        Self::with_transport(ClientTransport::WebSocket(client))
+    }
+
+    /// Constructs a new instance of the client that streams messages over gRPC.
+    pub fn with_grpc(client: GrpcClient) -> Self {
+        Self::with_transport(ClientTransport::Grpc(client))
+    }
+
+    /// Constructs a new instance of the client that forwards messages to one of the endpoints
+    /// selected by the selector. Each client must have a single endpoint.
+    pub fn with_endpoints(clients: Vec<GatewayClient>, selector: EndpointSelector) -> Self {
+        Self {
+            transports: clients.into_iter().flat_map(|e| e.transports).collect(),
+            selector: Arc::new(selector),
+        }
+    }
+
+    fn with_transport(transport: ClientTransport) -> Self {
+        Self {
+            selector: Arc::new(EndpointSelector::single()),
+            transports: vec![transport],
+        }
     }
 
     /// Receives the messages using [`SyncReader`] and sends it to the media gateway server.
     pub async fn forward_message(&self, media: &Media) -> anyhow::Result<ForwardResult> {
+        let index = self.selector.select();
+        let start = Instant::now();
+        let result = self.transports[index].forward_message(media).await;
+        self.selector.register_result(
+            index,
+            !ForwardResult::is_endpoint_failure(&result),
+            start.elapsed(),
+        );
+        result
+    }
+
+    /// Sends the batch of messages to the media gateway server. If the batch is processed by
+    /// the server the result for each message in the same order as in the batch is returned.
+    pub async fn forward_batch(
+        &self,
+        batch: &MediaBatch,
+    ) -> anyhow::Result<Vec<anyhow::Result<ForwardResult>>> {
+        let index = self.selector.select();
+        let start = Instant::now();
+        let result = self.transports[index].forward_batch(batch).await;
+        let success = result
+            .as_ref()
+            .is_ok_and(|results| !results.iter().any(ForwardResult::is_endpoint_failure));
+        self.selector
+            .register_result(index, success, start.elapsed());
+        result
+    }
+
+    /// Closes connections of WebSocket and gRPC transports.
+    pub async fn shutdown(&self) {
+        for transport in self.transports.iter() {
+            match transport {
+                ClientTransport::Http { .. } => {}
+                ClientTransport::WebSocket(client) => client.shutdown().await,
+                ClientTransport::Grpc(client) => client.shutdown().await,
+            }
+        }
+    }
+}
+
+impl ClientTransport {
+    async fn forward_message(&self, media: &Media) -> anyhow::Result<ForwardResult> {
+        let (client, url, compressor) = match self {
+            ClientTransport::Http {
+                client,
+                url,
+                compressor,
+                ..
+            } => (client, url, compressor),
+            ClientTransport::WebSocket(client) => return client.forward_message(media).await,
+            ClientTransport::Grpc(client) => return client.forward_message(media).await,
+        };
         let data = media.to_proto()?;
-        let send_result = self
-            .client
-            .post(&self.url)
-            .body(data)
-            .header(CONTENT_TYPE, "application/protobuf")
-            .send()
-            .await;
+        let send_result = post(client, url, data, compressor.as_ref())?.send().await;
         match send_result {
-            Ok(response) => match response.status() {
-                StatusCode::OK => Ok(ForwardResult::Success),
-                StatusCode::GATEWAY_TIMEOUT => Ok(ForwardResult::SendTimeout),
-                StatusCode::BAD_GATEWAY => Ok(ForwardResult::AckTimeout),
-                status_code => Err(anyhow!("Invalid HTTP status: {}", status_code)),
-            },
+            Ok(response) => Ok(ForwardResult::from_response(&response)),
             Err(e) => Err(anyhow!("Error while sending a message").context(e.to_string())),
         }
     }
+
+    async fn forward_batch(
+        &self,
+        batch: &MediaBatch,
+    ) -> anyhow::Result<Vec<anyhow::Result<ForwardResult>>> {
+        let (client, batch_url, compressor) = match self {
+            ClientTransport::Http {
+                client,
+                batch_url,
+                compressor,
+                ..
+            } => (client, batch_url, compressor),
+            ClientTransport::WebSocket(client) => {
+                return Ok(join_all(batch.items.iter().map(|e| client.forward_message(e))).await)
+            }
+            ClientTransport::Grpc(client) => {
+                return Ok(join_all(batch.items.iter().map(|e| client.forward_message(e))).await)
+            }
+        };
+        let data = batch.to_proto()?;
+        let send_result = post(client, batch_url, data, compressor.as_ref())?
+            .send()
+            .await;
+        let response = match send_result {
+            Ok(response) => response,
+            Err(e) => {
+                return Err(anyhow!("Error while sending a batch").context(e.to_string()));
+            }
+        };
+        match response.status() {
+            StatusCode::OK => {}
+            status @ (StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE) => {
+                let retry_after = retry_after(&response);
+                return Ok(batch
+                    .items
+                    .iter()
+                    .map(|_| {
+                        Ok(ForwardResult::Overloaded {
+                            status,
+                            retry_after,
+                        })
+                    })
+                    .collect());
+            }
+            status_code => {
+                return Ok(batch
+                    .items
+                    .iter()
+                    .map(|_| Ok(ForwardResult::HttpStatus(status_code)))
+                    .collect());
+            }
+        }
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| anyhow!("Error while receiving a batch result").context(e.to_string()))?;
+        let batch_result = MediaBatchResult::from_proto(&body)?;
+        if batch_result.statuses.len() != batch.items.len() {
+            bail!(
+                "Invalid batch result: {} statuses for {} messages",
+                batch_result.statuses.len(),
+                batch.items.len()
+            );
+        }
+        Ok(batch_result
+            .statuses
+            .into_iter()
+            .map(|status| match MediaStatus::try_from(status) {
+                Ok(status) => Ok(ForwardResult::from(status)),
+                Err(_) => Err(anyhow!("Unknown media status: {}", status)),
+            })
+            .collect())
+    }
+}
+
+/// Returns the delay from `Retry-After` header. Only the number of seconds is supported.
+fn retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+fn post(
+    client: &Client,
+    url: &str,
+    data: Vec<u8>,
+    compressor: Option<&Compressor>,
+) -> anyhow::Result<RequestBuilder> {
+    let request = client
+        .post(url)
+        .header(CONTENT_TYPE, "application/protobuf");
+    Ok(if let Some(compressor) = compressor {
+        request
+            .header(CONTENT_ENCODING, compressor.content_encoding())
+            .body(compressor.compress(&data)?)
+    } else {
+        request.body(data)
+    })
 }
 
 impl TryFrom<&GatewayClientConfiguration> for GatewayClient {
     type Error = anyhow::Error;
 
     fn try_from(configuration: &GatewayClientConfiguration) -> Result<Self, Self::Error> {
-        let mut client_builder = Client::builder().tls_built_in_root_certs(true);
+        new_client(
+            configuration,
+            &configuration.url,
+            configuration.endpoints.as_ref(),
+            configuration.tls.as_ref(),
+            configuration.auth.as_ref(),
+        )
+    }
+}
 
-        client_builder = if let Some(ssl_conf) = &configuration.tls {
-            client_builder = if let Some(certificate) = &ssl_conf.root_certificate {
-                let buf = fs::read(certificate)?;
-                let cert = Certificate::from_pem(&buf)?;
+/// Constructs a client for the route. The transport, compression, retry strategy and HTTP/2
+/// settings are taken from the client configuration.
+impl TryFrom<(&GatewayClientConfiguration, &RouteConfiguration)> for GatewayClient {
+    type Error = anyhow::Error;
 
-                client_builder.add_root_certificate(cert)
-            } else {
-                client_builder
-            };
+    fn try_from(
+        (configuration, route): (&GatewayClientConfiguration, &RouteConfiguration),
+    ) -> Result<Self, Self::Error> {
+        new_client(
+            configuration,
+            &route.url,
+            None,
+            route.tls.as_ref(),
+            route.auth.as_ref(),
+        )
+    }
+}
+
+fn new_client(
+    configuration: &GatewayClientConfiguration,
+    url: &str,
+    endpoints: Option<&EndpointsConfiguration>,
+    tls: Option<&ClientTlsConfiguration>,
+    auth: Option<&AuthConfiguration>,
+) -> anyhow::Result<GatewayClient> {
+    let auth_header = if let Some(auth_conf) = auth {
+        let mut auth_value = HeaderValue::from_str(
+            &Credentials::new(&auth_conf.basic.username, &auth_conf.basic.password)
+                .as_http_header(),
+        )?;
+        auth_value.set_sensitive(true);
+        Some(auth_value)
+    } else {
+        None
+    };
 
-            if let Some(identity) = &ssl_conf.identity {
-                let cert = fs::read(&identity.certificate)?;
-                let key = fs::read(&identity.key)?;
-                let identity = Identity::from_pkcs8_pem(&cert, &key)?;
+    let transport = configuration.transport.as_ref().unwrap_or(&Transport::Http);
+    if configuration.compression.is_some() && !matches!(transport, Transport::Http) {
+        bail!("Invalid compression: supported only for http transport");
+    }
 
-                client_builder.identity(identity)
-            } else {
-                client_builder
+    if endpoints.is_some() && matches!(transport, Transport::Grpc) {
+        bail!("Invalid endpoints: not supported for grpc transport");
+    }
+
+    let response_timeout = configuration
+        .response_timeout
+        .unwrap_or(DEFAULT_RESPONSE_TIMEOUT);
+    let mut urls = vec![url.to_string()];
+    if let Some(endpoints_conf) = endpoints {
+        urls.extend(endpoints_conf.urls.iter().cloned());
+    }
+    let mut clients = urls
+        .iter()
+        .map(|url| match transport {
+            Transport::Http => new_http_client(configuration, tls, url, auth_header.clone()),
+            Transport::WebSocket => {
+                new_websocket_client(tls, url, auth_header.clone(), response_timeout)
             }
-        } else {
-            client_builder
+            Transport::Grpc => new_grpc_client(tls, url, auth_header.clone(), response_timeout),
+        })
+        .collect::<anyhow::Result<Vec<GatewayClient>>>()?;
+
+    if let Some(endpoints_conf) = endpoints {
+        let failure_threshold = match endpoints_conf.failure_threshold {
+            Some(0) => bail!("Invalid endpoints failure_threshold: 0"),
+            Some(failure_threshold) => failure_threshold,
+            None => DEFAULT_FAILURE_THRESHOLD,
         };
+        let selector = EndpointSelector::new(
+            urls,
+            endpoints_conf.policy.clone(),
+            failure_threshold,
+            Some(HealthCheck {
+                client: new_reqwest_client(configuration, tls, None)?,
+                period: endpoints_conf
+                    .health_check_period
+                    .unwrap_or(DEFAULT_HEALTH_CHECK_PERIOD),
+            }),
+        )?;
+        Ok(GatewayClient::with_endpoints(clients, selector))
+    } else {
+        Ok(clients.remove(0))
+    }
+}
 
-        client_builder = if let Some(auth_conf) = &configuration.auth {
-            let mut headers = HeaderMap::new();
+fn new_reqwest_client(
+    configuration: &GatewayClientConfiguration,
+    tls: Option<&ClientTlsConfiguration>,
+    auth_header: Option<HeaderValue>,
+) -> anyhow::Result<Client> {
+    let mut client_builder = Client::builder().tls_built_in_root_certs(true);
 
-            let mut auth_value = HeaderValue::from_str(
-                &Credentials::new(&auth_conf.basic.username, &auth_conf.basic.password)
-                    .as_http_header(),
-            )?;
-            auth_value.set_sensitive(true);
-            headers.insert(AUTHORIZATION, auth_value);
+    client_builder = if let Some(ssl_conf) = tls {
+        client_builder = if let Some(certificate) = &ssl_conf.root_certificate {
+            let buf = fs::read(certificate)?;
+            let cert = Certificate::from_pem(&buf)?;
 
-            client_builder.default_headers(headers)
+            client_builder.add_root_certificate(cert)
         } else {
             client_builder
         };
 
-        Ok(GatewayClient::new(
-            client_builder.build()?,
-            configuration.url.clone(),
-        ))
+        if let Some(identity) = &ssl_conf.identity {
+            let cert = fs::read(&identity.certificate)?;
+            let key = fs::read(&identity.key)?;
+            let identity = Identity::from_pkcs8_pem(&cert, &key)?;
+
+            client_builder.identity(identity)
+        } else {
+            client_builder
+        }
+    } else {
+        client_builder
+    };
+
+    client_builder = if configuration.http2_prior_knowledge.unwrap_or(false) {
+        client_builder.http2_prior_knowledge()
+    } else {
+        client_builder
+    };
+
+    client_builder = if let Some(auth_value) = auth_header {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, auth_value);
+
+        client_builder.default_headers(headers)
+    } else {
+        client_builder
+    };
+
+    Ok(client_builder.build()?)
+}
+
+fn new_http_client(
+    configuration: &GatewayClientConfiguration,
+    tls: Option<&ClientTlsConfiguration>,
+    url: &str,
+    auth_header: Option<HeaderValue>,
+) -> anyhow::Result<GatewayClient> {
+    let compressor = match &configuration.compression {
+        Some(compression) => Some(Compressor::try_from(compression)?),
+        None => None,
+    };
+
+    Ok(GatewayClient::new(
+        new_reqwest_client(configuration, tls, auth_header)?,
+        url.to_string(),
+        compressor,
+    ))
+}
+
+fn new_websocket_client(
+    tls: Option<&ClientTlsConfiguration>,
+    url: &str,
+    auth_header: Option<HeaderValue>,
+    response_timeout: Duration,
+) -> anyhow::Result<GatewayClient> {
+    let ws_url = url.trim_end_matches('/');
+    let ws_url = if let Some(address) = ws_url.strip_prefix("https://") {
+        format!("wss://{}/ws", address)
+    } else if let Some(address) = ws_url.strip_prefix("http://") {
+        format!("ws://{}/ws", address)
+    } else {
+        bail!("Invalid url: {}", url);
+    };
+    let mut request = ws_url.into_client_request()?;
+    if let Some(auth_value) = auth_header {
+        request.headers_mut().insert(AUTHORIZATION, auth_value);
     }
+
+    let connector = if let Some(ssl_conf) = tls {
+        let mut connector_builder = native_tls::TlsConnector::builder();
+        if let Some(certificate) = &ssl_conf.root_certificate {
+            let buf = fs::read(certificate)?;
+            connector_builder.add_root_certificate(native_tls::Certificate::from_pem(&buf)?);
+        }
+        if let Some(identity) = &ssl_conf.identity {
+            let cert = fs::read(&identity.certificate)?;
+            let key = fs::read(&identity.key)?;
+            connector_builder.identity(native_tls::Identity::from_pkcs8(&cert, &key)?);
+        }
+        Some(Connector::NativeTls(connector_builder.build()?))
+    } else {
+        None
+    };
+
+    Ok(GatewayClient::with_websocket(WebSocketClient::new(
+        request,
+        connector,
+        response_timeout,
+    )))
+}
+
+fn new_grpc_client(
+    tls: Option<&ClientTlsConfiguration>,
+    url: &str,
+    auth_header: Option<HeaderValue>,
+    response_timeout: Duration,
+) -> anyhow::Result<GatewayClient> {
+    let mut endpoint = Endpoint::from_shared(url.to_string())?;
+
+    endpoint = if let Some(ssl_conf) = tls {
+        let mut tls_config = ClientTlsConfig::new();
+        if let Some(certificate) = &ssl_conf.root_certificate {
+            let buf = fs::read(certificate)?;
+            tls_config = tls_config.ca_certificate(tonic::transport::Certificate::from_pem(buf));
+        }
+        if let Some(identity) = &ssl_conf.identity {
+            let cert = fs::read(&identity.certificate)?;
+            let key = fs::read(&identity.key)?;
+            tls_config = tls_config.identity(tonic::transport::Identity::from_pem(cert, key));
+        }
+        endpoint.tls_config(tls_config)?
+    } else {
+        endpoint
+    };
+
+    let authorization = if let Some(auth_value) = auth_header {
+        let mut authorization = MetadataValue::try_from(auth_value.as_bytes())?;
+        authorization.set_sensitive(true);
+        Some(authorization)
+    } else {
+        None
+    };
+
+    Ok(GatewayClient::with_grpc(GrpcClient::new(
+        endpoint.connect_lazy(),
+        authorization,
+        response_timeout,
+    )))
 }
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use anyhow::anyhow;
+    use futures_util::{SinkExt, StreamExt};
     use rand::Rng;
     use reqwest::{Client, StatusCode};
     use savant_core::message::Message;
-    use wiremock::matchers::{body_bytes, method, path};
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+    use wiremock::matchers::{body_bytes, header, method, path};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
-    use media_gateway_common::model::Media;
+    use media_gateway_common::model::{
+        Media, MediaBatch, MediaBatchResult, MediaRequest, MediaResponse, MediaStatus,
+    };
 
-    use crate::client::{ForwardResult, GatewayClient};
+    use crate::client::{new_websocket_client, ForwardResult, GatewayClient};
+    use crate::compression::Compressor;
+    use crate::configuration::{CompressionAlgorithm, CompressionConfiguration, EndpointPolicy};
+    use crate::endpoint::EndpointSelector;
 
     #[tokio::test]
     async fn forward_message_success() {
@@ -169,11 +650,19 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn forward_message_invalid_http_status() {
-        let http_status = StatusCode::BAD_REQUEST;
+    async fn forward_message_bad_request() {
+        forward_test(
+            Some(StatusCode::BAD_REQUEST),
+            Ok(ForwardResult::HttpStatus(StatusCode::BAD_REQUEST)),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn forward_message_internal_server_error() {
         forward_test(
-            Some(http_status),
-            Err(anyhow!("Invalid HTTP status: {}", http_status)),
+            Some(StatusCode::INTERNAL_SERVER_ERROR),
+            Ok(ForwardResult::HttpStatus(StatusCode::INTERNAL_SERVER_ERROR)),
         )
         .await
     }
@@ -183,6 +672,245 @@ mod tests {
         forward_test(None, Err(anyhow!("Error while sending a message"))).await
     }
 
+    #[tokio::test]
+    async fn forward_batch_success() {
+        let batch = MediaBatch {
+            items: vec![new_media(), new_media(), new_media()],
+        };
+        let batch_result = MediaBatchResult {
+            statuses: vec![
+                MediaStatus::Success as i32,
+                MediaStatus::SendTimeout as i32,
+                MediaStatus::BadRequest as i32,
+            ],
+        };
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/batch"))
+            .and(body_bytes(
+                batch.to_proto().expect("http mock body setup failed"),
+            ))
+            .respond_with(
+                ResponseTemplate::new(StatusCode::OK).set_body_bytes(
+                    batch_result
+                        .to_proto()
+                        .expect("http mock response setup failed"),
+                ),
+            )
+            .mount(&server)
+            .await;
+        let client = GatewayClient::new(Client::default(), server.uri() + "/", None);
+
+        let results = client
+            .forward_batch(&batch)
+            .await
+            .expect("forward_batch failed");
+
+        assert_eq!(results.len(), 3);
+        assert!(matches!(results[0], Ok(ForwardResult::Success)));
+        assert!(matches!(results[1], Ok(ForwardResult::SendTimeout)));
+        assert!(matches!(
+            results[2],
+            Ok(ForwardResult::HttpStatus(StatusCode::BAD_REQUEST))
+        ));
+    }
+
+    #[tokio::test]
+    async fn forward_message_service_unavailable() {
+        forward_test(
+            Some(StatusCode::SERVICE_UNAVAILABLE),
+            Ok(ForwardResult::Overloaded {
+                status: StatusCode::SERVICE_UNAVAILABLE,
+                retry_after: None,
+            }),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn forward_batch_too_many_requests() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/batch"))
+            .respond_with(
+                ResponseTemplate::new(StatusCode::TOO_MANY_REQUESTS)
+                    .insert_header("retry-after", "3"),
+            )
+            .mount(&server)
+            .await;
+        let client = GatewayClient::new(Client::default(), server.uri(), None);
+        let batch = MediaBatch {
+            items: vec![new_media(), new_media()],
+        };
+
+        let results = client.forward_batch(&batch).await.unwrap();
+
+        assert_eq!(results.len(), 2);
+        for result in results {
+            assert!(matches!(
+                result,
+                Ok(ForwardResult::Overloaded {
+                    status: StatusCode::TOO_MANY_REQUESTS,
+                    retry_after: Some(delay),
+                }) if delay == Duration::from_secs(3)
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn forward_message_compressed() {
+        let media = new_media();
+        let compressor = Compressor::try_from(&CompressionConfiguration {
+            algorithm: CompressionAlgorithm::Zstd,
+            level: None,
+        })
+        .expect("compressor setup failed");
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(header("content-encoding", "zstd"))
+            .and(body_bytes(
+                zstd::bulk::compress(&media.to_proto().unwrap(), 3)
+                    .expect("http mock body setup failed"),
+            ))
+            .respond_with(ResponseTemplate::new(StatusCode::OK))
+            .mount(&server)
+            .await;
+        let client = GatewayClient::new(Client::default(), server.uri(), Some(compressor));
+
+        let result = client.forward_message(&media).await;
+
+        assert!(matches!(result, Ok(ForwardResult::Success)));
+    }
+
+    #[tokio::test]
+    async fn forward_message_failover() {
+        let primary = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(StatusCode::INTERNAL_SERVER_ERROR))
+            .mount(&primary)
+            .await;
+        let secondary = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(StatusCode::OK))
+            .mount(&secondary)
+            .await;
+        let selector = EndpointSelector::new(
+            vec![primary.uri(), secondary.uri()],
+            EndpointPolicy::Failover,
+            2,
+            None,
+        )
+        .expect("selector setup failed");
+        let client = GatewayClient::with_endpoints(
+            vec![
+                GatewayClient::new(Client::default(), primary.uri(), None),
+                GatewayClient::new(Client::default(), secondary.uri(), None),
+            ],
+            selector,
+        );
+        let media = new_media();
+
+        for _ in 0..2 {
+            assert!(matches!(
+                client.forward_message(&media).await,
+                Ok(ForwardResult::HttpStatus(StatusCode::INTERNAL_SERVER_ERROR))
+            ));
+        }
+        assert!(matches!(
+            client.forward_message(&media).await,
+            Ok(ForwardResult::Success)
+        ));
+        assert_eq!(primary.received_requests().await.unwrap().len(), 2);
+        assert_eq!(secondary.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn forward_message_websocket_failover() {
+        // the port of the primary endpoint is free after the listener is dropped
+        let primary = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let secondary = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let request = match stream.next().await {
+                Some(Ok(WsMessage::Binary(data))) => MediaRequest::from_proto(&data).unwrap(),
+                message => panic!("Unexpected message: {:?}", message),
+            };
+            let response = MediaResponse {
+                id: request.id,
+                status: MediaStatus::Success as i32,
+            };
+            stream
+                .send(WsMessage::Binary(response.to_proto().unwrap()))
+                .await
+                .unwrap();
+        });
+        let urls = vec![
+            format!("http://{}", primary),
+            format!("http://{}", secondary),
+        ];
+        let selector = EndpointSelector::new(urls.clone(), EndpointPolicy::Failover, 2, None)
+            .expect("selector setup failed");
+        let client = GatewayClient::with_endpoints(
+            urls.iter()
+                .map(|url| new_websocket_client(None, url, None, Duration::from_secs(1)).unwrap())
+                .collect(),
+            selector,
+        );
+        let media = new_media();
+
+        for _ in 0..2 {
+            assert!(client.forward_message(&media).await.is_err());
+        }
+        assert!(matches!(
+            client.forward_message(&media).await,
+            Ok(ForwardResult::Success)
+        ));
+        server.await.unwrap();
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn forward_batch_invalid_result() {
+        let batch = MediaBatch {
+            items: vec![new_media(), new_media()],
+        };
+        let batch_result = MediaBatchResult {
+            statuses: vec![MediaStatus::Success as i32],
+        };
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/batch"))
+            .respond_with(
+                ResponseTemplate::new(StatusCode::OK).set_body_bytes(
+                    batch_result
+                        .to_proto()
+                        .expect("http mock response setup failed"),
+                ),
+            )
+            .mount(&server)
+            .await;
+        let client = GatewayClient::new(Client::default(), server.uri(), None);
+
+        let result = client.forward_batch(&batch).await;
+
+        assert!(result.is_err());
+    }
+
+    fn new_media() -> Media {
+        let message = Message::unknown("message".to_string());
+        Media {
+            message: Option::from(savant_protobuf::generated::Message::from(&message)),
+            topic: "topic".as_bytes().to_vec(),
+            data: vec![vec![1]],
+        }
+    }
+
     async fn forward_test(
         http_status: Option<StatusCode>,
         expected_result: anyhow::Result<ForwardResult>,
@@ -216,7 +944,7 @@ mod tests {
             )
         };
 
-        let client = GatewayClient::new(Client::default(), gateway_url);
+        let client = GatewayClient::new(Client::default(), gateway_url, None);
 
         let actual_result = client.forward_message(&media).await;
 
@@ -234,6 +962,20 @@ mod tests {
                     ForwardResult::AckTimeout => {
                         assert!(matches!(expected_forward_result, ForwardResult::AckTimeout))
                     }
+                    ForwardResult::HttpStatus(status) => assert!(matches!(
+                        expected_forward_result,
+                        ForwardResult::HttpStatus(e) if e == status
+                    )),
+                    ForwardResult::Overloaded {
+                        status,
+                        retry_after,
+                    } => assert!(matches!(
+                        expected_forward_result,
+                        ForwardResult::Overloaded {
+                            status: expected_status,
+                            retry_after: expected_retry_after,
+                        } if expected_status == status && expected_retry_after == retry_after
+                    )),
                 }
             }
             Err(expected_error) => {
@@ -0,0 +1,134 @@
+//! Compression of request bodies.
+//!
+//! The module provides [`Compressor`].
+use std::io::Write;
+use std::time::Instant;
+
+use anyhow::bail;
+use flate2::write::GzEncoder;
+
+use crate::configuration::{CompressionAlgorithm, CompressionConfiguration};
+
+const DEFAULT_ZSTD_LEVEL: i32 = 3;
+const DEFAULT_GZIP_LEVEL: i32 = 6;
+
+/// Compresses request bodies with the configured algorithm and level.
+#[derive(Debug, Clone)]
+pub struct Compressor {
+    algorithm: CompressionAlgorithm,
+    level: i32,
+}
+
+impl Compressor {
+    /// Returns a value for `Content-Encoding` header.
+    pub fn content_encoding(&self) -> &'static str {
+        match self.algorithm {
+            CompressionAlgorithm::Zstd => "zstd",
+            CompressionAlgorithm::Gzip => "gzip",
+        }
+    }
+
+    /// Compresses the data. The compression ratio and time are logged.
+    pub fn compress(&self, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let start = Instant::now();
+        let compressed = match self.algorithm {
+            CompressionAlgorithm::Zstd => zstd::bulk::compress(data, self.level)?,
+            CompressionAlgorithm::Gzip => {
+                let mut encoder = GzEncoder::new(
+                    Vec::with_capacity(data.len()),
+                    flate2::Compression::new(self.level as u32),
+                );
+                encoder.write_all(data)?;
+                encoder.finish()?
+            }
+        };
+        log::debug!(
+            "Compressed {} bytes to {} bytes (ratio={:.2}) using {} in {} microseconds",
+            data.len(),
+            compressed.len(),
+            data.len() as f64 / compressed.len().max(1) as f64,
+            self.content_encoding(),
+            start.elapsed().as_micros()
+        );
+        Ok(compressed)
+    }
+}
+
+impl TryFrom<&CompressionConfiguration> for Compressor {
+    type Error = anyhow::Error;
+
+    fn try_from(configuration: &CompressionConfiguration) -> Result<Self, Self::Error> {
+        let level = match configuration.algorithm {
+            CompressionAlgorithm::Zstd => {
+                let level = configuration.level.unwrap_or(DEFAULT_ZSTD_LEVEL);
+                if !zstd::compression_level_range().contains(&level) {
+                    bail!("Invalid zstd compression level: {}", level);
+                }
+                level
+            }
+            CompressionAlgorithm::Gzip => {
+                let level = configuration.level.unwrap_or(DEFAULT_GZIP_LEVEL);
+                if !(0..=9).contains(&level) {
+                    bail!("Invalid gzip compression level: {}", level);
+                }
+                level
+            }
+        };
+        Ok(Self {
+            algorithm: configuration.algorithm.clone(),
+            level,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use crate::compression::Compressor;
+    use crate::configuration::{CompressionAlgorithm, CompressionConfiguration};
+
+    const DATA: &[u8] = &[7; 4096];
+
+    #[test]
+    fn compress_zstd() {
+        let compressor = new_compressor(CompressionAlgorithm::Zstd, Some(5));
+
+        let compressed = compressor.compress(DATA).unwrap();
+
+        assert_eq!(compressor.content_encoding(), "zstd");
+        assert!(compressed.len() < DATA.len());
+        assert_eq!(zstd::decode_all(compressed.as_slice()).unwrap(), DATA);
+    }
+
+    #[test]
+    fn compress_gzip() {
+        let compressor = new_compressor(CompressionAlgorithm::Gzip, None);
+
+        let compressed = compressor.compress(DATA).unwrap();
+
+        let mut decompressed = Vec::new();
+        GzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(compressor.content_encoding(), "gzip");
+        assert!(compressed.len() < DATA.len());
+        assert_eq!(decompressed, DATA);
+    }
+
+    #[test]
+    fn invalid_level() {
+        let configuration = CompressionConfiguration {
+            algorithm: CompressionAlgorithm::Gzip,
+            level: Some(10),
+        };
+
+        assert!(Compressor::try_from(&configuration).is_err());
+    }
+
+    fn new_compressor(algorithm: CompressionAlgorithm, level: Option<i32>) -> Compressor {
+        Compressor::try_from(&CompressionConfiguration { algorithm, level }).unwrap()
+    }
+}
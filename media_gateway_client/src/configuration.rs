@@ -1,18 +1,19 @@
 //! The media gateway client configuration.
 //!
 //! The module provides [`GatewayClientConfiguration`].
+use std::collections::HashMap;
 use std::time::Duration;
 
-use savant_core::transport::zeromq::{NonBlockingReader, ReaderConfigBuilder};
+use savant_core::message::label_filter::LabelFilterRule;
+use savant_core::transport::zeromq::{ReaderConfig, ReaderConfigBuilder};
 use serde::{Deserialize, Serialize};
 use twelf::{config, Layer};
 
 use media_gateway_common::configuration::{
-    ClientTlsConfiguration, Credentials, StatisticsConfiguration,
+    ClientTlsConfiguration, Credentials, SinkConfiguration, StatisticsConfiguration,
 };
 
-use crate::retry::RetryStrategy;
-use crate::wait::WaitStrategy;
+use crate::retry::{RetryPolicy, RetryStrategy};
 
 /// Authentication settings to connect to the media gateway server.
 #[derive(Debug, Serialize, Deserialize)]
@@ -29,20 +30,95 @@ pub struct GatewayClientConfiguration {
     pub ip: String,
     /// A port to bind to
     pub port: u16,
-    /// An endpoint of the media gateway service to accept messages
+    /// An endpoint of the media gateway service to accept messages. If additional endpoints are
+    /// specified it is the first (primary) one.
     pub url: String,
+    /// Additional endpoints of the media gateway service and a policy to select an endpoint
+    pub endpoints: Option<EndpointsConfiguration>,
     /// A strategy how to retry to send a message to the media gateway service
     pub retry_strategy: Option<RetryStrategy>,
-    /// Reader configuration
-    pub in_stream: SourceConfiguration,
-    /// A strategy how to wait for data while reading
-    pub wait_strategy: Option<WaitStrategy>,
+    /// Actions for outcomes of unsuccessful attempts to send a message
+    pub retry_policy: Option<RetryPolicy>,
+    /// Reader configuration. Exactly one of `in_stream` and `in_streams` should be specified.
+    pub in_stream: Option<SourceConfiguration>,
+    /// Reader configurations of named sources. Messages from all sources are forwarded by the
+    /// same sender.
+    pub in_streams: Option<Vec<NamedSourceConfiguration>>,
+    /// Deprecated and ignored since sources are read by dedicated blocking threads. A warning is
+    /// logged if it is specified.
+    pub wait_strategy: Option<serde_json::Value>,
     /// TLS settings
     pub tls: Option<ClientTlsConfiguration>,
     /// Authentication settings
     pub auth: Option<AuthConfiguration>,
     /// Statistics settings
     pub statistics: Option<StatisticsConfiguration>,
+    /// Batch settings. If specified messages are forwarded in batches.
+    pub batch: Option<BatchConfiguration>,
+    /// The maximum number of concurrent requests to the media gateway service. If specified
+    /// messages from different sources are forwarded independently, messages from the same
+    /// source are forwarded in order.
+    pub max_inflight: Option<usize>,
+    /// `true` if HTTP/2 should be used without negotiation (for plain HTTP connections)
+    pub http2_prior_knowledge: Option<bool>,
+    /// A transport to forward messages. [`Transport::Http`] by default.
+    pub transport: Option<Transport>,
+    /// Compression settings. If specified request bodies are compressed (only for
+    /// [`Transport::Http`]).
+    pub compression: Option<CompressionConfiguration>,
+    /// The maximum time to wait for the response to a message sent via
+    /// [`Transport::WebSocket`] or [`Transport::Grpc`]. If the response is not received in time
+    /// the attempt fails with an acknowledgement timeout. 30 seconds by default.
+    pub response_timeout: Option<Duration>,
+    /// Spool settings. If specified messages are persisted on disk until they are accepted by
+    /// the media gateway service.
+    pub spool: Option<SpoolConfiguration>,
+    /// Limits after which a message is not retried any more. If not specified messages are
+    /// retried until they are accepted or rejected by the media gateway service.
+    pub expiry: Option<ExpiryConfiguration>,
+    /// A sink for messages that are expired or rejected by the media gateway service. If not
+    /// specified such messages are dropped.
+    pub dead_letter: Option<DeadLetterConfiguration>,
+    /// Routes of messages to other media gateway services. A message is forwarded by the first
+    /// route it matches or to [`GatewayClientConfiguration::url`] if it matches no routes.
+    pub routes: Option<Vec<RouteConfiguration>>,
+    /// Sampling settings. If specified video frames are sampled before they are queued.
+    pub sampling: Option<SamplingConfiguration>,
+    /// A policy of the queue of messages between readers and the sender and queues of sources
+    /// and routes forwarded independently if they are full. [`QueueOverflowPolicy::Block`] by
+    /// default. Not applied if the spool is specified.
+    pub queue_overflow: Option<QueueOverflowPolicy>,
+    /// Priority classes of messages. If specified messages of each class are queued separately
+    /// and forwarded according to priorities of classes. Not applied if the spool is specified.
+    pub priorities: Option<PriorityConfiguration>,
+    /// Weights of sources. If specified messages of different sources are forwarded by deficit
+    /// round-robin according to their weights, otherwise in order of arrival. Not applied if
+    /// the spool is specified.
+    pub fair_queuing: Option<FairQueuingConfiguration>,
+    /// Bandwidth shaping settings. If specified the number of bytes of encoded messages sent per
+    /// second is limited.
+    pub shaping: Option<ShapingConfiguration>,
+    /// Congestion control settings. If specified frame rates of sources are adjusted to the
+    /// measured state of the link.
+    pub congestion_control: Option<CongestionControlConfiguration>,
+    /// `true` if video frames forwarded to [`GatewayClientConfiguration::url`] should be
+    /// stripped of their content and data. `false` by default.
+    pub metadata_only: Option<bool>,
+    /// Transcoding settings. If specified JPEG or PNG images in video frames are downscaled and
+    /// re-encoded before they are queued.
+    pub transcoding: Option<TranscodingConfiguration>,
+    /// Deduplication settings. If specified consecutive duplicate video frames of each source
+    /// are suppressed before they are queued.
+    pub deduplication: Option<DeduplicationConfiguration>,
+    /// Enrichment settings. If specified messages are stamped with routing labels and
+    /// propagated context entries before they are queued.
+    pub enrichment: Option<EnrichmentConfiguration>,
+    /// Ingest settings. If specified messages can be sent to the client via HTTP in addition to
+    /// ZeroMQ sources.
+    pub ingest: Option<IngestConfiguration>,
+    /// Upload schedule settings. If specified messages are buffered during configured time
+    /// windows and forwarded outside of them.
+    pub upload_schedule: Option<UploadScheduleConfiguration>,
 }
 
 impl GatewayClientConfiguration {
@@ -59,6 +135,440 @@ impl GatewayClientConfiguration {
     }
 }
 
+/// Settings of the HTTP endpoint to ingest messages.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IngestConfiguration {
+    /// `true` if only requests from loopback addresses are accepted. `true` by default.
+    pub local_only: Option<bool>,
+    /// Authentication settings of ingest requests. If not specified requests are not
+    /// authenticated.
+    pub auth: Option<AuthConfiguration>,
+    /// The maximum size of a request body in bytes. 16 MiB by default.
+    pub max_body_size: Option<usize>,
+}
+
+/// A transport to forward messages to the media gateway server.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum Transport {
+    /// Each message (or batch) is sent in a separate HTTP request.
+    #[serde(rename = "http")]
+    Http,
+    /// Messages are streamed over a single WebSocket connection.
+    #[serde(rename = "websocket")]
+    WebSocket,
+    /// Messages are streamed over a single bidirectional gRPC stream. `url` is the gRPC endpoint
+    /// of the media gateway server.
+    #[serde(rename = "grpc")]
+    Grpc,
+}
+
+/// Settings of additional endpoints of the media gateway service.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EndpointsConfiguration {
+    /// Endpoints in addition to [`GatewayClientConfiguration::url`] in order of priority
+    pub urls: Vec<String>,
+    /// A policy to select an endpoint for each request
+    pub policy: EndpointPolicy,
+    /// The number of consecutive errors after which an endpoint is marked down. 3 by default.
+    pub failure_threshold: Option<u32>,
+    /// A period between health checks of an endpoint marked down. 5 seconds by default.
+    pub health_check_period: Option<Duration>,
+}
+
+/// A policy to select an endpoint of the media gateway service. Endpoints marked down are not
+/// selected unless all endpoints are down.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum EndpointPolicy {
+    /// The first endpoint in order of priority
+    #[serde(rename = "failover")]
+    Failover,
+    /// Endpoints in turn
+    #[serde(rename = "round_robin")]
+    RoundRobin,
+    /// The endpoint with the least average latency
+    #[serde(rename = "least_latency")]
+    LeastLatency,
+}
+
+/// A route of messages to a separate media gateway service. Messages of each route are forwarded
+/// and retried independently.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RouteConfiguration {
+    /// A unique name of the route used in logs
+    pub name: String,
+    /// Conditions a message should match to be forwarded by the route
+    pub filter: RouteFilter,
+    /// An endpoint of the media gateway service to accept messages of the route
+    pub url: String,
+    /// TLS settings of the route
+    pub tls: Option<ClientTlsConfiguration>,
+    /// Authentication settings of the route
+    pub auth: Option<AuthConfiguration>,
+    /// `true` if video frames forwarded by the route should be stripped of their content and
+    /// data. `false` by default.
+    pub metadata_only: Option<bool>,
+}
+
+/// Conditions to match a message. A message matches if it meets all specified conditions.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RouteFilter {
+    /// A ZeroMQ topic
+    pub topic: Option<String>,
+    /// A source id of a video frame, user data or end of stream message
+    pub source_id: Option<String>,
+    /// A rule for routing labels of a message
+    pub routing_labels: Option<LabelFilterRule>,
+    /// A message type
+    pub message_type: Option<MessageType>,
+}
+
+/// A type of [`Message`](savant_core::message::Message).
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum MessageType {
+    #[serde(rename = "video_frame")]
+    VideoFrame,
+    #[serde(rename = "video_frame_batch")]
+    VideoFrameBatch,
+    #[serde(rename = "video_frame_update")]
+    VideoFrameUpdate,
+    #[serde(rename = "user_data")]
+    UserData,
+    #[serde(rename = "end_of_stream")]
+    EndOfStream,
+    #[serde(rename = "shutdown")]
+    Shutdown,
+    #[serde(rename = "unknown")]
+    Unknown,
+}
+
+/// Priority classes of messages.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PriorityConfiguration {
+    /// Classes in order of priority (the first one has the highest priority). A message belongs
+    /// to the first class it matches. Messages matching no classes have the lowest priority.
+    pub classes: Vec<PriorityClassConfiguration>,
+    /// A mode to drain queues of classes. [`PriorityMode::Strict`] by default.
+    pub mode: Option<PriorityMode>,
+    /// A weight of messages matching no classes for [`PriorityMode::Weighted`]. 1 by default.
+    pub default_weight: Option<u32>,
+}
+
+/// A priority class of messages.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PriorityClassConfiguration {
+    /// A unique name of the class
+    pub name: String,
+    /// Conditions a message should match to belong to the class
+    pub filter: RouteFilter,
+    /// A weight of the class for [`PriorityMode::Weighted`]. 1 by default.
+    pub weight: Option<u32>,
+}
+
+/// A mode to drain queues of priority classes.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum PriorityMode {
+    /// A message is taken from the queue of a class only if queues of all classes with higher
+    /// priorities are empty.
+    #[serde(rename = "strict")]
+    #[default]
+    Strict,
+    /// Queues are drained in turn, the number of messages taken from the queue of a class at a
+    /// time is equal to its weight.
+    #[serde(rename = "weighted")]
+    Weighted,
+}
+
+/// Settings of deficit round-robin between sources.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FairQueuingConfiguration {
+    /// The number of bytes a source with weight 1 can send in its turn. 65536 by default.
+    pub quantum: Option<usize>,
+    /// A weight of sources without specified weights. 1 by default.
+    pub default_weight: Option<u32>,
+    /// Weights by source ids
+    pub sources: Option<HashMap<String, u32>>,
+    /// Weights by prefixes of source ids. If several prefixes match the longest one is applied.
+    pub prefixes: Option<HashMap<String, u32>>,
+}
+
+/// Settings of a token bucket to limit the bandwidth.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShapingConfiguration {
+    /// The maximum number of bytes per second
+    pub rate: u64,
+    /// The maximum number of bytes sent at once. [`ShapingConfiguration::rate`] by default.
+    pub burst: Option<u64>,
+    /// A policy if the limit is reached. [`ShapingPolicy::Wait`] by default.
+    pub policy: Option<ShapingPolicy>,
+}
+
+/// A policy how to handle messages if the bandwidth limit is reached.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum ShapingPolicy {
+    /// Messages wait until they can be sent.
+    #[serde(rename = "wait")]
+    Wait,
+    /// Video frames rejected by sampling policies are dropped, other messages wait.
+    #[serde(rename = "sample")]
+    Sample(SamplingConfiguration),
+}
+
+/// Settings of the adaptive control of frame rates of sources.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CongestionControlConfiguration {
+    /// The minimum number of frames per second of a source
+    pub min_fps: f64,
+    /// The maximum number of frames per second of a source
+    pub max_fps: f64,
+    /// A period between adjustments of frame rates. 1 second by default.
+    pub period: Option<Duration>,
+    /// The number of frames per second added to frame rates if the link is not congested. 1 by
+    /// default.
+    pub increase: Option<f64>,
+    /// A factor by which frame rates are multiplied if the link is congested. 0.5 by default.
+    pub decrease: Option<f64>,
+    /// The maximum average latency of requests. If exceeded the link is congested.
+    pub max_latency: Option<Duration>,
+    /// The maximum growth of the queue of messages per period. If exceeded the link is
+    /// congested.
+    pub max_queue_growth: Option<usize>,
+}
+
+/// Sampling policies of video frames. Other messages are not sampled.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SamplingConfiguration {
+    /// A policy for sources without their own policies. If not specified such sources are not
+    /// sampled.
+    pub default: Option<SamplingPolicy>,
+    /// Policies by source ids
+    pub sources: Option<HashMap<String, SamplingPolicy>>,
+}
+
+/// A sampling policy. A frame is forwarded if it meets all specified conditions which are
+/// checked in the order of fields.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SamplingPolicy {
+    /// `true` if only keyframes should be forwarded
+    pub keyframes_only: Option<bool>,
+    /// Every Nth frame is forwarded starting with the first one
+    pub every_nth: Option<u64>,
+    /// The maximum number of frames per second based on frame PTS
+    pub max_fps: Option<f64>,
+}
+
+/// Data to stamp messages with. Values of routing labels and propagated context entries are
+/// templates where `${NAME}` placeholders are replaced with values of environment variables
+/// (`HOSTNAME` is the host name of the system if the environment variable is not set).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EnrichmentConfiguration {
+    /// Routing labels added to messages
+    pub routing_labels: Option<Vec<String>>,
+    /// Entries set in propagated context of messages
+    pub propagated_context: Option<HashMap<String, String>>,
+    /// A key of the propagated context entry with the local receive timestamp in nanoseconds
+    /// since the Unix epoch. If not specified the timestamp is not added.
+    pub receive_timestamp_key: Option<String>,
+}
+
+/// Settings of suppression of duplicate video frames.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeduplicationConfiguration {
+    /// The maximum interval between forwarded frames of a source. A duplicate frame is forwarded
+    /// if no frames of the source were forwarded during the interval. 1 second by default.
+    pub heartbeat: Option<Duration>,
+}
+
+/// Transcoding policies of video frames with JPEG or PNG images as their content. Other
+/// messages are not transcoded.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TranscodingConfiguration {
+    /// A policy for sources without their own policies. If not specified such sources are not
+    /// transcoded.
+    pub default: Option<TranscodingPolicy>,
+    /// Policies by source ids
+    pub sources: Option<HashMap<String, TranscodingPolicy>>,
+}
+
+/// A transcoding policy. An image larger than the maximum resolution is downscaled preserving
+/// its aspect ratio. The image is re-encoded to JPEG if it is downscaled, it is PNG or the
+/// quality is specified.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TranscodingPolicy {
+    /// The maximum width of the image
+    pub max_width: Option<u32>,
+    /// The maximum height of the image
+    pub max_height: Option<u32>,
+    /// JPEG quality from 1 to 100. 75 by default.
+    pub quality: Option<u8>,
+}
+
+/// Time windows when messages are buffered instead of being forwarded. Messages buffered in the
+/// queue are handled according to [`GatewayClientConfiguration::queue_overflow`] if it is full.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UploadScheduleConfiguration {
+    /// An IANA name of the timezone of schedules, e.g. `Europe/Berlin`. `UTC` by default.
+    pub timezone: Option<String>,
+    /// Windows when messages are buffered
+    pub buffer_windows: Vec<BufferWindowConfiguration>,
+    /// Classes of messages forwarded during buffer windows. [`ScheduleClass::Control`] by
+    /// default.
+    pub always_forward: Option<Vec<ScheduleClass>>,
+    /// The maximum number of messages in each lane of the queue. If not specified the sum of
+    /// `inflight_ops` of sources.
+    pub buffer_size: Option<usize>,
+}
+
+/// A time window when messages are buffered.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BufferWindowConfiguration {
+    /// A cron expression of starts of the window with fields for seconds, minutes, hours, days
+    /// of month, months and days of week, e.g. `0 0 9 * * Mon-Fri`
+    pub start: String,
+    /// A duration of the window
+    pub duration: Duration,
+}
+
+/// A class of messages for upload schedules.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum ScheduleClass {
+    /// End of stream and shutdown messages
+    #[serde(rename = "control")]
+    Control,
+    /// Video frames
+    #[serde(rename = "video_frame")]
+    VideoFrame,
+    /// Other messages, e.g. video frame updates and user data
+    #[serde(rename = "metadata")]
+    Metadata,
+}
+
+/// A policy how to handle new messages if the queue of messages is full. End of stream and
+/// shutdown messages are never dropped.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum QueueOverflowPolicy {
+    /// Readers wait for free space.
+    #[serde(rename = "block")]
+    #[default]
+    Block,
+    /// The oldest message in the queue is dropped.
+    #[serde(rename = "drop_oldest")]
+    DropOldest,
+    /// The new message is dropped.
+    #[serde(rename = "drop_newest")]
+    DropNewest,
+    /// Video frames are dropped by GOPs: the oldest frame in the queue and following
+    /// non-keyframes of the same source are dropped up to the next keyframe of the source.
+    #[serde(rename = "drop_gop")]
+    DropGop,
+}
+
+/// Settings to forward messages in batches. A batch is sent as soon as one of the limits is
+/// reached.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BatchConfiguration {
+    /// The maximum number of messages in a batch
+    pub max_count: usize,
+    /// The maximum total size of serialized messages in a batch in bytes
+    pub max_bytes: usize,
+    /// The maximum time to wait for next messages after the first message in a batch is received
+    pub max_linger: Duration,
+}
+
+/// Settings to compress request bodies.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CompressionConfiguration {
+    /// A compression algorithm
+    pub algorithm: CompressionAlgorithm,
+    /// A compression level. 3 for zstd and 6 for gzip by default.
+    pub level: Option<i32>,
+}
+
+/// A compression algorithm. The algorithm is passed to the server in `Content-Encoding` header.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum CompressionAlgorithm {
+    #[serde(rename = "zstd")]
+    Zstd,
+    #[serde(rename = "gzip")]
+    Gzip,
+}
+
+/// Settings of a disk-backed spool.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SpoolConfiguration {
+    /// A directory to store spool segments
+    pub directory: String,
+    /// The size of a segment in bytes after which a new segment is started
+    pub segment_size: u64,
+    /// The maximum total size of segments in bytes
+    pub max_size: u64,
+    /// What to do if the spool is full. [`SpoolEviction::DropOldest`] by default.
+    pub eviction: Option<SpoolEviction>,
+    /// An interval after which the segment being written is synced to disk on the next append.
+    /// Segments are synced only when they are full or the spool is closed by default.
+    pub sync_interval: Option<Duration>,
+}
+
+/// A policy what to do if the spool is full.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum SpoolEviction {
+    /// The oldest segment is deleted including unacknowledged messages.
+    #[serde(rename = "drop_oldest")]
+    DropOldest,
+    /// The new message is dropped.
+    #[serde(rename = "drop_newest")]
+    DropNewest,
+}
+
+/// Limits after which a message is not retried any more.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExpiryConfiguration {
+    /// The maximum number of attempts to send a message
+    pub max_attempts: Option<u32>,
+    /// The maximum age of a message
+    pub max_age: Option<Duration>,
+    /// A time from which the age of a message is measured. [`AgeSource::ReceiveTime`] by default.
+    pub age_source: Option<AgeSource>,
+}
+
+/// A time from which the age of a message is measured.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum AgeSource {
+    /// The time when the message is received from ZeroMQ
+    #[serde(rename = "receive_time")]
+    ReceiveTime,
+    /// The creation timestamp of the video frame. The receive time is used for other messages.
+    #[serde(rename = "frame_timestamp")]
+    FrameTimestamp,
+}
+
+/// A sink for messages that are not forwarded.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum DeadLetterConfiguration {
+    /// Messages are appended to the file as
+    /// [`DeadLetter`](media_gateway_common::model::DeadLetter) records.
+    #[serde(rename = "file")]
+    File {
+        /// A path to the file
+        path: String,
+    },
+    /// Messages are written to the ZeroMQ socket. The reason is appended to extra data.
+    #[serde(rename = "zeromq")]
+    ZeroMq(SinkConfiguration),
+}
+
+/// A configuration of a named source of messages.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NamedSourceConfiguration {
+    /// A unique name of the source used in logs and statistics
+    pub name: String,
+    /// Reader configuration
+    #[serde(flatten)]
+    pub source: SourceConfiguration,
+    /// Deprecated and ignored since the source is read by a dedicated blocking thread. A warning
+    /// is logged if it is specified.
+    pub wait_strategy: Option<serde_json::Value>,
+}
+
 // copy-paste from Replay (except documentation) and visibility of SourceConfiguration fields
 /// A representation for [`savant_core::transport::zeromq::TopicPrefixSpec`].
 #[derive(Debug, Serialize, Deserialize)]
@@ -98,14 +608,14 @@ pub struct SourceConfiguration {
     pub source_cache_size: usize,
     /// Permissions for the IPC endpoint. See [`std::fs::set_permissions`].
     pub fix_ipc_permissions: Option<u32>,
-    /// The maximum number of read messages
+    /// The maximum number of received messages waiting to be processed
     pub inflight_ops: usize,
 }
 
-impl TryFrom<&SourceConfiguration> for NonBlockingReader {
+impl TryFrom<&SourceConfiguration> for ReaderConfig {
     type Error = anyhow::Error;
 
-    fn try_from(source_conf: &SourceConfiguration) -> Result<NonBlockingReader, Self::Error> {
+    fn try_from(source_conf: &SourceConfiguration) -> Result<ReaderConfig, Self::Error> {
         let conf = ReaderConfigBuilder::default().url(&source_conf.url)?;
         let conf = if let Some(fix_ipc_permissions) = source_conf.fix_ipc_permissions {
             conf.with_fix_ipc_permissions(Some(fix_ipc_permissions))?
@@ -118,10 +628,7 @@ impl TryFrom<&SourceConfiguration> for NonBlockingReader {
             .with_topic_prefix_spec((&source_conf.topic_prefix_spec).into())?
             .with_routing_cache_size(source_conf.source_cache_size)?
             .build()?;
-
-        let mut reader = NonBlockingReader::new(&conf, source_conf.inflight_ops)?;
-        reader.start()?;
-        Ok(reader)
+        Ok(conf)
     }
 }
 impl From<&TopicPrefixSpec> for savant_core::transport::zeromq::TopicPrefixSpec {
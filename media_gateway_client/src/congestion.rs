@@ -0,0 +1,396 @@
+//! Adaptive control of the frame rate.
+//!
+//! The module provides [`CongestionController`].
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+use savant_core::message::Message;
+use serde::Serialize;
+use tokio_timerfd::sleep;
+
+use crate::client::ForwardResult;
+use crate::configuration::CongestionControlConfiguration;
+use crate::queue::MessageQueue;
+
+const DEFAULT_PERIOD: Duration = Duration::from_secs(1);
+const DEFAULT_INCREASE: f64 = 1.0;
+const DEFAULT_DECREASE: f64 = 0.5;
+/// The number of periods without frames after which the state of a source is forgotten
+const STALE_SOURCE_PERIODS: u32 = 10;
+
+/// Controls the frame rate of each source by additive increase/multiplicative decrease (AIMD).
+/// The link is considered congested during a period if a message is not sent because of a
+/// timeout or the server overload, the average latency of requests exceeds the limit or the
+/// queue of messages grows more than the limit. At the end of each period frame rates of all
+/// sources are multiplied by the decrease factor if the link is congested or increased by the
+/// increase step otherwise within the floor and the ceiling. Frames exceeding the frame rate of
+/// the source are dropped.
+///
+/// The decision is global: outcomes of requests are not attributed to sources or destinations,
+/// so congestion of any destination (e.g. a slow route) decreases frame rates of all sources.
+/// The state of a source which sent no frames during several periods is forgotten, so its
+/// frame rate starts from the ceiling when it sends frames again.
+pub struct CongestionController {
+    min_fps: f64,
+    max_fps: f64,
+    period: Duration,
+    increase: f64,
+    decrease: f64,
+    max_latency: Option<Duration>,
+    max_queue_growth: Option<usize>,
+    state: Mutex<ControllerState>,
+}
+
+#[derive(Default)]
+struct ControllerState {
+    sources: HashMap<String, SourceState>,
+    /// Outcomes of requests in the current period
+    requests: u64,
+    latency: Duration,
+    failures: u64,
+    queue_depth: usize,
+    increases: u64,
+    decreases: u64,
+    last_decrease_reason: Option<String>,
+}
+
+struct SourceState {
+    fps: f64,
+    last_forwarded: Option<Instant>,
+    /// The time of the last frame whether it is forwarded or not
+    last_received: Instant,
+}
+
+/// The current state of [`CongestionController`].
+#[derive(Debug, Serialize, PartialEq)]
+pub struct CongestionStats {
+    /// Target frame rates by source ids
+    pub sources: HashMap<String, f64>,
+    /// The number of periods frame rates are increased
+    pub increases: u64,
+    /// The number of periods frame rates are decreased
+    pub decreases: u64,
+    /// The reason of the last decrease
+    pub last_decrease_reason: Option<String>,
+}
+
+impl CongestionController {
+    /// Constructs a new instance.
+    ///
+    /// # Arguments
+    /// * `min_fps` - the floor of the frame rate of a source
+    /// * `max_fps` - the ceiling of the frame rate of a source (the initial frame rate)
+    /// * `period` - a period between adjustments
+    /// * `increase` - a step to increase the frame rate
+    /// * `decrease` - a factor to decrease the frame rate
+    /// * `max_latency` - the maximum average latency of requests
+    /// * `max_queue_growth` - the maximum growth of the queue of messages per period
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        min_fps: f64,
+        max_fps: f64,
+        period: Duration,
+        increase: f64,
+        decrease: f64,
+        max_latency: Option<Duration>,
+        max_queue_growth: Option<usize>,
+    ) -> Self {
+        Self {
+            min_fps,
+            max_fps,
+            period,
+            increase,
+            decrease,
+            max_latency,
+            max_queue_growth,
+            state: Mutex::new(ControllerState::default()),
+        }
+    }
+
+    /// Returns `true` if the message should be forwarded. Only video frames are controlled,
+    /// other messages are always forwarded.
+    pub fn accept(&self, message: &Message) -> bool {
+        let frame = match message.as_video_frame() {
+            Some(frame) => frame,
+            None => return true,
+        };
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let source = state
+            .sources
+            .entry(frame.get_source_id())
+            .or_insert(SourceState {
+                fps: self.max_fps,
+                last_forwarded: None,
+                last_received: now,
+            });
+        source.last_received = now;
+        if let Some(last_forwarded) = source.last_forwarded {
+            if now.duration_since(last_forwarded).as_secs_f64() < 1.0 / source.fps {
+                return false;
+            }
+        }
+        source.last_forwarded = Some(now);
+        true
+    }
+
+    /// Registers the outcome of a request for a message or a batch.
+    ///
+    /// # Arguments
+    /// * `results` - results of messages
+    /// * `latency` - the duration of the request
+    pub fn register<'a>(
+        &self,
+        results: impl IntoIterator<Item = &'a anyhow::Result<ForwardResult>>,
+        latency: Duration,
+    ) {
+        let failures = results
+            .into_iter()
+            .filter(|e| {
+                matches!(
+                    e,
+                    Ok(ForwardResult::SendTimeout
+                        | ForwardResult::AckTimeout
+                        | ForwardResult::Overloaded { .. })
+                )
+            })
+            .count();
+        let mut state = self.state.lock().unwrap();
+        state.requests += 1;
+        state.latency += latency;
+        state.failures += failures as u64;
+    }
+
+    /// Adjusts frame rates periodically until the service is stopped.
+    pub async fn run(&self, queue: &MessageQueue, stopped: &OnceLock<()>) {
+        while stopped.get().is_none() {
+            sleep(self.period)
+                .await
+                .expect("Error while waiting for frame rate adjustment");
+            let queue_depth = queue.depths().values().sum();
+            self.adjust(queue_depth);
+        }
+    }
+
+    /// Returns the current state.
+    pub fn stats(&self) -> CongestionStats {
+        let state = self.state.lock().unwrap();
+        CongestionStats {
+            sources: state
+                .sources
+                .iter()
+                .map(|(source_id, source)| (source_id.clone(), source.fps))
+                .collect(),
+            increases: state.increases,
+            decreases: state.decreases,
+            last_decrease_reason: state.last_decrease_reason.clone(),
+        }
+    }
+
+    /// Increases or decreases frame rates according to outcomes in the current period and
+    /// starts a new period. States of stale sources are removed.
+    fn adjust(&self, queue_depth: usize) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let stale_after = self.period * STALE_SOURCE_PERIODS;
+        state
+            .sources
+            .retain(|_, source| now.duration_since(source.last_received) < stale_after);
+        let queue_growth = queue_depth.saturating_sub(state.queue_depth);
+        let latency = if state.requests > 0 {
+            Some(state.latency / state.requests as u32)
+        } else {
+            None
+        };
+        let reason = if state.failures > 0 {
+            Some(format!("{} timeouts or overloads", state.failures))
+        } else if let (Some(latency), Some(max_latency)) = (latency, self.max_latency) {
+            (latency > max_latency).then(|| format!("latency {:?}", latency))
+        } else {
+            None
+        }
+        .or_else(|| {
+            self.max_queue_growth
+                .filter(|e| queue_growth > *e)
+                .map(|_| format!("queue growth {}", queue_growth))
+        });
+        for source in state.sources.values_mut() {
+            source.fps = match reason {
+                Some(_) => (source.fps * self.decrease).max(self.min_fps),
+                None => (source.fps + self.increase).min(self.max_fps),
+            };
+        }
+        match reason {
+            Some(reason) => {
+                log::info!("Frame rates are decreased: {}", reason);
+                state.decreases += 1;
+                state.last_decrease_reason = Some(reason);
+            }
+            None => state.increases += 1,
+        }
+        state.requests = 0;
+        state.latency = Duration::ZERO;
+        state.failures = 0;
+        state.queue_depth = queue_depth;
+    }
+}
+
+impl TryFrom<&CongestionControlConfiguration> for CongestionController {
+    type Error = anyhow::Error;
+
+    fn try_from(configuration: &CongestionControlConfiguration) -> Result<Self, Self::Error> {
+        if configuration.min_fps <= 0.0
+            || configuration.min_fps > configuration.max_fps
+            || !configuration.max_fps.is_finite()
+        {
+            bail!(
+                "Invalid congestion control frame rates: min_fps={}, max_fps={}",
+                configuration.min_fps,
+                configuration.max_fps
+            );
+        }
+        let period = configuration.period.unwrap_or(DEFAULT_PERIOD);
+        if period.is_zero() {
+            bail!("Invalid congestion control period: 0");
+        }
+        let increase = configuration.increase.unwrap_or(DEFAULT_INCREASE);
+        if increase <= 0.0 || !increase.is_finite() {
+            bail!("Invalid congestion control increase: {}", increase);
+        }
+        let decrease = configuration.decrease.unwrap_or(DEFAULT_DECREASE);
+        if decrease <= 0.0 || decrease >= 1.0 || decrease.is_nan() {
+            bail!("Invalid congestion control decrease: {}", decrease);
+        }
+        Ok(CongestionController::new(
+            configuration.min_fps,
+            configuration.max_fps,
+            period,
+            increase,
+            decrease,
+            configuration.max_latency,
+            configuration.max_queue_growth,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::thread;
+    use std::time::Duration;
+
+    use savant_core::message::Message;
+    use savant_core::primitives::frame::{
+        VideoFrameContent, VideoFrameProxy, VideoFrameTranscodingMethod,
+    };
+
+    use crate::client::ForwardResult;
+    use crate::congestion::CongestionController;
+
+    #[test]
+    fn aimd() {
+        let controller = new_controller();
+        assert!(controller.accept(&new_frame("source")));
+
+        controller.register(&[Ok(ForwardResult::SendTimeout)], Duration::from_millis(10));
+        controller.adjust(0);
+        assert_eq!(
+            controller.stats().sources,
+            HashMap::from([("source".to_string(), 5.0)])
+        );
+
+        controller.adjust(0);
+        controller.adjust(0);
+        assert_eq!(controller.stats().sources["source"], 7.0);
+
+        // the floor is reached
+        for _ in 0..3 {
+            controller.register(&[Ok(ForwardResult::AckTimeout)], Duration::from_millis(10));
+            controller.adjust(0);
+        }
+        let stats = controller.stats();
+        assert_eq!(stats.sources["source"], 2.0);
+        assert_eq!(stats.increases, 2);
+        assert_eq!(stats.decreases, 4);
+    }
+
+    #[test]
+    fn latency_and_queue_growth() {
+        let controller = new_controller();
+        assert!(controller.accept(&new_frame("source")));
+
+        controller.register(&[Ok(ForwardResult::Success)], Duration::from_millis(500));
+        controller.adjust(0);
+        assert_eq!(controller.stats().sources["source"], 5.0);
+
+        controller.adjust(200);
+        let stats = controller.stats();
+        assert_eq!(stats.sources["source"], 2.5);
+        assert_eq!(
+            stats.last_decrease_reason,
+            Some("queue growth 200".to_string())
+        );
+
+        // the queue does not grow
+        controller.adjust(200);
+        assert_eq!(controller.stats().sources["source"], 3.5);
+    }
+
+    #[test]
+    fn evict_stale_sources() {
+        let controller =
+            CongestionController::new(2.0, 10.0, Duration::from_millis(1), 1.0, 0.5, None, None);
+        assert!(controller.accept(&new_frame("stale")));
+
+        thread::sleep(Duration::from_millis(20));
+        assert!(controller.accept(&new_frame("active")));
+        controller.adjust(0);
+
+        assert_eq!(
+            controller.stats().sources,
+            HashMap::from([("active".to_string(), 10.0)])
+        );
+    }
+
+    #[test]
+    fn accept() {
+        let controller = new_controller();
+
+        assert!(controller.accept(&new_frame("source")));
+        assert!(!controller.accept(&new_frame("source")));
+        assert!(controller.accept(&new_frame("other")));
+        assert!(controller.accept(&Message::unknown("message".to_string())));
+    }
+
+    fn new_controller() -> CongestionController {
+        CongestionController::new(
+            2.0,
+            10.0,
+            Duration::from_secs(1),
+            1.0,
+            0.5,
+            Some(Duration::from_millis(100)),
+            Some(100),
+        )
+    }
+
+    fn new_frame(source_id: &str) -> Message {
+        let frame = VideoFrameProxy::new(
+            source_id,
+            "30/1",
+            1280,
+            720,
+            VideoFrameContent::None,
+            VideoFrameTranscodingMethod::Copy,
+            &None,
+            None,
+            (1, 1000000),
+            0,
+            None,
+            None,
+        );
+        Message::video_frame(&frame)
+    }
+}
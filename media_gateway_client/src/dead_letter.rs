@@ -0,0 +1,122 @@
+//! A sink for messages that are not forwarded.
+//!
+//! The module provides [`DeadLetterSink`].
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::Mutex;
+
+use anyhow::bail;
+use savant_core::message::Message;
+use savant_core::transport::zeromq::SyncWriter;
+
+use media_gateway_common::model::{DeadLetter, Media};
+
+use crate::configuration::DeadLetterConfiguration;
+
+/// Writes messages that are expired or rejected by the media gateway server with the reason.
+pub enum DeadLetterSink {
+    /// Records (a length as 4 bytes in little endian followed by [`DeadLetter`] in protobuf) are
+    /// appended to the file.
+    File(Mutex<File>),
+    /// Messages are written to the ZeroMQ socket with the same topic. The reason is appended to
+    /// extra data as the last item.
+    ZeroMq(Mutex<SyncWriter>),
+}
+
+impl DeadLetterSink {
+    /// Writes the message with the reason to the sink. The call blocks until the message is
+    /// written.
+    pub fn send(&self, media: &Media, reason: &str) -> anyhow::Result<()> {
+        match self {
+            DeadLetterSink::File(file) => {
+                let data = DeadLetter {
+                    media: Some(media.clone()),
+                    reason: reason.to_string(),
+                }
+                .to_proto()?;
+                let mut record = Vec::with_capacity(data.len() + 4);
+                record.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                record.extend_from_slice(&data);
+                file.lock().unwrap().write_all(&record)?;
+                Ok(())
+            }
+            DeadLetterSink::ZeroMq(writer) => {
+                let topic = std::str::from_utf8(&media.topic)?;
+                let message = match &media.message {
+                    Some(message) => Message::try_from(message)?,
+                    None => bail!("No message"),
+                };
+                let mut data = media
+                    .data
+                    .iter()
+                    .map(|e| e.as_slice())
+                    .collect::<Vec<&[u8]>>();
+                data.push(reason.as_bytes());
+                writer
+                    .lock()
+                    .unwrap()
+                    .send_message(topic, &message, &data)?;
+                Ok(())
+            }
+        }
+    }
+}
+
+impl TryFrom<&DeadLetterConfiguration> for DeadLetterSink {
+    type Error = anyhow::Error;
+
+    fn try_from(configuration: &DeadLetterConfiguration) -> Result<Self, Self::Error> {
+        match configuration {
+            DeadLetterConfiguration::File { path } => {
+                let file = OpenOptions::new().create(true).append(true).open(path)?;
+                Ok(DeadLetterSink::File(Mutex::new(file)))
+            }
+            DeadLetterConfiguration::ZeroMq(sink_configuration) => Ok(DeadLetterSink::ZeroMq(
+                Mutex::new(SyncWriter::try_from(sink_configuration)?),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use media_gateway_common::model::{DeadLetter, Media};
+
+    use crate::configuration::DeadLetterConfiguration;
+    use crate::dead_letter::DeadLetterSink;
+
+    #[test]
+    fn send_to_file() {
+        let path = std::env::temp_dir().join(format!("dead-letter-{}", rand::random::<u64>()));
+        let sink = DeadLetterSink::try_from(&DeadLetterConfiguration::File {
+            path: path.to_str().unwrap().to_string(),
+        })
+        .unwrap();
+        let media = Media {
+            message: None,
+            topic: "topic".as_bytes().to_vec(),
+            data: vec![vec![1]],
+        };
+
+        sink.send(&media, "first").unwrap();
+        sink.send(&media, "second").unwrap();
+
+        let content = fs::read(&path).unwrap();
+        let mut reasons = Vec::new();
+        let mut position = 0;
+        while position < content.len() {
+            let length =
+                u32::from_le_bytes(content[position..position + 4].try_into().unwrap()) as usize;
+            position += 4;
+            let dead_letter =
+                DeadLetter::from_proto(&content[position..position + length]).unwrap();
+            position += length;
+            assert_eq!(dead_letter.media, Some(media.clone()));
+            reasons.push(dead_letter.reason);
+        }
+        assert_eq!(reasons, vec!["first", "second"]);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,165 @@
+//! Suppression of duplicate video frames.
+//!
+//! The module provides [`Deduplicator`].
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+use savant_core::message::Message;
+use savant_core::primitives::frame::VideoFrameContent;
+use savant_core::to_json_value::ToSerdeJsonValue;
+
+use crate::configuration::DeduplicationConfiguration;
+
+const DEFAULT_HEARTBEAT: Duration = Duration::from_secs(1);
+
+/// Suppresses consecutive video frames of the same source with the same content, data
+/// attachments, objects and attributes. A frame is forwarded anyway if no frames of the source
+/// were forwarded during the heartbeat interval. Other messages are always forwarded.
+pub struct Deduplicator {
+    heartbeat: Duration,
+    states: Mutex<HashMap<String, SourceState>>,
+}
+
+/// The last forwarded frame of a source.
+struct SourceState {
+    /// A hash of the content and data attachments
+    content_hash: u64,
+    /// A hash of objects and attributes
+    metadata_hash: u64,
+    forwarded_at: Instant,
+}
+
+impl Deduplicator {
+    /// Constructs a new instance.
+    ///
+    /// # Arguments
+    /// * `heartbeat` - the maximum interval between forwarded frames of a source
+    pub fn new(heartbeat: Duration) -> Self {
+        Self {
+            heartbeat,
+            states: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns `true` if the message should be forwarded.
+    ///
+    /// # Arguments
+    /// * `message` - a message
+    /// * `data` - data attachments of the message
+    pub fn accept(&self, message: &Message, data: &[Vec<u8>]) -> bool {
+        let frame = match message.as_video_frame() {
+            Some(frame) => frame,
+            None => return true,
+        };
+        let mut hasher = DefaultHasher::new();
+        if let VideoFrameContent::Internal(content) = frame.get_content().as_ref() {
+            content.hash(&mut hasher);
+        }
+        data.hash(&mut hasher);
+        let content_hash = hasher.finish();
+        let json = frame.to_serde_json_value();
+        let mut hasher = DefaultHasher::new();
+        for key in ["objects", "attributes"] {
+            json.get(key).map(|e| e.to_string()).hash(&mut hasher);
+        }
+        let metadata_hash = hasher.finish();
+
+        let now = Instant::now();
+        let mut states = self.states.lock().unwrap();
+        if let Some(state) = states.get(&frame.get_source_id()) {
+            if state.content_hash == content_hash
+                && state.metadata_hash == metadata_hash
+                && now.duration_since(state.forwarded_at) < self.heartbeat
+            {
+                return false;
+            }
+        }
+        states.insert(
+            frame.get_source_id(),
+            SourceState {
+                content_hash,
+                metadata_hash,
+                forwarded_at: now,
+            },
+        );
+        true
+    }
+}
+
+impl TryFrom<&DeduplicationConfiguration> for Deduplicator {
+    type Error = anyhow::Error;
+
+    fn try_from(configuration: &DeduplicationConfiguration) -> Result<Self, Self::Error> {
+        let heartbeat = configuration.heartbeat.unwrap_or(DEFAULT_HEARTBEAT);
+        if heartbeat.is_zero() {
+            bail!("Invalid deduplication heartbeat: 0");
+        }
+        Ok(Deduplicator::new(heartbeat))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+
+    use savant_core::message::Message;
+    use savant_core::primitives::frame::{
+        VideoFrameContent, VideoFrameProxy, VideoFrameTranscodingMethod,
+    };
+
+    use crate::deduplication::Deduplicator;
+
+    #[test]
+    fn suppress_duplicates() {
+        let deduplicator = Deduplicator::new(Duration::from_secs(60));
+
+        assert!(deduplicator.accept(&new_frame("source", vec![1]), &[vec![1]]));
+        assert!(!deduplicator.accept(&new_frame("source", vec![1]), &[vec![1]]));
+        assert!(deduplicator.accept(&new_frame("other", vec![1]), &[vec![1]]));
+        assert!(deduplicator.accept(&new_frame("source", vec![2]), &[vec![1]]));
+        assert!(deduplicator.accept(&new_frame("source", vec![2]), &[vec![2]]));
+        assert!(!deduplicator.accept(&new_frame("source", vec![2]), &[vec![2]]));
+    }
+
+    #[test]
+    fn heartbeat() {
+        let deduplicator = Deduplicator::new(Duration::from_millis(50));
+
+        assert!(deduplicator.accept(&new_frame("source", vec![1]), &[]));
+        assert!(!deduplicator.accept(&new_frame("source", vec![1]), &[]));
+        thread::sleep(Duration::from_millis(60));
+        assert!(deduplicator.accept(&new_frame("source", vec![1]), &[]));
+    }
+
+    #[test]
+    fn non_frame_messages() {
+        let deduplicator = Deduplicator::new(Duration::from_secs(60));
+        let message = Message::unknown("message".to_string());
+
+        assert!(deduplicator.accept(&message, &[]));
+        assert!(deduplicator.accept(&message, &[]));
+    }
+
+    fn new_frame(source_id: &str, content: Vec<u8>) -> Message {
+        let frame = VideoFrameProxy::new(
+            source_id,
+            "30/1",
+            1280,
+            720,
+            VideoFrameContent::Internal(content),
+            VideoFrameTranscodingMethod::Copy,
+            &None,
+            None,
+            (1, 1000000),
+            0,
+            None,
+            None,
+        );
+        Message::video_frame(&frame)
+    }
+}
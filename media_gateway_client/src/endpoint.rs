@@ -0,0 +1,315 @@
+//! Selection of media gateway server endpoints.
+//!
+//! The module provides [`EndpointSelector`] that tracks availability and latency of endpoints and
+//! selects an endpoint for each request according to [`EndpointPolicy`].
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use reqwest::{Client, StatusCode};
+
+use crate::configuration::EndpointPolicy;
+
+/// A weight of the last request latency in the average latency of an endpoint.
+const LATENCY_WEIGHT: f64 = 0.2;
+
+/// Settings to check health of endpoints marked down.
+pub struct HealthCheck {
+    /// A client to send health requests
+    pub client: Client,
+    /// A period between health checks of an endpoint marked down
+    pub period: Duration,
+}
+
+/// Selects an endpoint for each request. An endpoint is marked down after consecutive errors and
+/// marked up again after a successful health check (`GET /health`). If all endpoints are down
+/// they are selected as if all of them were up.
+pub struct EndpointSelector {
+    endpoints: Vec<Endpoint>,
+    policy: EndpointPolicy,
+    failure_threshold: u32,
+    health_check: Option<HealthCheck>,
+    next: AtomicUsize,
+}
+
+struct Endpoint {
+    url: String,
+    health_url: String,
+    state: Mutex<EndpointState>,
+}
+
+impl Endpoint {
+    fn new(url: String, health_url: String) -> Self {
+        Self {
+            url,
+            health_url,
+            state: Mutex::new(EndpointState {
+                up: true,
+                consecutive_failures: 0,
+                latency: None,
+                last_check: None,
+                checking: false,
+            }),
+        }
+    }
+}
+
+struct EndpointState {
+    up: bool,
+    consecutive_failures: u32,
+    latency: Option<Duration>,
+    last_check: Option<Instant>,
+    checking: bool,
+}
+
+impl EndpointSelector {
+    /// Constructs a new instance.
+    ///
+    /// # Arguments
+    /// * `urls` - endpoint urls in order of priority
+    /// * `policy` - a policy to select an endpoint
+    /// * `failure_threshold` - the number of consecutive errors after which an endpoint is marked
+    ///   down
+    /// * `health_check` - settings to check health of endpoints marked down
+    pub fn new(
+        urls: Vec<String>,
+        policy: EndpointPolicy,
+        failure_threshold: u32,
+        health_check: Option<HealthCheck>,
+    ) -> anyhow::Result<Self> {
+        let endpoints = urls
+            .into_iter()
+            .map(|url| {
+                let health_url = reqwest::Url::parse(&url)?.join("/health")?.to_string();
+                Ok(Endpoint::new(url, health_url))
+            })
+            .collect::<anyhow::Result<Vec<Endpoint>>>()?;
+        Ok(Self {
+            endpoints,
+            policy,
+            failure_threshold,
+            health_check,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// Constructs a new instance for the single endpoint that is always selected.
+    pub fn single() -> Self {
+        Self {
+            endpoints: vec![Endpoint::new(String::new(), String::new())],
+            policy: EndpointPolicy::Failover,
+            failure_threshold: u32::MAX,
+            health_check: None,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns an index of the endpoint for the next request. Health checks of endpoints marked
+    /// down are started if they are due.
+    pub fn select(self: &Arc<Self>) -> usize {
+        if self.endpoints.len() == 1 {
+            return 0;
+        }
+        self.start_health_checks();
+
+        let mut candidates = self
+            .endpoints
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.state.lock().unwrap().up)
+            .map(|(i, _)| i)
+            .collect::<Vec<usize>>();
+        if candidates.is_empty() {
+            candidates = (0..self.endpoints.len()).collect();
+        }
+        match self.policy {
+            EndpointPolicy::Failover => candidates[0],
+            EndpointPolicy::RoundRobin => {
+                candidates[self.next.fetch_add(1, Ordering::Relaxed) % candidates.len()]
+            }
+            EndpointPolicy::LeastLatency => *candidates
+                .iter()
+                .min_by_key(|&&i| {
+                    self.endpoints[i]
+                        .state
+                        .lock()
+                        .unwrap()
+                        .latency
+                        .unwrap_or(Duration::ZERO)
+                })
+                .unwrap(),
+        }
+    }
+
+    /// Registers the result of a request to the endpoint.
+    pub fn register_result(&self, index: usize, success: bool, latency: Duration) {
+        let endpoint = &self.endpoints[index];
+        let mut state = endpoint.state.lock().unwrap();
+        if success {
+            state.consecutive_failures = 0;
+            state.latency = Some(match state.latency {
+                Some(average) => {
+                    average.mul_f64(1.0 - LATENCY_WEIGHT) + latency.mul_f64(LATENCY_WEIGHT)
+                }
+                None => latency,
+            });
+            return;
+        }
+        state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+        if state.up && state.consecutive_failures >= self.failure_threshold {
+            log::warn!(
+                "Endpoint {} is marked down after {} consecutive errors",
+                endpoint.url,
+                state.consecutive_failures
+            );
+            state.up = false;
+            state.last_check = Some(Instant::now());
+        }
+    }
+
+    fn start_health_checks(self: &Arc<Self>) {
+        let period = match &self.health_check {
+            Some(health_check) => health_check.period,
+            None => return,
+        };
+        for (index, endpoint) in self.endpoints.iter().enumerate() {
+            {
+                let mut state = endpoint.state.lock().unwrap();
+                if state.up
+                    || state.checking
+                    || state.last_check.is_some_and(|e| e.elapsed() < period)
+                {
+                    continue;
+                }
+                state.checking = true;
+                state.last_check = Some(Instant::now());
+            }
+            let selector = self.clone();
+            tokio::spawn(async move { selector.check_health(index).await });
+        }
+    }
+
+    async fn check_health(&self, index: usize) {
+        let endpoint = &self.endpoints[index];
+        let client = &self.health_check.as_ref().unwrap().client;
+        let healthy = match client.get(&endpoint.health_url).send().await {
+            Ok(response) => response.status() == StatusCode::OK,
+            Err(e) => {
+                log::debug!(
+                    "Error while checking health of endpoint {}: {:?}",
+                    endpoint.url,
+                    e
+                );
+                false
+            }
+        };
+        let mut state = endpoint.state.lock().unwrap();
+        state.checking = false;
+        if healthy {
+            log::info!("Endpoint {} is marked up", endpoint.url);
+            state.up = true;
+            state.consecutive_failures = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use reqwest::{Client, StatusCode};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::configuration::EndpointPolicy;
+    use crate::endpoint::{EndpointSelector, HealthCheck};
+
+    const LATENCY: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn failover() {
+        let selector = new_selector(EndpointPolicy::Failover);
+
+        assert_eq!(selector.select(), 0);
+        selector.register_result(0, false, LATENCY);
+        assert_eq!(selector.select(), 0);
+        selector.register_result(0, false, LATENCY);
+        assert_eq!(selector.select(), 1);
+        selector.register_result(1, false, LATENCY);
+        selector.register_result(1, false, LATENCY);
+        // all endpoints are down
+        assert_eq!(selector.select(), 0);
+    }
+
+    #[test]
+    fn round_robin() {
+        let selector = new_selector(EndpointPolicy::RoundRobin);
+
+        assert_eq!(selector.select(), 0);
+        assert_eq!(selector.select(), 1);
+        assert_eq!(selector.select(), 0);
+        selector.register_result(1, false, LATENCY);
+        selector.register_result(1, false, LATENCY);
+        assert_eq!(selector.select(), 0);
+        assert_eq!(selector.select(), 0);
+    }
+
+    #[test]
+    fn least_latency() {
+        let selector = new_selector(EndpointPolicy::LeastLatency);
+
+        selector.register_result(0, true, Duration::from_millis(10));
+        selector.register_result(1, true, Duration::from_millis(5));
+
+        assert_eq!(selector.select(), 1);
+    }
+
+    #[tokio::test]
+    async fn recover_after_health_check() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/health"))
+            .respond_with(ResponseTemplate::new(StatusCode::OK))
+            .mount(&server)
+            .await;
+        let selector = Arc::new(
+            EndpointSelector::new(
+                vec![server.uri(), "http://127.0.0.1:1".to_string()],
+                EndpointPolicy::Failover,
+                1,
+                Some(HealthCheck {
+                    client: Client::default(),
+                    period: Duration::ZERO,
+                }),
+            )
+            .unwrap(),
+        );
+
+        selector.register_result(0, false, LATENCY);
+        assert_eq!(selector.select(), 1);
+
+        for _ in 0..100 {
+            if selector.select() == 0 {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("Endpoint is not recovered");
+    }
+
+    fn new_selector(policy: EndpointPolicy) -> Arc<EndpointSelector> {
+        Arc::new(
+            EndpointSelector::new(
+                vec![
+                    "http://127.0.0.1:1".to_string(),
+                    "http://127.0.0.1:2".to_string(),
+                ],
+                policy,
+                2,
+                None,
+            )
+            .unwrap(),
+        )
+    }
+}
@@ -0,0 +1,180 @@
+//! Enrichment of messages with data of the client.
+//!
+//! The module provides [`Enricher`].
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{env, fs};
+
+use anyhow::bail;
+use savant_protobuf::generated::Message;
+
+use crate::configuration::EnrichmentConfiguration;
+
+const HOSTNAME_VARIABLE: &str = "HOSTNAME";
+const HOSTNAME_PATH: &str = "/proc/sys/kernel/hostname";
+
+/// Stamps messages with static routing labels and propagated context entries. Values of labels
+/// and entries are templates expanded once when the enricher is created.
+pub struct Enricher {
+    routing_labels: Vec<String>,
+    propagated_context: HashMap<String, String>,
+    /// A key of the propagated context entry for the local receive timestamp
+    receive_timestamp_key: Option<String>,
+}
+
+impl Enricher {
+    /// Adds routing labels which the message does not have and sets propagated context entries.
+    ///
+    /// # Arguments
+    /// * `message` - a message
+    /// * `received_at` - the time when the message is received
+    pub fn enrich(&self, message: &mut Message, received_at: SystemTime) {
+        for label in &self.routing_labels {
+            if !message.routing_labels.contains(label) {
+                message.routing_labels.push(label.clone());
+            }
+        }
+        for (key, value) in &self.propagated_context {
+            message
+                .propagated_context
+                .insert(key.clone(), value.clone());
+        }
+        if let Some(key) = &self.receive_timestamp_key {
+            let timestamp = received_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos();
+            message
+                .propagated_context
+                .insert(key.clone(), timestamp.to_string());
+        }
+    }
+}
+
+impl TryFrom<&EnrichmentConfiguration> for Enricher {
+    type Error = anyhow::Error;
+
+    fn try_from(configuration: &EnrichmentConfiguration) -> Result<Self, Self::Error> {
+        let routing_labels = configuration
+            .routing_labels
+            .iter()
+            .flatten()
+            .map(|e| expand(e, variable))
+            .collect::<anyhow::Result<Vec<String>>>()?;
+        let propagated_context = configuration
+            .propagated_context
+            .iter()
+            .flatten()
+            .map(|(key, value)| Ok((key.clone(), expand(value, variable)?)))
+            .collect::<anyhow::Result<HashMap<String, String>>>()?;
+        if let Some(key) = &configuration.receive_timestamp_key {
+            if key.is_empty() {
+                bail!("Invalid enrichment receive_timestamp_key: empty");
+            }
+        }
+        Ok(Self {
+            routing_labels,
+            propagated_context,
+            receive_timestamp_key: configuration.receive_timestamp_key.clone(),
+        })
+    }
+}
+
+/// Returns the value of the template variable. `HOSTNAME` is the host name of the system if
+/// the environment variable is not set, other variables are environment variables.
+fn variable(name: &str) -> Option<String> {
+    if let Ok(value) = env::var(name) {
+        return Some(value);
+    }
+    if name == HOSTNAME_VARIABLE {
+        return fs::read_to_string(HOSTNAME_PATH)
+            .ok()
+            .map(|e| e.trim().to_string());
+    }
+    None
+}
+
+/// Replaces `${NAME}` placeholders in the template with values of variables.
+fn expand(template: &str, variable: impl Fn(&str) -> Option<String>) -> anyhow::Result<String> {
+    let mut result = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("${") {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => bail!(
+                "Invalid enrichment template {}: unclosed placeholder",
+                template
+            ),
+        };
+        let name = &rest[start + 2..end];
+        match variable(name) {
+            Some(value) => {
+                result.push_str(&rest[..start]);
+                result.push_str(&value);
+            }
+            None => bail!(
+                "Invalid enrichment template {}: unknown variable {}",
+                template,
+                name
+            ),
+        }
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use savant_protobuf::generated::Message;
+
+    use crate::configuration::EnrichmentConfiguration;
+    use crate::enrichment::{expand, Enricher};
+
+    #[test]
+    fn expand_templates() {
+        let variable = |name: &str| (name == "HOSTNAME").then(|| "edge-1".to_string());
+
+        assert_eq!(
+            expand("${HOSTNAME}/camera", variable).unwrap(),
+            "edge-1/camera"
+        );
+        assert_eq!(expand("site-1", variable).unwrap(), "site-1");
+        assert!(expand("${UNKNOWN}", variable).is_err());
+        assert!(expand("${HOSTNAME", variable).is_err());
+    }
+
+    #[test]
+    fn enrich() {
+        let enricher = Enricher::try_from(&EnrichmentConfiguration {
+            routing_labels: Some(vec!["edge".to_string(), "site-1".to_string()]),
+            propagated_context: Some(HashMap::from([(
+                "client_id".to_string(),
+                "client-1".to_string(),
+            )])),
+            receive_timestamp_key: Some("received_at".to_string()),
+        })
+        .unwrap();
+        let mut message = Message {
+            routing_labels: vec!["edge".to_string()],
+            ..Default::default()
+        };
+
+        enricher.enrich(&mut message, UNIX_EPOCH + Duration::from_secs(1));
+
+        assert_eq!(
+            message.routing_labels,
+            vec!["edge".to_string(), "site-1".to_string()]
+        );
+        assert_eq!(
+            message.propagated_context,
+            HashMap::from([
+                ("client_id".to_string(), "client-1".to_string()),
+                ("received_at".to_string(), "1000000000".to_string()),
+            ])
+        );
+    }
+}
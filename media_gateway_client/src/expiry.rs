@@ -0,0 +1,188 @@
+//! Limits after which a message is not retried.
+//!
+//! The module provides [`Expiry`].
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::bail;
+use savant_core::message::Message;
+
+use media_gateway_common::model::Media;
+
+use crate::configuration::{AgeSource, ExpiryConfiguration};
+
+/// Checks whether a message exceeds the maximum number of attempts or the maximum age.
+#[derive(Debug, Clone)]
+pub struct Expiry {
+    max_attempts: Option<u32>,
+    max_age: Option<Duration>,
+    age_source: AgeSource,
+}
+
+impl Expiry {
+    /// Returns the time after which the message is expired or [`None`] if the age is not
+    /// limited.
+    ///
+    /// # Arguments
+    /// * `received_at` - the time when the message is received
+    /// * `media` - the message
+    pub fn deadline(&self, received_at: Instant, media: &Media) -> Option<Instant> {
+        let max_age = self.max_age?;
+        let created_at = match self.age_source {
+            AgeSource::ReceiveTime => None,
+            AgeSource::FrameTimestamp => frame_timestamp(media),
+        };
+        match created_at {
+            Some(created_at) => {
+                let age = SystemTime::now()
+                    .duration_since(created_at)
+                    .unwrap_or(Duration::ZERO);
+                let now = Instant::now();
+                Some((now + max_age).checked_sub(age).unwrap_or(now))
+            }
+            None => Some(received_at + max_age),
+        }
+    }
+
+    /// Returns the reason if the message is expired after the number of attempts.
+    pub fn check(&self, attempts: u32, deadline: Option<Instant>) -> Option<String> {
+        if let Some(max_attempts) = self.max_attempts {
+            if attempts >= max_attempts {
+                return Some(format!("max_attempts {} is reached", max_attempts));
+            }
+        }
+        match deadline {
+            Some(deadline) if Instant::now() >= deadline => Some("max_age is exceeded".to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<&ExpiryConfiguration> for Expiry {
+    type Error = anyhow::Error;
+
+    fn try_from(configuration: &ExpiryConfiguration) -> Result<Self, Self::Error> {
+        if configuration.max_attempts == Some(0) {
+            bail!("Invalid expiry max_attempts: 0");
+        }
+        Ok(Self {
+            max_attempts: configuration.max_attempts,
+            max_age: configuration.max_age,
+            age_source: configuration
+                .age_source
+                .clone()
+                .unwrap_or(AgeSource::ReceiveTime),
+        })
+    }
+}
+
+/// Returns the creation timestamp of the video frame or [`None`] for other messages.
+fn frame_timestamp(media: &Media) -> Option<SystemTime> {
+    let message = Message::try_from(media.message.as_ref()?).ok()?;
+    let frame = message.as_video_frame()?;
+    let timestamp = u64::try_from(frame.get_creation_timestamp_ns()).ok()?;
+    Some(UNIX_EPOCH + Duration::from_nanos(timestamp))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use savant_core::message::Message;
+    use savant_core::primitives::frame::{
+        VideoFrameContent, VideoFrameProxy, VideoFrameTranscodingMethod,
+    };
+
+    use media_gateway_common::model::Media;
+
+    use crate::configuration::{AgeSource, ExpiryConfiguration};
+    use crate::expiry::Expiry;
+
+    const MAX_AGE: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn max_attempts() {
+        let expiry = new_expiry(Some(2), None, None);
+
+        assert!(expiry.check(1, None).is_none());
+        assert!(expiry.check(2, None).is_some());
+    }
+
+    #[test]
+    fn max_age_receive_time() {
+        let expiry = new_expiry(None, Some(MAX_AGE), None);
+        let media = new_frame_media();
+
+        let expired = expiry.deadline(Instant::now() - 2 * MAX_AGE, &media);
+        let not_expired = expiry.deadline(Instant::now(), &media);
+
+        assert!(expiry.check(1, expired).is_some());
+        assert!(expiry.check(1, not_expired).is_none());
+    }
+
+    #[test]
+    fn max_age_frame_timestamp() {
+        let expiry = new_expiry(None, Some(MAX_AGE), Some(AgeSource::FrameTimestamp));
+
+        // the frame is created just now
+        let frame_deadline = expiry.deadline(Instant::now() - 2 * MAX_AGE, &new_frame_media());
+        // the receive time is used for messages without video frames
+        let other_deadline = expiry.deadline(
+            Instant::now() - 2 * MAX_AGE,
+            &Media {
+                message: None,
+                topic: vec![],
+                data: vec![],
+            },
+        );
+
+        assert!(expiry.check(1, frame_deadline).is_none());
+        assert!(expiry.check(1, other_deadline).is_some());
+    }
+
+    #[test]
+    fn invalid_max_attempts() {
+        let configuration = ExpiryConfiguration {
+            max_attempts: Some(0),
+            max_age: None,
+            age_source: None,
+        };
+
+        assert!(Expiry::try_from(&configuration).is_err());
+    }
+
+    fn new_expiry(
+        max_attempts: Option<u32>,
+        max_age: Option<Duration>,
+        age_source: Option<AgeSource>,
+    ) -> Expiry {
+        Expiry::try_from(&ExpiryConfiguration {
+            max_attempts,
+            max_age,
+            age_source,
+        })
+        .unwrap()
+    }
+
+    fn new_frame_media() -> Media {
+        let frame = VideoFrameProxy::new(
+            "source",
+            "30/1",
+            1280,
+            720,
+            VideoFrameContent::None,
+            VideoFrameTranscodingMethod::Copy,
+            &None,
+            None,
+            (1, 1000000),
+            0,
+            None,
+            None,
+        );
+        let message = Message::video_frame(&frame);
+        Media {
+            message: Option::from(savant_protobuf::generated::Message::from(&message)),
+            topic: "source".as_bytes().to_vec(),
+            data: vec![],
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! Weights of sources for fair queuing.
+//!
+//! The module provides [`FairQueuing`].
+use std::collections::HashMap;
+
+use anyhow::bail;
+
+use crate::configuration::FairQueuingConfiguration;
+
+const DEFAULT_QUANTUM: usize = 65536;
+const DEFAULT_WEIGHT: u32 = 1;
+
+/// Calculates quanta of sources for deficit round-robin. A source can send the number of bytes
+/// equal to its quantum in its turn. The quantum of a source is the base quantum multiplied by
+/// the weight of the source.
+pub struct FairQueuing {
+    quantum: usize,
+    default_weight: u32,
+    sources: HashMap<String, u32>,
+    /// Weights by prefixes of source ids sorted by the length of prefixes in descending order
+    prefixes: Vec<(String, u32)>,
+}
+
+impl FairQueuing {
+    /// Constructs a new instance.
+    ///
+    /// # Arguments
+    /// * `quantum` - a quantum of sources with weight 1 in bytes
+    /// * `default_weight` - a weight of sources without specified weights
+    /// * `sources` - weights by source ids
+    /// * `prefixes` - weights by prefixes of source ids
+    pub fn new(
+        quantum: usize,
+        default_weight: u32,
+        sources: HashMap<String, u32>,
+        prefixes: HashMap<String, u32>,
+    ) -> Self {
+        let mut prefixes = prefixes.into_iter().collect::<Vec<(String, u32)>>();
+        prefixes.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+        Self {
+            quantum,
+            default_weight,
+            sources,
+            prefixes,
+        }
+    }
+
+    /// Returns the quantum of the source in bytes.
+    pub fn quantum(&self, source: &str) -> usize {
+        self.quantum * self.weight(source) as usize
+    }
+
+    /// Returns the weight of the source. The weight by the source id takes precedence over
+    /// weights by prefixes. If several prefixes match the longest one is applied.
+    fn weight(&self, source: &str) -> u32 {
+        if let Some(weight) = self.sources.get(source) {
+            return *weight;
+        }
+        self.prefixes
+            .iter()
+            .find(|(prefix, _)| source.starts_with(prefix.as_str()))
+            .map_or(self.default_weight, |(_, weight)| *weight)
+    }
+}
+
+impl TryFrom<&FairQueuingConfiguration> for FairQueuing {
+    type Error = anyhow::Error;
+
+    fn try_from(configuration: &FairQueuingConfiguration) -> Result<Self, Self::Error> {
+        let quantum = configuration.quantum.unwrap_or(DEFAULT_QUANTUM);
+        if quantum == 0 {
+            bail!("Invalid fair queuing quantum: 0");
+        }
+        let default_weight = configuration.default_weight.unwrap_or(DEFAULT_WEIGHT);
+        let sources = configuration.sources.clone().unwrap_or_default();
+        let prefixes = configuration.prefixes.clone().unwrap_or_default();
+        if default_weight == 0 || sources.values().chain(prefixes.values()).any(|e| *e == 0) {
+            bail!("Invalid fair queuing weight: 0");
+        }
+        Ok(FairQueuing::new(quantum, default_weight, sources, prefixes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::configuration::FairQueuingConfiguration;
+    use crate::fair_queuing::FairQueuing;
+
+    #[test]
+    fn quantum() {
+        let fair_queuing = FairQueuing::try_from(&FairQueuingConfiguration {
+            quantum: Some(100),
+            default_weight: None,
+            sources: Some(HashMap::from([("camera-4k".to_string(), 8)])),
+            prefixes: Some(HashMap::from([
+                ("camera".to_string(), 2),
+                ("camera-hd".to_string(), 4),
+            ])),
+        })
+        .unwrap();
+
+        assert_eq!(fair_queuing.quantum("camera-4k"), 800);
+        assert_eq!(fair_queuing.quantum("camera-hd-1"), 400);
+        assert_eq!(fair_queuing.quantum("camera-1"), 200);
+        assert_eq!(fair_queuing.quantum("metadata"), 100);
+    }
+
+    #[test]
+    fn zero_weight() {
+        let result = FairQueuing::try_from(&FairQueuingConfiguration {
+            quantum: None,
+            default_weight: Some(0),
+            sources: None,
+            prefixes: None,
+        });
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,1151 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::mem;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use savant_core::message::Message;
+use tokio::sync::mpsc::Receiver;
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task::JoinHandle;
+use tokio_timerfd::sleep;
+
+use media_gateway_common::model::{Media, MediaBatch};
+use media_gateway_common::statistics::StatisticsService;
+
+use crate::batch::next_batch;
+use crate::client::{ForwardResult, GatewayClient};
+use crate::configuration::{BatchConfiguration, QueueOverflowPolicy};
+use crate::congestion::CongestionController;
+use crate::dead_letter::DeadLetterSink;
+use crate::expiry::Expiry;
+use crate::metadata::strip_content;
+use crate::queue::{MessageClass, MessageInfo, MessageQueue};
+use crate::retry::{Retry, RetryAction, RetryPolicy, RetryStrategy};
+use crate::routing::source_key;
+use crate::shaper::Shaper;
+use crate::spool::{Spool, SpoolId};
+
+/// An idle lane is closed after the timeout by default.
+pub const LANE_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Data to complete a message after it is accepted by the media gateway server.
+#[derive(Debug, Clone)]
+pub struct MessageContext {
+    pub statistics_id: Option<i64>,
+    pub spool_id: Option<SpoolId>,
+    /// The time when the message is received
+    pub received_at: Instant,
+    /// A kind of the message. If not specified it is determined from the message when it is
+    /// dispatched.
+    pub class: Option<MessageClass>,
+    /// A source id of the message or its topic if the message has no source id. If not
+    /// specified it is determined from the message when it is dispatched.
+    pub source: Option<String>,
+}
+
+impl MessageContext {
+    /// Constructs a context of the message received now.
+    pub fn new(statistics_id: Option<i64>, topic: &[u8], message: &Message) -> Self {
+        Self {
+            statistics_id,
+            class: Some(MessageClass::from(message)),
+            source: Some(source_key(topic, Some(message))),
+            ..Default::default()
+        }
+    }
+
+    /// Returns a kind of the message decoding the message only if the kind is not known.
+    pub fn class(&self, media: &Media) -> MessageClass {
+        match &self.class {
+            Some(class) => class.clone(),
+            None => media
+                .message
+                .as_ref()
+                .and_then(|e| Message::try_from(e).ok())
+                .as_ref()
+                .map_or(MessageClass::Other, MessageClass::from),
+        }
+    }
+
+    /// Returns properties of the message to queue it in a lane decoding the message only if
+    /// they are not known.
+    fn info(&self, media: &Media) -> MessageInfo {
+        let (class, source) = match (&self.class, &self.source) {
+            (Some(class), Some(source)) => (class.clone(), source.clone()),
+            _ => {
+                let message = media
+                    .message
+                    .as_ref()
+                    .and_then(|e| Message::try_from(e).ok());
+                (
+                    message
+                        .as_ref()
+                        .map_or(MessageClass::Other, MessageClass::from),
+                    source_key(&media.topic, message.as_ref()),
+                )
+            }
+        };
+        MessageInfo {
+            class,
+            source,
+            lane: 0,
+        }
+    }
+}
+
+impl Default for MessageContext {
+    fn default() -> Self {
+        Self {
+            statistics_id: None,
+            spool_id: None,
+            received_at: Instant::now(),
+            class: None,
+            source: None,
+        }
+    }
+}
+
+/// Settings of [`Forwarder`] in addition to its client.
+#[derive(Clone)]
+pub struct ForwarderOptions {
+    /// A strategy how to retry to send a message
+    pub retry_strategy: RetryStrategy,
+    /// Actions for outcomes of unsuccessful attempts to send a message
+    pub retry_policy: RetryPolicy,
+    /// Batch settings. If specified messages are forwarded in batches.
+    pub batch_configuration: Option<BatchConfiguration>,
+    /// The maximum number of concurrent requests. 1 by default.
+    pub max_inflight: usize,
+    pub statistics_service: Arc<Option<StatisticsService>>,
+    /// A spool to acknowledge forwarded messages
+    pub spool: Option<Arc<Spool>>,
+    pub expiry: Option<Expiry>,
+    pub dead_letter: Option<Arc<DeadLetterSink>>,
+    pub shaper: Option<Arc<Shaper>>,
+    pub congestion_controller: Option<Arc<CongestionController>>,
+    /// `true` if the content and data of video frames should be stripped
+    pub metadata_only: bool,
+}
+
+impl Default for ForwarderOptions {
+    fn default() -> Self {
+        Self {
+            retry_strategy: RetryStrategy::default(),
+            retry_policy: RetryPolicy::default(),
+            batch_configuration: None,
+            max_inflight: 1,
+            statistics_service: Arc::new(None),
+            spool: None,
+            expiry: None,
+            dead_letter: None,
+            shaper: None,
+            congestion_controller: None,
+            metadata_only: false,
+        }
+    }
+}
+
+/// Forwards messages from a queue to the media gateway server one by one or in batches retrying
+/// failed ones. Messages from the same queue are forwarded in order. The number of concurrent
+/// requests is shared between all queues forwarded by the same instance.
+///
+/// Failed messages are retried, dropped or written to [`DeadLetterSink`] (if it is specified)
+/// according to [`RetryPolicy`]. Messages expired according to [`Expiry`] are written to
+/// [`DeadLetterSink`].
+///
+/// If [`Shaper`] is specified each attempt waits for the bandwidth and video frames may be
+/// dropped if the bandwidth limit is reached.
+///
+/// If [`CongestionController`] is specified outcomes and latencies of requests are registered
+/// by it.
+///
+/// If `metadata_only` is set the content and data of video frames are stripped before they are
+/// forwarded.
+pub struct Forwarder {
+    client: GatewayClient,
+    retry_strategy: RetryStrategy,
+    retry_policy: RetryPolicy,
+    batch_configuration: Option<BatchConfiguration>,
+    inflight: Semaphore,
+    statistics_service: Arc<Option<StatisticsService>>,
+    spool: Option<Arc<Spool>>,
+    expiry: Option<Expiry>,
+    dead_letter: Option<Arc<DeadLetterSink>>,
+    shaper: Option<Arc<Shaper>>,
+    congestion_controller: Option<Arc<CongestionController>>,
+    metadata_only: bool,
+}
+
+impl Forwarder {
+    pub fn new(client: GatewayClient, options: ForwarderOptions) -> Self {
+        Self {
+            client,
+            retry_strategy: options.retry_strategy,
+            retry_policy: options.retry_policy,
+            batch_configuration: options.batch_configuration,
+            inflight: Semaphore::new(options.max_inflight),
+            statistics_service: options.statistics_service,
+            spool: options.spool,
+            expiry: options.expiry,
+            dead_letter: options.dead_letter,
+            shaper: options.shaper,
+            congestion_controller: options.congestion_controller,
+            metadata_only: options.metadata_only,
+        }
+    }
+
+    /// Constructs an instance forwarding messages via HTTP to the server with the URL for
+    /// tests.
+    #[cfg(test)]
+    pub fn with_url(url: String, options: ForwarderOptions) -> Self {
+        Self::new(
+            GatewayClient::new(reqwest::Client::default(), url, None),
+            options,
+        )
+    }
+
+    /// Closes connections of the client.
+    pub async fn shutdown(&self) {
+        self.client.shutdown().await;
+    }
+
+    /// Forwards messages from the queue until it is closed.
+    pub async fn run(&self, receiver: &mut Receiver<(MessageContext, Media)>) {
+        match &self.batch_configuration {
+            Some(batch_configuration) => self.forward_batches(receiver, batch_configuration).await,
+            None => self.forward_messages(receiver).await,
+        }
+    }
+
+    async fn forward_messages(&self, receiver: &mut Receiver<(MessageContext, Media)>) {
+        while let Some((context, media)) = receiver.recv().await {
+            let media = self.prepare(media);
+            let deadline = self.deadline(&context, &media);
+            let mut retry: Option<Retry> = None;
+            let mut attempts = 0;
+            loop {
+                if let Some(reason) = self.check_expiry(attempts, deadline) {
+                    self.reject(context, &media, &reason).await;
+                    break;
+                }
+                if let Some(shaper) = &self.shaper {
+                    if shaper.drops(&media) {
+                        log::debug!("Message is dropped by bandwidth shaping");
+                        self.complete(context);
+                        break;
+                    }
+                    shaper.acquire(media.proto_len()).await;
+                }
+                attempts += 1;
+                let forward_result = {
+                    let _permit = self.inflight.acquire().await;
+                    let started = Instant::now();
+                    let forward_result = self.client.forward_message(&media).await;
+                    if let Some(controller) = &self.congestion_controller {
+                        controller.register([&forward_result], started.elapsed());
+                    }
+                    forward_result
+                };
+                match &forward_result {
+                    Ok(ForwardResult::Success) => {
+                        self.complete(context);
+                        if retry.is_some() {
+                            log::info!(
+                                "Success while sending message on {} retry",
+                                retry.unwrap().number()
+                            );
+                        } else {
+                            log::debug!("Success while sending message (retry=0)");
+                        }
+                        break;
+                    }
+                    Ok(result) => {
+                        log::warn!(
+                            "Failure while sending message (retry={}): {:?}",
+                            retry.as_ref().map_or(0, |e| e.number()),
+                            result
+                        );
+                    }
+                    Err(e) => {
+                        log::warn!(
+                            "Error while sending message (retry={}): {:?}",
+                            retry.as_ref().map_or(0, |e| e.number()),
+                            e
+                        )
+                    }
+                }
+                let action = self.retry_policy.action(&forward_result);
+                if action != RetryAction::Retry {
+                    self.give_up(action, context, &media, &failure_reason(&forward_result))
+                        .await;
+                    break;
+                }
+                if let Some(reason) = self.check_expiry(attempts, deadline) {
+                    self.reject(context, &media, &reason).await;
+                    break;
+                }
+                let retry_after = forward_result
+                    .as_ref()
+                    .ok()
+                    .and_then(ForwardResult::retry_after);
+                retry = Some(self.wait_for_retry(retry, retry_after).await);
+            }
+        }
+    }
+
+    async fn forward_batches(
+        &self,
+        receiver: &mut Receiver<(MessageContext, Media)>,
+        batch_configuration: &BatchConfiguration,
+    ) {
+        while let Some(items) = next_batch(receiver, batch_configuration).await {
+            let (mut contexts, items): (Vec<(MessageContext, Option<Instant>)>, Vec<Media>) = items
+                .into_iter()
+                .map(|(context, media)| {
+                    let media = self.prepare(media);
+                    let deadline = self.deadline(&context, &media);
+                    ((context, deadline), media)
+                })
+                .unzip();
+            let mut batch = MediaBatch { items };
+            let mut retry: Option<Retry> = None;
+            let mut attempts = 0;
+            loop {
+                if self.expire_batch(attempts, &mut contexts, &mut batch).await {
+                    break;
+                }
+                if let Some(shaper) = &self.shaper {
+                    if self.shape_batch(shaper, &mut contexts, &mut batch) {
+                        break;
+                    }
+                    shaper
+                        .acquire(batch.items.iter().map(Media::proto_len).sum())
+                        .await;
+                }
+                attempts += 1;
+                let forward_result = {
+                    let _permit = self.inflight.acquire().await;
+                    let started = Instant::now();
+                    let forward_result = self.client.forward_batch(&batch).await;
+                    if let (Some(controller), Ok(results)) =
+                        (&self.congestion_controller, &forward_result)
+                    {
+                        controller.register(results, started.elapsed());
+                    }
+                    forward_result
+                };
+                let mut retry_after = None;
+                match forward_result {
+                    Ok(results) => {
+                        let total = results.len();
+                        let mut failed_contexts = Vec::new();
+                        let mut failed_items = Vec::new();
+                        let items = mem::take(&mut batch.items);
+                        for (((context, deadline), media), result) in
+                            mem::take(&mut contexts).into_iter().zip(items).zip(results)
+                        {
+                            if let Ok(ForwardResult::Success) = result {
+                                self.complete(context);
+                                continue;
+                            }
+                            let reason = failure_reason(&result);
+                            match self.retry_policy.action(&result) {
+                                RetryAction::Retry => {
+                                    log::debug!(
+                                        "Failure while sending message in batch: {}",
+                                        reason
+                                    );
+                                    retry_after = retry_after.max(
+                                        result.as_ref().ok().and_then(ForwardResult::retry_after),
+                                    );
+                                    failed_contexts.push((context, deadline));
+                                    failed_items.push(media);
+                                }
+                                action => self.give_up(action, context, &media, &reason).await,
+                            }
+                        }
+                        if failed_items.is_empty() {
+                            if retry.is_some() {
+                                log::info!(
+                                    "Success while sending batch of {} messages on {} retry",
+                                    total,
+                                    retry.unwrap().number()
+                                );
+                            } else {
+                                log::debug!(
+                                    "Success while sending batch of {} messages (retry=0)",
+                                    total
+                                );
+                            }
+                            break;
+                        }
+                        log::warn!(
+                            "Failure while sending batch (retry={}): {} of {} messages failed",
+                            retry.as_ref().map_or(0, |e| e.number()),
+                            failed_items.len(),
+                            total
+                        );
+                        contexts = failed_contexts;
+                        batch.items = failed_items;
+                    }
+                    Err(e) => {
+                        log::warn!(
+                            "Error while sending batch (retry={}): {:?}",
+                            retry.as_ref().map_or(0, |e| e.number()),
+                            e
+                        );
+                        let forward_result = Err(e);
+                        let action = self.retry_policy.action(&forward_result);
+                        if action != RetryAction::Retry {
+                            let reason = failure_reason(&forward_result);
+                            let items = mem::take(&mut batch.items);
+                            for ((context, _), media) in
+                                mem::take(&mut contexts).into_iter().zip(items)
+                            {
+                                self.give_up(action, context, &media, &reason).await;
+                            }
+                            break;
+                        }
+                    }
+                }
+                if self.expire_batch(attempts, &mut contexts, &mut batch).await {
+                    break;
+                }
+                retry = Some(self.wait_for_retry(retry, retry_after).await);
+            }
+        }
+    }
+
+    /// Waits for the next retry. If the server suggested a delay it is used instead of the delay
+    /// of the retry strategy, but the state of the retry strategy is advanced anyway.
+    async fn wait_for_retry(&self, retry: Option<Retry>, retry_after: Option<Duration>) -> Retry {
+        let next_retry = self.retry_strategy.next_retry(retry);
+        let sleep_duration = retry_after.unwrap_or(next_retry.delay());
+        log::warn!("Next retry after {} nanoseconds", sleep_duration.as_nanos());
+        // a zero duration disarms the timer
+        if !sleep_duration.is_zero() {
+            sleep(sleep_duration)
+                .await
+                .expect("Error while sleeping between attmpts to send a message");
+        }
+        next_retry
+    }
+
+    /// Strips the content of the message if only metadata should be forwarded.
+    fn prepare(&self, media: Media) -> Media {
+        if self.metadata_only {
+            strip_content(media)
+        } else {
+            media
+        }
+    }
+
+    fn deadline(&self, context: &MessageContext, media: &Media) -> Option<Instant> {
+        self.expiry
+            .as_ref()
+            .and_then(|e| e.deadline(context.received_at, media))
+    }
+
+    fn check_expiry(&self, attempts: u32, deadline: Option<Instant>) -> Option<String> {
+        self.expiry
+            .as_ref()
+            .and_then(|e| e.check(attempts, deadline))
+    }
+
+    /// Rejects expired messages of the batch. Returns `true` if no messages are left.
+    async fn expire_batch(
+        &self,
+        attempts: u32,
+        contexts: &mut Vec<(MessageContext, Option<Instant>)>,
+        batch: &mut MediaBatch,
+    ) -> bool {
+        let mut left_contexts = Vec::new();
+        let mut left_items = Vec::new();
+        let items = mem::take(&mut batch.items);
+        for ((context, deadline), media) in mem::take(contexts).into_iter().zip(items) {
+            match self.check_expiry(attempts, deadline) {
+                Some(reason) => self.reject(context, &media, &reason).await,
+                None => {
+                    left_contexts.push((context, deadline));
+                    left_items.push(media);
+                }
+            }
+        }
+        *contexts = left_contexts;
+        batch.items = left_items;
+        batch.items.is_empty()
+    }
+
+    /// Drops messages of the batch according to the shaper. Returns `true` if no messages are
+    /// left.
+    fn shape_batch(
+        &self,
+        shaper: &Shaper,
+        contexts: &mut Vec<(MessageContext, Option<Instant>)>,
+        batch: &mut MediaBatch,
+    ) -> bool {
+        let mut left_contexts = Vec::new();
+        let mut left_items = Vec::new();
+        let items = mem::take(&mut batch.items);
+        for ((context, deadline), media) in mem::take(contexts).into_iter().zip(items) {
+            if shaper.drops(&media) {
+                log::debug!("Message is dropped by bandwidth shaping");
+                self.complete(context);
+            } else {
+                left_contexts.push((context, deadline));
+                left_items.push(media);
+            }
+        }
+        *contexts = left_contexts;
+        batch.items = left_items;
+        batch.items.is_empty()
+    }
+
+    /// Completes the message that is not retried according to the action.
+    async fn give_up(
+        &self,
+        action: RetryAction,
+        context: MessageContext,
+        media: &Media,
+        reason: &str,
+    ) {
+        if action == RetryAction::Drop {
+            log::warn!("Message is dropped: {}", reason);
+            self.complete(context);
+        } else {
+            self.reject(context, media, reason).await;
+        }
+    }
+
+    /// Completes the message that is not forwarded writing it to the dead letter sink.
+    async fn reject(&self, context: MessageContext, media: &Media, reason: &str) {
+        log::warn!("Message is not forwarded: {}", reason);
+        if let Some(dead_letter) = &self.dead_letter {
+            // the file or the socket is written by a blocking thread
+            let dead_letter = dead_letter.clone();
+            let media = media.clone();
+            let reason = reason.to_string();
+            let send_result =
+                tokio::task::spawn_blocking(move || dead_letter.send(&media, &reason))
+                    .await
+                    .expect("Error in dead letter task");
+            if let Err(e) = send_result {
+                log::warn!("Error while writing message to dead letter sink: {:?}", e);
+            }
+        }
+        self.complete(context);
+    }
+
+    fn complete(&self, context: MessageContext) {
+        if let (Some(spool), Some(spool_id)) = (&self.spool, context.spool_id) {
+            // an acknowledgement not written before the shutdown results in a replay
+            let spool = spool.clone();
+            tokio::task::spawn_blocking(move || spool.acknowledge(spool_id));
+        }
+        if let Some(stat_id) = context.statistics_id {
+            if let Err(e) = self
+                .statistics_service
+                .as_ref()
+                .as_ref()
+                .unwrap()
+                .register_message_end(stat_id)
+            {
+                log::warn!("Error while ending message statistics: {:?}", e)
+            }
+        }
+    }
+}
+
+fn failure_reason(result: &anyhow::Result<ForwardResult>) -> String {
+    match result {
+        Ok(ForwardResult::HttpStatus(status)) | Ok(ForwardResult::Overloaded { status, .. }) => {
+            format!("HTTP status {}", status)
+        }
+        Ok(result) => format!("{:?}", result),
+        Err(e) => format!("{:#}", e),
+    }
+}
+
+/// Settings of lanes of [`Dispatcher`].
+#[derive(Clone)]
+pub struct DispatchOptions {
+    /// `true` if messages from different sources are forwarded via separate lanes
+    pub per_source: bool,
+    /// The maximum number of messages waiting in each lane
+    pub lane_size: usize,
+    /// A policy to handle new messages if a lane is full
+    pub overflow: QueueOverflowPolicy,
+    /// A time after which an empty lane is closed
+    pub idle_timeout: Duration,
+    /// A statistics service to register messages dropped by lanes
+    pub statistics_service: Arc<Option<StatisticsService>>,
+}
+
+impl Default for DispatchOptions {
+    fn default() -> Self {
+        Self {
+            per_source: false,
+            lane_size: 1,
+            overflow: QueueOverflowPolicy::default(),
+            idle_timeout: LANE_IDLE_TIMEOUT,
+            statistics_service: Arc::new(None),
+        }
+    }
+}
+
+/// A queue of messages of a destination and a source forwarded by a separate task.
+struct Lane {
+    queue: Arc<MessageQueue>,
+    handle: JoinHandle<()>,
+    /// The time when the last message is added
+    used_at: Instant,
+}
+
+/// Distributes messages between lanes each of which is forwarded by a separate task with the
+/// forwarder of its destination. If `per_source` is set messages of each source (a source id or
+/// a topic if the message has no source id) are queued in a separate lane, otherwise all
+/// messages of a destination are queued in the single lane.
+///
+/// If a lane is full a new message is handled according to the overflow policy, so a slow lane
+/// does not delay other lanes unless the policy is [`QueueOverflowPolicy::Block`]. A lane is
+/// closed if it is empty for the idle timeout.
+pub struct Dispatcher {
+    forwarders: Vec<Arc<Forwarder>>,
+    options: DispatchOptions,
+    lanes: HashMap<(usize, String), Lane>,
+    /// Tasks of closed lanes which may still forward their last messages
+    closing: HashMap<(usize, String), JoinHandle<()>>,
+}
+
+impl Dispatcher {
+    /// Constructs a new instance.
+    ///
+    /// # Arguments
+    /// * `forwarders` - forwarders for destinations in order of their indexes
+    /// * `options` - settings of lanes
+    pub fn new(forwarders: Vec<Arc<Forwarder>>, options: DispatchOptions) -> Self {
+        Self {
+            forwarders,
+            options,
+            lanes: HashMap::new(),
+            closing: HashMap::new(),
+        }
+    }
+
+    /// Dispatches messages from the queue until it is closed and waits for all lanes to be
+    /// forwarded.
+    ///
+    /// # Arguments
+    /// * `receiver` - a queue of messages
+    /// * `select` - returns an index of the destination of the message
+    pub async fn run(
+        mut self,
+        receiver: &mut Receiver<(MessageContext, Media)>,
+        select: impl Fn(&Media) -> usize,
+    ) {
+        let reaping = sleep(self.options.idle_timeout);
+        tokio::pin!(reaping);
+        loop {
+            tokio::select! {
+                result = &mut reaping => {
+                    result.expect("Error while waiting for idle lanes");
+                    self.reap();
+                    reaping.set(sleep(self.options.idle_timeout));
+                }
+                item = receiver.recv() => match item {
+                    Some((context, media)) => {
+                        let destination = select(&media);
+                        if let Err(e) = self.push(destination, context, media).await {
+                            log::warn!("Error while dispatching message: {:?}", e);
+                            break;
+                        }
+                    }
+                    None => break,
+                }
+            }
+        }
+        for lane in self.lanes.values() {
+            lane.queue.close();
+        }
+        let handles = self
+            .lanes
+            .into_values()
+            .map(|e| e.handle)
+            .chain(self.closing.into_values());
+        for handle in handles {
+            if let Err(e) = handle.await {
+                log::warn!("Error in message forwarding task: {:?}", e);
+            }
+        }
+    }
+
+    async fn push(
+        &mut self,
+        destination: usize,
+        context: MessageContext,
+        media: Media,
+    ) -> anyhow::Result<()> {
+        let info = context.info(&media);
+        let source = if self.options.per_source {
+            info.source.clone()
+        } else {
+            String::new()
+        };
+        let lane = match self.lanes.entry((destination, source)) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                log::debug!(
+                    "Starting forwarding for destination {} and source {}",
+                    entry.key().0,
+                    entry.key().1
+                );
+                // the lane of the same key closed earlier is forwarded first to keep the order
+                let previous = self.closing.remove(entry.key());
+                let lane = start_lane(
+                    self.forwarders[destination].clone(),
+                    &self.options,
+                    previous,
+                );
+                entry.insert(lane)
+            }
+        };
+        lane.used_at = Instant::now();
+        lane.queue.push(context, media, info).await
+    }
+
+    /// Closes lanes which are empty for the idle timeout.
+    fn reap(&mut self) {
+        let idle_timeout = self.options.idle_timeout;
+        let idle = self
+            .lanes
+            .iter()
+            .filter(|(_, lane)| lane.used_at.elapsed() >= idle_timeout && lane.queue.is_empty())
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<(usize, String)>>();
+        for key in idle {
+            log::debug!(
+                "Stopping forwarding for destination {} and source {}",
+                key.0,
+                key.1
+            );
+            let lane = self.lanes.remove(&key).unwrap();
+            lane.queue.close();
+            self.closing.insert(key, lane.handle);
+        }
+        self.closing.retain(|_, handle| !handle.is_finished());
+    }
+}
+
+fn start_lane(
+    forwarder: Arc<Forwarder>,
+    options: &DispatchOptions,
+    previous: Option<JoinHandle<()>>,
+) -> Lane {
+    let queue = Arc::new(MessageQueue::new(
+        options.overflow,
+        options.lane_size,
+        None,
+        None,
+        options.statistics_service.clone(),
+    ));
+    let lane_queue = queue.clone();
+    let handle = tokio::spawn(async move {
+        if let Some(previous) = previous {
+            if let Err(e) = previous.await {
+                log::warn!("Error in message forwarding task: {:?}", e);
+            }
+        }
+        let (sender, mut receiver) = mpsc::channel(1);
+        let feeding = async move {
+            while let Some(item) = lane_queue.pop().await {
+                if sender.send(item).await.is_err() {
+                    break;
+                }
+            }
+        };
+        tokio::join!(feeding, forwarder.run(&mut receiver));
+    });
+    Lane {
+        queue,
+        handle,
+        used_at: Instant::now(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use reqwest::StatusCode;
+    use tokio::sync::mpsc;
+    use tokio::sync::mpsc::Receiver;
+    use wiremock::matchers::method;
+    use wiremock::{Match, Mock, MockServer, Request, ResponseTemplate};
+
+    use media_gateway_common::model::{DeadLetter, Media};
+
+    use crate::configuration::{DeadLetterConfiguration, ExpiryConfiguration, QueueOverflowPolicy};
+    use crate::dead_letter::DeadLetterSink;
+    use crate::expiry::Expiry;
+    use crate::forwarder::{
+        DispatchOptions, Dispatcher, Forwarder, ForwarderOptions, MessageContext,
+    };
+    use crate::retry::{RetryAction, RetryPolicy, RetryStrategy};
+
+    #[tokio::test]
+    async fn dispatch_per_source_order() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(StatusCode::OK))
+            .mount(&server)
+            .await;
+        let forwarder = Arc::new(Forwarder::with_url(
+            server.uri(),
+            ForwarderOptions {
+                retry_strategy: new_retry_strategy(),
+                max_inflight: 4,
+                ..Default::default()
+            },
+        ));
+        let (sender, mut receiver) = mpsc::channel(100);
+        for i in 0..20u8 {
+            let media = Media {
+                message: None,
+                topic: format!("source{}", i % 3).into_bytes(),
+                data: vec![vec![i]],
+            };
+            sender
+                .send((MessageContext::default(), media))
+                .await
+                .unwrap();
+        }
+        drop(sender);
+
+        dispatch(
+            forwarder,
+            &mut receiver,
+            DispatchOptions {
+                per_source: true,
+                lane_size: 10,
+                ..Default::default()
+            },
+        )
+        .await;
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 20);
+        for source in 0..3u8 {
+            let topic = format!("source{}", source).into_bytes();
+            let data = requests
+                .iter()
+                .map(|e| Media::from_proto(&e.body).unwrap())
+                .filter(|e| e.topic == topic)
+                .map(|e| e.data[0][0])
+                .collect::<Vec<u8>>();
+            let expected = (0..20u8).filter(|e| e % 3 == source).collect::<Vec<u8>>();
+            assert_eq!(data, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn dispatch_slow_source() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(TopicMatcher("slow"))
+            .respond_with(
+                ResponseTemplate::new(StatusCode::OK).set_delay(Duration::from_millis(500)),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(StatusCode::OK))
+            .mount(&server)
+            .await;
+        let forwarder = Arc::new(Forwarder::with_url(
+            server.uri(),
+            ForwarderOptions {
+                max_inflight: 4,
+                ..Default::default()
+            },
+        ));
+        let (sender, mut receiver) = mpsc::channel(100);
+        // the lane of the slow source overflows
+        for (topic, i) in (0..10u8)
+            .map(|e| ("slow", e))
+            .chain((0..3u8).map(|e| ("fast", e)))
+        {
+            let media = Media {
+                message: None,
+                topic: topic.as_bytes().to_vec(),
+                data: vec![vec![i]],
+            };
+            sender
+                .send((MessageContext::default(), media))
+                .await
+                .unwrap();
+        }
+        let dispatching = tokio::spawn(async move {
+            let options = DispatchOptions {
+                per_source: true,
+                lane_size: 2,
+                overflow: QueueOverflowPolicy::DropOldest,
+                ..Default::default()
+            };
+            dispatch(forwarder, &mut receiver, options).await;
+        });
+
+        // messages of the fast source are not blocked by the slow one
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(received_data(&server, "fast").await, vec![0, 1, 2]);
+
+        drop(sender);
+        dispatching.await.unwrap();
+        let slow_data = received_data(&server, "slow").await;
+        assert!(slow_data.len() < 10);
+        assert_eq!(slow_data.last(), Some(&9));
+    }
+
+    #[tokio::test]
+    async fn dispatch_after_idle_timeout() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(StatusCode::OK))
+            .mount(&server)
+            .await;
+        let forwarder = Arc::new(Forwarder::with_url(server.uri(), Default::default()));
+        let (sender, mut receiver) = mpsc::channel(10);
+        let dispatching = tokio::spawn(async move {
+            let options = DispatchOptions {
+                per_source: true,
+                lane_size: 10,
+                idle_timeout: Duration::from_millis(50),
+                ..Default::default()
+            };
+            dispatch(forwarder, &mut receiver, options).await;
+        });
+        for i in 0..4u8 {
+            if i == 2 {
+                // the lane is closed and started again
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+            let media = Media {
+                message: None,
+                topic: "source".as_bytes().to_vec(),
+                data: vec![vec![i]],
+            };
+            sender
+                .send((MessageContext::default(), media))
+                .await
+                .unwrap();
+        }
+        drop(sender);
+        dispatching.await.unwrap();
+
+        assert_eq!(received_data(&server, "source").await, vec![0, 1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn retry_after_overloaded() {
+        let elapsed = retry_after_test("1", Duration::from_millis(1)).await;
+
+        assert!(elapsed >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn retry_after_shorter_than_backoff() {
+        let elapsed = retry_after_test("0", Duration::from_secs(60)).await;
+
+        assert!(elapsed < Duration::from_secs(60));
+    }
+
+    /// Sends a message rejected once with the delay in `Retry-After` header. Returns the time
+    /// spent to forward the message.
+    async fn retry_after_test(retry_after: &str, delay: Duration) -> Duration {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(StatusCode::TOO_MANY_REQUESTS)
+                    .insert_header("retry-after", retry_after),
+            )
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(StatusCode::OK))
+            .mount(&server)
+            .await;
+        let forwarder = Arc::new(Forwarder::with_url(
+            server.uri(),
+            ForwarderOptions {
+                retry_strategy: RetryStrategy::Fixed { delay },
+                ..Default::default()
+            },
+        ));
+        let (sender, mut receiver) = mpsc::channel(1);
+        let media = Media {
+            message: None,
+            topic: "source".as_bytes().to_vec(),
+            data: vec![vec![1]],
+        };
+        sender
+            .send((MessageContext::default(), media))
+            .await
+            .unwrap();
+        drop(sender);
+        let started = Instant::now();
+
+        dispatch(forwarder, &mut receiver, new_dispatch_options()).await;
+
+        let elapsed = started.elapsed();
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 2);
+        elapsed
+    }
+
+    #[tokio::test]
+    async fn dead_letter_rejected() {
+        let reasons = dead_letter_test(StatusCode::BAD_REQUEST, RetryPolicy::default(), 1).await;
+
+        assert_eq!(reasons, vec!["HTTP status 400 Bad Request"; 3]);
+    }
+
+    #[tokio::test]
+    async fn dead_letter_max_attempts() {
+        let reasons =
+            dead_letter_test(StatusCode::INTERNAL_SERVER_ERROR, RetryPolicy::default(), 2).await;
+
+        assert_eq!(reasons, vec!["max_attempts 2 is reached"; 3]);
+    }
+
+    #[tokio::test]
+    async fn drop_by_retry_policy() {
+        let retry_policy = RetryPolicy {
+            http_statuses: Some(HashMap::from([(503, RetryAction::Drop)])),
+            ..Default::default()
+        };
+
+        let reasons = dead_letter_test(StatusCode::SERVICE_UNAVAILABLE, retry_policy, 1).await;
+
+        assert!(reasons.is_empty());
+    }
+
+    /// Forwards 3 messages to the server responding with the status. Returns dead letter reasons.
+    async fn dead_letter_test(
+        status: StatusCode,
+        retry_policy: RetryPolicy,
+        expected_attempts: usize,
+    ) -> Vec<String> {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(status))
+            .mount(&server)
+            .await;
+        let path = std::env::temp_dir().join(format!("dead-letter-{}", rand::random::<u64>()));
+        let forwarder = Arc::new(Forwarder::with_url(
+            server.uri(),
+            ForwarderOptions {
+                retry_strategy: new_retry_strategy(),
+                retry_policy,
+                expiry: Some(
+                    Expiry::try_from(&ExpiryConfiguration {
+                        max_attempts: Some(2),
+                        max_age: None,
+                        age_source: None,
+                    })
+                    .unwrap(),
+                ),
+                dead_letter: Some(Arc::new(
+                    DeadLetterSink::try_from(&DeadLetterConfiguration::File {
+                        path: path.to_str().unwrap().to_string(),
+                    })
+                    .unwrap(),
+                )),
+                ..Default::default()
+            },
+        ));
+        let (sender, mut receiver) = mpsc::channel(10);
+        for i in 0..3u8 {
+            let media = Media {
+                message: None,
+                topic: "source".as_bytes().to_vec(),
+                data: vec![vec![i]],
+            };
+            sender
+                .send((MessageContext::default(), media))
+                .await
+                .unwrap();
+        }
+        drop(sender);
+
+        dispatch(forwarder, &mut receiver, new_dispatch_options()).await;
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 3 * expected_attempts);
+        let content = fs::read(&path).unwrap();
+        fs::remove_file(path).unwrap();
+        let mut reasons = Vec::new();
+        let mut position = 0;
+        while position < content.len() {
+            let length =
+                u32::from_le_bytes(content[position..position + 4].try_into().unwrap()) as usize;
+            position += 4;
+            let dead_letter =
+                DeadLetter::from_proto(&content[position..position + length]).unwrap();
+            position += length;
+            reasons.push(dead_letter.reason);
+        }
+        reasons
+    }
+
+    /// Matches requests with messages of the topic.
+    struct TopicMatcher(&'static str);
+
+    impl Match for TopicMatcher {
+        fn matches(&self, request: &Request) -> bool {
+            Media::from_proto(&request.body).is_ok_and(|e| e.topic == self.0.as_bytes())
+        }
+    }
+
+    /// Returns the first bytes of data of messages of the topic received by the server.
+    async fn received_data(server: &MockServer, topic: &str) -> Vec<u8> {
+        server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|e| Media::from_proto(&e.body).unwrap())
+            .filter(|e| e.topic == topic.as_bytes())
+            .map(|e| e.data[0][0])
+            .collect()
+    }
+
+    /// Forwards messages via lanes of the single destination.
+    async fn dispatch(
+        forwarder: Arc<Forwarder>,
+        receiver: &mut Receiver<(MessageContext, Media)>,
+        options: DispatchOptions,
+    ) {
+        Dispatcher::new(vec![forwarder], options)
+            .run(receiver, |_| 0)
+            .await
+    }
+
+    fn new_dispatch_options() -> DispatchOptions {
+        DispatchOptions {
+            lane_size: 10,
+            ..Default::default()
+        }
+    }
+
+    fn new_retry_strategy() -> RetryStrategy {
+        RetryStrategy::Exponential {
+            initial_delay: Duration::from_millis(1),
+            maximum_delay: Duration::from_millis(10),
+            multiplier: 2,
+        }
+    }
+}
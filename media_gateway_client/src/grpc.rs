@@ -0,0 +1,172 @@
+//! A gRPC transport for the media gateway client.
+//!
+//! The module provides [`GrpcClient`] that streams messages over a single bidirectional gRPC
+//! stream and matches responses with requests by ids.
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use tokio::sync::{mpsc, Mutex};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::transport::Channel;
+use tonic::Streaming;
+
+use media_gateway_common::grpc::gateway_client::GatewayClient as GatewayGrpcClient;
+use media_gateway_common::model::{Media, MediaRequest, MediaResponse};
+
+use crate::client::ForwardResult;
+use crate::pending::PendingRequests;
+
+const REQUEST_CHANNEL_SIZE: usize = 16;
+
+/// A client that streams messages to the media gateway server over gRPC.
+///
+/// The stream is opened on the first message. If the stream is broken all pending messages fail
+/// and the stream is reopened on the next message. If the stream cannot be opened the message
+/// fails, so attempts to open the stream are retried by the forwarder. If the response is not
+/// received within the response timeout the message fails with [`ForwardResult::AckTimeout`].
+pub struct GrpcClient {
+    channel: Channel,
+    authorization: Option<MetadataValue<Ascii>>,
+    response_timeout: Duration,
+    connection: Mutex<Option<Arc<Connection>>>,
+}
+
+struct Connection {
+    sender: mpsc::Sender<MediaRequest>,
+    pending: Arc<PendingRequests>,
+}
+
+impl GrpcClient {
+    /// Constructs a new instance of the client.
+    ///
+    /// # Arguments
+    /// * `channel` - a channel to the gRPC endpoint of the media gateway server (TLS settings)
+    /// * `authorization` - a value of `authorization` metadata
+    /// * `response_timeout` - the maximum time to wait for the response to a message
+    pub fn new(
+        channel: Channel,
+        authorization: Option<MetadataValue<Ascii>>,
+        response_timeout: Duration,
+    ) -> Self {
+        Self {
+            channel,
+            authorization,
+            response_timeout,
+            connection: Mutex::new(None),
+        }
+    }
+
+    /// Sends the message to the media gateway server and waits for the response.
+    pub async fn forward_message(&self, media: &Media) -> anyhow::Result<ForwardResult> {
+        let connection = self.connection().await?;
+        let (id, receiver) = connection.pending.register()?;
+
+        let request = MediaRequest {
+            id,
+            media: Some(media.clone()),
+        };
+        if connection.sender.send(request).await.is_err() {
+            connection.pending.close();
+            return Err(anyhow!("Error while sending a message: stream is closed"));
+        }
+
+        connection
+            .pending
+            .wait(id, receiver, self.response_timeout)
+            .await
+    }
+
+    /// Closes the stream if it is open.
+    pub async fn shutdown(&self) {
+        if let Some(connection) = self.connection.lock().await.take() {
+            // the stream is finished when the last sender of requests is dropped
+            connection.pending.close();
+        }
+    }
+
+    /// Returns the open stream or opens a new one.
+    async fn connection(&self) -> anyhow::Result<Arc<Connection>> {
+        let mut connection = self.connection.lock().await;
+        if let Some(current) = connection.as_ref() {
+            if !current.pending.is_closed() {
+                return Ok(current.clone());
+            }
+        }
+        let new_connection = self
+            .connect()
+            .await
+            .map_err(|e| e.context("Error while opening stream"))?;
+        log::info!("gRPC stream is opened");
+        *connection = Some(new_connection.clone());
+        Ok(new_connection)
+    }
+
+    async fn connect(&self) -> anyhow::Result<Arc<Connection>> {
+        let (sender, receiver) = mpsc::channel(REQUEST_CHANNEL_SIZE);
+        let mut request = tonic::Request::new(ReceiverStream::new(receiver));
+        if let Some(authorization) = &self.authorization {
+            request
+                .metadata_mut()
+                .insert("authorization", authorization.clone());
+        }
+        let mut client = GatewayGrpcClient::new(self.channel.clone());
+        let responses = client.forward(request).await?.into_inner();
+
+        let pending = Arc::new(PendingRequests::new());
+        tokio::spawn(receive(responses, pending.clone()));
+        Ok(Arc::new(Connection { sender, pending }))
+    }
+}
+
+async fn receive(mut responses: Streaming<MediaResponse>, pending: Arc<PendingRequests>) {
+    loop {
+        match responses.message().await {
+            Ok(Some(response)) => pending.complete(&response),
+            Ok(None) => {
+                log::info!("gRPC stream is closed by the server");
+                break;
+            }
+            Err(status) => {
+                log::warn!("Error while receiving a response: {:?}", status);
+                break;
+            }
+        }
+    }
+    pending.close();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::net::TcpListener;
+    use tonic::transport::Endpoint;
+
+    use media_gateway_common::model::Media;
+
+    use crate::grpc::GrpcClient;
+
+    #[tokio::test]
+    async fn stream_error() {
+        // the port is free after the listener is dropped
+        let address = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let channel = Endpoint::from_shared(format!("http://{}", address))
+            .unwrap()
+            .connect_lazy();
+        let client = GrpcClient::new(channel, None, Duration::from_secs(1));
+        let media = Media {
+            message: None,
+            topic: "topic".as_bytes().to_vec(),
+            data: vec![vec![1]],
+        };
+
+        assert!(client.forward_message(&media).await.is_err());
+        client.shutdown().await;
+    }
+}
@@ -0,0 +1,130 @@
+//! Access control of the ingest endpoint.
+//!
+//! The module provides [`IngestAccess`].
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::http::StatusCode;
+use actix_web::HttpRequest;
+use http_auth_basic::Credentials;
+
+use crate::configuration::IngestConfiguration;
+
+/// The default maximum size of a request body in bytes
+pub const DEFAULT_MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+/// Checks whether a request to the ingest endpoint is allowed.
+pub struct IngestAccess {
+    /// `true` if only requests from loopback addresses are allowed
+    local_only: bool,
+    /// The expected value of the authorization header if basic authentication is enabled
+    authorization: Option<String>,
+    max_body_size: usize,
+}
+
+impl IngestAccess {
+    /// Returns the maximum size of a request body in bytes.
+    pub fn max_body_size(&self) -> usize {
+        self.max_body_size
+    }
+
+    /// Returns an error status if the request is not allowed.
+    pub fn authorize(&self, request: &HttpRequest) -> Result<(), StatusCode> {
+        if self.local_only && !request.peer_addr().is_some_and(|e| e.ip().is_loopback()) {
+            return Err(StatusCode::FORBIDDEN);
+        }
+        if let Some(authorization) = &self.authorization {
+            let header = request
+                .headers()
+                .get(AUTHORIZATION)
+                .and_then(|e| e.to_str().ok());
+            if header != Some(authorization.as_str()) {
+                return Err(StatusCode::UNAUTHORIZED);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<&IngestConfiguration> for IngestAccess {
+    fn from(configuration: &IngestConfiguration) -> Self {
+        Self {
+            local_only: configuration.local_only.unwrap_or(true),
+            authorization: configuration
+                .auth
+                .as_ref()
+                .map(|e| Credentials::new(&e.basic.username, &e.basic.password).as_http_header()),
+            max_body_size: configuration.max_body_size.unwrap_or(DEFAULT_MAX_BODY_SIZE),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header::AUTHORIZATION;
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use http_auth_basic::Credentials;
+
+    use media_gateway_common::configuration::Credentials as BasicCredentials;
+
+    use crate::configuration::{AuthConfiguration, IngestConfiguration};
+    use crate::ingest::IngestAccess;
+
+    #[test]
+    fn local_only() {
+        let access = IngestAccess::from(&IngestConfiguration {
+            local_only: None,
+            auth: None,
+            max_body_size: None,
+        });
+
+        let local_request = TestRequest::default()
+            .peer_addr("127.0.0.1:12345".parse().unwrap())
+            .to_http_request();
+        let remote_request = TestRequest::default()
+            .peer_addr("192.168.0.1:12345".parse().unwrap())
+            .to_http_request();
+
+        assert_eq!(access.authorize(&local_request), Ok(()));
+        assert_eq!(
+            access.authorize(&remote_request),
+            Err(StatusCode::FORBIDDEN)
+        );
+    }
+
+    #[test]
+    fn basic_auth() {
+        let access = IngestAccess::from(&IngestConfiguration {
+            local_only: Some(false),
+            auth: Some(AuthConfiguration {
+                basic: BasicCredentials {
+                    username: "user".to_string(),
+                    password: "password".to_string(),
+                },
+            }),
+            max_body_size: None,
+        });
+
+        let authorized_request = TestRequest::default()
+            .insert_header((
+                AUTHORIZATION,
+                Credentials::new("user", "password").as_http_header(),
+            ))
+            .to_http_request();
+        let unauthorized_request = TestRequest::default()
+            .insert_header((
+                AUTHORIZATION,
+                Credentials::new("user", "wrong").as_http_header(),
+            ))
+            .to_http_request();
+
+        assert_eq!(access.authorize(&authorized_request), Ok(()));
+        assert_eq!(
+            access.authorize(&unauthorized_request),
+            Err(StatusCode::UNAUTHORIZED)
+        );
+        assert_eq!(
+            access.authorize(&TestRequest::default().to_http_request()),
+            Err(StatusCode::UNAUTHORIZED)
+        );
+    }
+}
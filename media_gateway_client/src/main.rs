@@ -1,9 +1,9 @@
 //! A client application for [`media_gateway_server`](https://github.com/insight-platform/MediaGateway).
 //!
 //! The application reads messages from [ZeroMQ](https://zeromq.org/) using
-//! [`NonBlockingReader`](savant_core::transport::zeromq::NonBlockingReader) from `savant_core`
-//! crate and sends them to the server using [`Client`](reqwest::Client) from `reqwest` crate
-//! concurrently.
+//! [`SyncReader`](savant_core::transport::zeromq::SyncReader) from `savant_core` crate in a
+//! dedicated thread for each source and sends them to the server using
+//! [`Client`](reqwest::Client) from `reqwest` crate concurrently.
 
 //! To run the client
 //! ```bash
@@ -14,10 +14,33 @@
 //! * TLS (including a self-signed PEM encoded certificate)
 //! * client certificate authentication
 //! * basic authentication
+//! * batches
+//! * compression (zstd, gzip)
+//! * WebSocket streaming
+//! * gRPC streaming
+//! * disk-backed store-and-forward spool
+//! * multiple server endpoints with failover and load balancing
+//! * message expiry and dead-letter handling
+//! * backoff honoring `Retry-After` header of overloaded server responses
+//! * multiple ZeroMQ sources
+//! * rule-based routing of messages to different servers
+//! * sampling of video frames
+//! * overflow policies of the message queue (block, drop oldest, drop newest, drop GOP)
+//! * priority classes of messages with strict or weighted priorities
+//! * weighted fair queuing across sources with queue depths reported at `/queue` endpoint
+//! * bandwidth shaping with a token bucket with its state reported at `/shaper` endpoint
+//! * adaptive congestion control of frame rates with its state reported at `/congestion`
+//!   endpoint
+//! * metadata-only forwarding of video frames without their content
+//! * downscaling and JPEG re-encoding of JPEG or PNG images in video frames
+//! * suppression of duplicate video frames of static scenes
+//! * enrichment of messages with routing labels and propagated context of the client
+//! * HTTP ingest of messages at `/ingest` endpoint for producers without ZeroMQ
+//! * scheduled upload windows with buffering of messages by cron schedules
 //!
 //! # Examples
 //! See [configuration files](https://github.com/insight-platform/MediaGateway/blob/main/samples/configuration/client).
-//! `in_stream` fields represents configuration for
+//! `in_stream` (and each of `in_streams`) fields represents configuration for
 //! [`ReaderConfigBuilder`](savant_core::transport::zeromq::ReaderConfigBuilder).
 use std::env::args;
 use std::sync::Arc;
@@ -31,13 +54,37 @@ use media_gateway_common::api::health;
 use media_gateway_common::health::HealthService;
 
 use crate::configuration::GatewayClientConfiguration;
+use crate::ingest::{IngestAccess, DEFAULT_MAX_BODY_SIZE};
 use crate::service::GatewayClientService;
 
+mod api;
+mod batch;
 mod client;
+mod compression;
 pub mod configuration;
+mod congestion;
+mod dead_letter;
+mod deduplication;
+mod endpoint;
+mod enrichment;
+mod expiry;
+mod fair_queuing;
+mod forwarder;
+mod grpc;
+mod ingest;
+mod metadata;
+mod pending;
+mod priority;
+mod queue;
 mod retry;
+mod routing;
+mod sampling;
+mod schedule;
 mod service;
-mod wait;
+mod shaper;
+mod spool;
+mod transcoding;
+mod websocket;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -61,6 +108,14 @@ async fn main() -> Result<()> {
     let health_service = web::Data::new(HealthService::new());
     let service = Arc::new(GatewayClientService::try_from(&conf)?);
     let service_to_stop = service.clone();
+    let service_data = web::Data::from(service.clone());
+    let ingest_access = conf.ingest.as_ref().map(IngestAccess::from);
+    let ingest_payload_config = web::PayloadConfig::new(
+        ingest_access
+            .as_ref()
+            .map_or(DEFAULT_MAX_BODY_SIZE, IngestAccess::max_body_size),
+    );
+    let ingest_data = web::Data::new(ingest_access);
 
     tokio::spawn(async move {
         let mut interrupt_signal = unix::signal(unix::SignalKind::interrupt()).unwrap();
@@ -82,7 +137,17 @@ async fn main() -> Result<()> {
     HttpServer::new(move || {
         App::new()
             .app_data(health_service.clone())
+            .app_data(service_data.clone())
+            .app_data(ingest_data.clone())
             .route("/health", web::get().to(health))
+            .route("/queue", web::get().to(api::queue))
+            .route("/shaper", web::get().to(api::shaper))
+            .route("/congestion", web::get().to(api::congestion))
+            .service(
+                web::resource("/ingest")
+                    .app_data(ingest_payload_config.clone())
+                    .route(web::post().to(api::ingest)),
+            )
     })
     .bind(bind_address)?
     .run()
@@ -0,0 +1,107 @@
+//! Stripping of video frame content for metadata-only forwarding.
+use savant_core::message::Message;
+use savant_core::primitives::frame::VideoFrameContent;
+
+use media_gateway_common::model::Media;
+
+/// Replaces the content of a video frame with [`VideoFrameContent::None`] and drops data
+/// attachments. Objects, attributes and timestamps of the frame as well as routing labels and
+/// propagated context of the message are kept. Other messages are returned unchanged.
+pub fn strip_content(media: Media) -> Media {
+    let message = match media.message.as_ref().map(Message::try_from) {
+        Some(Ok(message)) => message,
+        Some(Err(e)) => {
+            log::warn!("Error while decoding message to strip content: {:?}", e);
+            return media;
+        }
+        None => return media,
+    };
+    let mut frame = match message.as_video_frame() {
+        Some(frame) => frame,
+        None => return media,
+    };
+    frame.set_content(VideoFrameContent::None);
+    let mut stripped = Message::video_frame(&frame);
+    *stripped.meta_mut() = message.meta().clone();
+    Media {
+        message: Some(savant_protobuf::generated::Message::from(&stripped)),
+        topic: media.topic,
+        data: vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use savant_core::message::Message;
+    use savant_core::primitives::frame::{
+        VideoFrameContent, VideoFrameProxy, VideoFrameTranscodingMethod,
+    };
+
+    use media_gateway_common::model::Media;
+
+    use crate::metadata::strip_content;
+
+    #[test]
+    fn strip_video_frame() {
+        let mut message = new_frame(VideoFrameContent::Internal(vec![0; 1024]));
+        message.meta_mut().routing_labels = vec!["analytics".to_string()];
+        message
+            .meta_mut()
+            .span_context
+            .0
+            .insert("key".to_string(), "value".to_string());
+        let media = Media {
+            message: Some(savant_protobuf::generated::Message::from(&message)),
+            topic: "topic".as_bytes().to_vec(),
+            data: vec![vec![1, 2, 3]],
+        };
+        let max_len = media.proto_len() - 1024;
+
+        let media = strip_content(media);
+
+        assert!(media.proto_len() <= max_len);
+        let message = Message::try_from(media.message.as_ref().unwrap()).unwrap();
+        let frame = message.as_video_frame().unwrap();
+        assert_eq!(frame.get_source_id(), "source");
+        assert_eq!(frame.get_pts(), 42);
+        assert_eq!(message.meta().routing_labels, vec!["analytics"]);
+        assert_eq!(
+            message.meta().span_context.0.get("key").map(String::as_str),
+            Some("value")
+        );
+        assert!(media.data.is_empty());
+    }
+
+    #[test]
+    fn keep_other_messages() {
+        let media = Media {
+            message: Some(savant_protobuf::generated::Message::from(
+                &Message::unknown("message".to_string()),
+            )),
+            topic: "topic".as_bytes().to_vec(),
+            data: vec![vec![1, 2, 3]],
+        };
+
+        let media = strip_content(media);
+
+        assert_eq!(media.data, vec![vec![1, 2, 3]]);
+    }
+
+    fn new_frame(content: VideoFrameContent) -> Message {
+        let frame = VideoFrameProxy::new(
+            "source",
+            "30/1",
+            1280,
+            720,
+            content,
+            VideoFrameTranscodingMethod::Copy,
+            &None,
+            Some(true),
+            (1, 1000000),
+            42,
+            None,
+            None,
+        );
+        Message::video_frame(&frame)
+    }
+}
@@ -0,0 +1,177 @@
+//! Requests waiting for responses via streaming transports.
+//!
+//! The module provides [`PendingRequests`].
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use tokio::sync::oneshot;
+use tokio_timerfd::sleep;
+
+use media_gateway_common::model::{MediaResponse, MediaStatus};
+
+use crate::client::ForwardResult;
+
+/// Requests sent via a single connection and waiting for responses. Responses are matched with
+/// requests by ids.
+pub struct PendingRequests {
+    /// Senders for responses by request ids. [`None`] if the connection is closed.
+    requests: Mutex<Option<HashMap<u64, oneshot::Sender<MediaStatus>>>>,
+    next_id: AtomicU64,
+}
+
+impl PendingRequests {
+    pub fn new() -> Self {
+        Self {
+            requests: Mutex::new(Some(HashMap::new())),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Registers a new request. Returns an id for the request and a receiver for the response.
+    pub fn register(&self) -> anyhow::Result<(u64, ResponseReceiver)> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = oneshot::channel();
+        match self.requests.lock().unwrap().as_mut() {
+            Some(requests) => requests.insert(id, sender),
+            None => return Err(anyhow!("Connection is closed")),
+        };
+        Ok((id, ResponseReceiver(receiver)))
+    }
+
+    /// Passes the response to the request with the same id.
+    pub fn complete(&self, response: &MediaResponse) {
+        let sender = match self.requests.lock().unwrap().as_mut() {
+            Some(requests) => requests.remove(&response.id),
+            None => None,
+        };
+        match (sender, MediaStatus::try_from(response.status)) {
+            (Some(sender), Ok(status)) => {
+                let _ = sender.send(status);
+            }
+            (Some(_), Err(_)) => log::warn!("Unknown media status: {}", response.status),
+            (None, _) => log::warn!("Unexpected response id: {}", response.id),
+        }
+    }
+
+    /// Waits for the response to the request with the id. If the response is not received
+    /// within the timeout the request is forgotten and [`ForwardResult::AckTimeout`] is
+    /// returned.
+    ///
+    /// # Arguments
+    /// * `id` - an id of the request
+    /// * `receiver` - a receiver for the response to the request
+    /// * `timeout` - the maximum time to wait for the response
+    pub async fn wait(
+        &self,
+        id: u64,
+        receiver: ResponseReceiver,
+        timeout: Duration,
+    ) -> anyhow::Result<ForwardResult> {
+        tokio::select! {
+            result = receiver.receive() => result,
+            _ = sleep(timeout) => {
+                if let Some(requests) = self.requests.lock().unwrap().as_mut() {
+                    requests.remove(&id);
+                }
+                log::warn!("Timeout while waiting for response to request {}", id);
+                Ok(ForwardResult::AckTimeout)
+            }
+        }
+    }
+
+    /// Fails all pending requests and rejects new ones.
+    pub fn close(&self) {
+        self.requests.lock().unwrap().take();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.requests.lock().unwrap().is_none()
+    }
+}
+
+impl Default for PendingRequests {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A receiver for the response to a request registered in [`PendingRequests`].
+pub struct ResponseReceiver(oneshot::Receiver<MediaStatus>);
+
+impl ResponseReceiver {
+    /// Waits for the response. Fails if the connection is closed before the response is received.
+    pub async fn receive(self) -> anyhow::Result<ForwardResult> {
+        match self.0.await {
+            Ok(status) => Ok(ForwardResult::from(status)),
+            Err(_) => Err(anyhow!("Connection is closed")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use media_gateway_common::model::{MediaResponse, MediaStatus};
+
+    use crate::client::ForwardResult;
+    use crate::pending::PendingRequests;
+
+    #[tokio::test]
+    async fn complete() {
+        let pending = PendingRequests::new();
+        let (first_id, first_receiver) = pending.register().unwrap();
+        let (second_id, second_receiver) = pending.register().unwrap();
+
+        pending.complete(&MediaResponse {
+            id: second_id,
+            status: MediaStatus::SendTimeout as i32,
+        });
+        pending.complete(&MediaResponse {
+            id: first_id,
+            status: MediaStatus::Success as i32,
+        });
+
+        assert!(matches!(
+            first_receiver.receive().await,
+            Ok(ForwardResult::Success)
+        ));
+        assert!(matches!(
+            second_receiver.receive().await,
+            Ok(ForwardResult::SendTimeout)
+        ));
+    }
+
+    #[tokio::test]
+    async fn wait_timeout() {
+        let pending = PendingRequests::new();
+        let (id, receiver) = pending.register().unwrap();
+
+        assert!(matches!(
+            pending.wait(id, receiver, Duration::from_millis(10)).await,
+            Ok(ForwardResult::AckTimeout)
+        ));
+        assert!(pending
+            .requests
+            .lock()
+            .unwrap()
+            .as_ref()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn close() {
+        let pending = PendingRequests::new();
+        let (_, receiver) = pending.register().unwrap();
+
+        pending.close();
+
+        assert!(pending.is_closed());
+        assert!(receiver.receive().await.is_err());
+        assert!(pending.register().is_err());
+    }
+}
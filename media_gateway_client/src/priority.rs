@@ -0,0 +1,139 @@
+//! Priority classes of messages.
+//!
+//! The module provides [`Priorities`].
+use std::collections::HashSet;
+
+use anyhow::bail;
+use savant_core::message::Message;
+
+use crate::configuration::{PriorityConfiguration, PriorityMode, RouteFilter};
+use crate::routing::matches;
+
+const DEFAULT_WEIGHT: u32 = 1;
+
+/// Selects a priority class for each message. Classes are checked in order and the first
+/// matching one is applied. Messages matching no classes belong to the default class with the
+/// lowest priority. Each class corresponds to a lane of
+/// [`MessageQueue`](crate::queue::MessageQueue) with the same index, the default class
+/// corresponds to the last lane.
+pub struct Priorities {
+    filters: Vec<RouteFilter>,
+    /// Weights of classes followed by the weight of the default class if priorities are
+    /// weighted
+    weights: Option<Vec<u32>>,
+}
+
+impl Priorities {
+    /// Constructs a new instance.
+    ///
+    /// # Arguments
+    /// * `filters` - filters of classes in order of priority
+    /// * `weights` - weights of classes followed by the weight of the default class. If not
+    ///   specified priorities are strict.
+    pub fn new(filters: Vec<RouteFilter>, weights: Option<Vec<u32>>) -> Self {
+        Self { filters, weights }
+    }
+
+    /// Returns the number of classes including the default one.
+    pub fn lanes(&self) -> usize {
+        self.filters.len() + 1
+    }
+
+    /// Returns an index of the class for the message.
+    pub fn select(&self, topic: &[u8], message: &Message) -> usize {
+        self.filters
+            .iter()
+            .position(|filter| matches(filter, topic, Some(message)))
+            .unwrap_or(self.filters.len())
+    }
+
+    /// Returns `true` if classes are drained in turn according to their weights.
+    pub fn is_weighted(&self) -> bool {
+        self.weights.is_some()
+    }
+
+    /// Returns the weight of the class.
+    pub fn weight(&self, lane: usize) -> u32 {
+        self.weights.as_ref().map_or(DEFAULT_WEIGHT, |e| e[lane])
+    }
+}
+
+impl TryFrom<&PriorityConfiguration> for Priorities {
+    type Error = anyhow::Error;
+
+    fn try_from(configuration: &PriorityConfiguration) -> Result<Self, Self::Error> {
+        if configuration.classes.is_empty() {
+            bail!("Invalid priorities: no classes");
+        }
+        let mut names = HashSet::new();
+        for class in configuration.classes.iter() {
+            if !names.insert(class.name.as_str()) {
+                bail!("Invalid priorities: duplicate class name {}", class.name);
+            }
+        }
+        let weights = configuration
+            .classes
+            .iter()
+            .map(|e| e.weight.unwrap_or(DEFAULT_WEIGHT))
+            .chain([configuration.default_weight.unwrap_or(DEFAULT_WEIGHT)])
+            .collect::<Vec<u32>>();
+        if weights.contains(&0) {
+            bail!("Invalid priorities: weight 0");
+        }
+        let weights = match configuration.mode.unwrap_or_default() {
+            PriorityMode::Strict => None,
+            PriorityMode::Weighted => Some(weights),
+        };
+        let filters = configuration
+            .classes
+            .iter()
+            .map(|e| e.filter.clone())
+            .collect();
+        Ok(Priorities::new(filters, weights))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use savant_core::message::Message;
+
+    use crate::configuration::{
+        MessageType, PriorityClassConfiguration, PriorityConfiguration, PriorityMode, RouteFilter,
+    };
+    use crate::priority::Priorities;
+
+    #[test]
+    fn select() {
+        let priorities = Priorities::try_from(&new_configuration(Some(1))).unwrap();
+        let message = Message::unknown("message".to_string());
+
+        assert_eq!(priorities.lanes(), 2);
+        assert_eq!(priorities.select("control".as_bytes(), &message), 0);
+        assert_eq!(priorities.select("other".as_bytes(), &message), 1);
+        assert!(priorities.is_weighted());
+        assert_eq!(priorities.weight(0), 1);
+        assert_eq!(priorities.weight(1), 3);
+    }
+
+    #[test]
+    fn zero_weight() {
+        assert!(Priorities::try_from(&new_configuration(Some(0))).is_err());
+    }
+
+    fn new_configuration(weight: Option<u32>) -> PriorityConfiguration {
+        PriorityConfiguration {
+            classes: vec![PriorityClassConfiguration {
+                name: "control".to_string(),
+                filter: RouteFilter {
+                    topic: Some("control".to_string()),
+                    source_id: None,
+                    routing_labels: None,
+                    message_type: Some(MessageType::Unknown),
+                },
+                weight,
+            }],
+            mode: Some(PriorityMode::Weighted),
+            default_weight: Some(3),
+        }
+    }
+}
@@ -0,0 +1,795 @@
+//! A bounded queue of messages between readers and the sender.
+//!
+//! The module provides [`MessageQueue`].
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use anyhow::bail;
+use savant_core::message::Message;
+use tokio::sync::Notify;
+
+use media_gateway_common::model::Media;
+use media_gateway_common::statistics::StatisticsService;
+
+use crate::configuration::QueueOverflowPolicy;
+use crate::fair_queuing::FairQueuing;
+use crate::forwarder::MessageContext;
+use crate::priority::Priorities;
+use crate::routing::source_key;
+
+/// A statistics stage of messages dropped by the queue
+pub const QUEUE_STAGE_NAME: &str = "client-queue-dropped";
+
+/// A kind of the message used to decide whether it can be dropped.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MessageClass {
+    /// A message that is never dropped (end of stream or shutdown)
+    Control,
+    /// A video frame. A frame without keyframe flag is considered a keyframe.
+    Frame { source_id: String, keyframe: bool },
+    /// Any other message
+    Other,
+}
+
+impl From<&Message> for MessageClass {
+    fn from(message: &Message) -> Self {
+        if message.is_end_of_stream() || message.is_shutdown() {
+            MessageClass::Control
+        } else if let Some(frame) = message.as_video_frame() {
+            MessageClass::Frame {
+                source_id: frame.get_source_id(),
+                keyframe: frame.get_keyframe() != Some(false),
+            }
+        } else {
+            MessageClass::Other
+        }
+    }
+}
+
+/// Properties of a message required to queue it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageInfo {
+    /// A kind of the message
+    pub class: MessageClass,
+    /// A source id of the message or its topic if the message has no source id
+    pub source: String,
+    /// A lane of the priority class of the message
+    pub lane: usize,
+}
+
+struct QueueItem {
+    context: MessageContext,
+    media: Media,
+    class: MessageClass,
+    source: String,
+    /// A sequence number of the item in the queue
+    sequence: u64,
+    /// A size of the item in bytes
+    size: usize,
+}
+
+/// A queue of a priority class consisting of queues of sources.
+#[derive(Default)]
+struct Lane {
+    sources: HashMap<String, SourceQueue>,
+    /// Sources with messages in order of round-robin
+    active: VecDeque<String>,
+    /// The number of messages in the lane
+    len: usize,
+}
+
+#[derive(Default)]
+struct SourceQueue {
+    items: VecDeque<QueueItem>,
+    /// The number of bytes the source can send before its turn is over
+    deficit: usize,
+}
+
+impl Lane {
+    fn push(&mut self, item: QueueItem, fair_queuing: Option<&FairQueuing>) {
+        let queue = self.sources.entry(item.source.clone()).or_default();
+        if queue.items.is_empty() {
+            queue.deficit = fair_queuing.map_or(0, |e| e.quantum(&item.source));
+            self.active.push_back(item.source.clone());
+        }
+        queue.items.push_back(item);
+        self.len += 1;
+    }
+
+    /// Removes the next item according to deficit round-robin or the oldest item if fair
+    /// queuing is disabled.
+    fn pop(&mut self, fair_queuing: Option<&FairQueuing>) -> Option<QueueItem> {
+        let source = match fair_queuing {
+            None => self.find_oldest(|_| true)?.0,
+            Some(fair_queuing) => loop {
+                let source = self.active.front()?;
+                let queue = self.sources.get_mut(source).unwrap();
+                let size = queue.items.front().unwrap().size;
+                if queue.deficit >= size {
+                    queue.deficit -= size;
+                    break source.clone();
+                }
+                // the turn of the source is over, the quantum is added for the next turn
+                queue.deficit += fair_queuing.quantum(source);
+                self.active.rotate_left(1);
+            },
+        };
+        self.remove(&source, 0)
+    }
+
+    /// Removes the item of the source at the position.
+    fn remove(&mut self, source: &str, position: usize) -> Option<QueueItem> {
+        let queue = self.sources.get_mut(source)?;
+        let item = queue.items.remove(position)?;
+        self.len -= 1;
+        if queue.items.is_empty() {
+            self.sources.remove(source);
+            self.active.retain(|e| e != source);
+        }
+        Some(item)
+    }
+
+    /// Returns the source and the position of the oldest item matching the predicate.
+    fn find_oldest(&self, predicate: impl Fn(&QueueItem) -> bool) -> Option<(String, usize)> {
+        self.sources
+            .iter()
+            .filter_map(|(source, queue)| {
+                queue
+                    .items
+                    .iter()
+                    .position(&predicate)
+                    .map(|position| (source, position, queue.items[position].sequence))
+            })
+            .min_by_key(|(_, _, sequence)| *sequence)
+            .map(|(source, position, _)| (source.clone(), position))
+    }
+}
+
+struct QueueState {
+    /// Queues of priority classes
+    lanes: Vec<Lane>,
+    /// A lane to pop messages from with weighted priorities
+    current_lane: usize,
+    /// The number of messages that can be popped from the current lane before switching to the
+    /// next one with weighted priorities
+    credits: u32,
+    /// Sources which frames are dropped until the next keyframe
+    skipped_sources: HashSet<String>,
+    next_sequence: u64,
+    closed: bool,
+}
+
+/// A queue of messages with a separate lane for each priority class. Each lane has the limited
+/// capacity. If the lane is full new messages are handled according to [`QueueOverflowPolicy`].
+/// Control messages are never dropped. If no messages can be dropped to free space for a control
+/// message it is queued over the capacity unless the policy is [`QueueOverflowPolicy::Block`].
+///
+/// Messages of each source are queued separately within a lane. If fair queuing is enabled
+/// sources are served by deficit round-robin, otherwise messages are taken in order of arrival.
+pub struct MessageQueue {
+    policy: QueueOverflowPolicy,
+    capacity: usize,
+    priorities: Option<Priorities>,
+    fair_queuing: Option<FairQueuing>,
+    state: Mutex<QueueState>,
+    not_empty: Notify,
+    not_full: Notify,
+    statistics_service: Arc<Option<StatisticsService>>,
+}
+
+impl MessageQueue {
+    /// Constructs a new instance.
+    ///
+    /// # Arguments
+    /// * `policy` - a policy to handle new messages if the queue is full
+    /// * `capacity` - the maximum number of messages in each lane
+    /// * `priorities` - priority classes of messages. If not specified all messages are queued
+    ///   in the single lane.
+    /// * `fair_queuing` - weights of sources. If not specified messages of all sources are
+    ///   taken in order of arrival.
+    /// * `statistics_service` - a statistics service to register dropped messages
+    pub fn new(
+        policy: QueueOverflowPolicy,
+        capacity: usize,
+        priorities: Option<Priorities>,
+        fair_queuing: Option<FairQueuing>,
+        statistics_service: Arc<Option<StatisticsService>>,
+    ) -> Self {
+        let lanes = priorities.as_ref().map_or(1, |e| e.lanes());
+        let credits = priorities.as_ref().map_or(0, |e| e.weight(0));
+        Self {
+            policy,
+            capacity,
+            priorities,
+            fair_queuing,
+            state: Mutex::new(QueueState {
+                lanes: (0..lanes).map(|_| Lane::default()).collect(),
+                current_lane: 0,
+                credits,
+                skipped_sources: HashSet::new(),
+                next_sequence: 0,
+                closed: false,
+            }),
+            not_empty: Notify::new(),
+            not_full: Notify::new(),
+            statistics_service,
+        }
+    }
+
+    /// Returns properties of the message required to queue it.
+    pub fn info(&self, topic: &[u8], message: &Message) -> MessageInfo {
+        MessageInfo {
+            class: MessageClass::from(message),
+            source: source_key(topic, Some(message)),
+            lane: self
+                .priorities
+                .as_ref()
+                .map_or(0, |e| e.select(topic, message)),
+        }
+    }
+
+    /// Adds the message to the lane. Waits for free space only if the policy is
+    /// [`QueueOverflowPolicy::Block`]. Fails if the queue is closed.
+    pub async fn push(
+        &self,
+        context: MessageContext,
+        media: Media,
+        info: MessageInfo,
+    ) -> anyhow::Result<()> {
+        let lane = info.lane;
+        let size = media.proto_len();
+        let mut item = QueueItem {
+            context,
+            media,
+            class: info.class,
+            source: info.source,
+            sequence: 0,
+            size,
+        };
+        loop {
+            let not_full = self.not_full.notified();
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.closed {
+                    bail!("Queue is closed");
+                }
+                item.sequence = state.next_sequence;
+                item = match self.try_push(&mut state, item, lane) {
+                    None => {
+                        state.next_sequence += 1;
+                        self.not_empty.notify_one();
+                        return Ok(());
+                    }
+                    Some(item) => item,
+                };
+            }
+            not_full.await;
+        }
+    }
+
+    /// Removes the next message from the lane selected by priorities waiting for it if the
+    /// queue is empty. Returns [`None`] if the queue is closed and empty.
+    pub async fn pop(&self) -> Option<(MessageContext, Media)> {
+        loop {
+            {
+                let mut state = self.state.lock().unwrap();
+                if let Some(lane) = self.next_lane(&mut state) {
+                    let item = state.lanes[lane].pop(self.fair_queuing.as_ref()).unwrap();
+                    self.not_full.notify_waiters();
+                    return Some((item.context, item.media));
+                }
+                if state.closed {
+                    return None;
+                }
+            }
+            self.not_empty.notified().await;
+        }
+    }
+
+    /// Removes the oldest message of the class matching the predicate from the lane with the
+    /// highest priority waiting for it if there are no such messages. Messages of other classes
+    /// stay in the queue. If the queue is closed the next message is removed regardless of its
+    /// class so that the queue can be drained.
+    pub async fn pop_matching(
+        &self,
+        predicate: impl Fn(&MessageClass) -> bool,
+    ) -> Option<(MessageContext, Media)> {
+        loop {
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.closed {
+                    break;
+                }
+                let item = state.lanes.iter_mut().find_map(|lane| {
+                    let (source, position) = lane.find_oldest(|e| predicate(&e.class))?;
+                    lane.remove(&source, position)
+                });
+                if let Some(item) = item {
+                    self.not_full.notify_waiters();
+                    return Some((item.context, item.media));
+                }
+            }
+            self.not_empty.notified().await;
+        }
+        self.pop().await
+    }
+
+    /// Closes the queue. Messages in the queue are still available via [`MessageQueue::pop`].
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.not_empty.notify_one();
+        self.not_full.notify_waiters();
+    }
+
+    /// Returns `true` if there are no messages in the queue.
+    pub fn is_empty(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.lanes.iter().all(|e| e.len == 0)
+    }
+
+    /// Returns the number of queued messages by sources.
+    pub fn depths(&self) -> HashMap<String, usize> {
+        let state = self.state.lock().unwrap();
+        let mut depths = HashMap::new();
+        for lane in state.lanes.iter() {
+            for (source, queue) in lane.sources.iter() {
+                *depths.entry(source.clone()).or_default() += queue.items.len();
+            }
+        }
+        depths
+    }
+
+    /// Returns a non-empty lane to pop a message from. Lanes are drained in order of priority
+    /// with strict priorities or in turn by the number of messages equal to their weights with
+    /// weighted priorities.
+    fn next_lane(&self, state: &mut QueueState) -> Option<usize> {
+        match self.priorities.as_ref().filter(|e| e.is_weighted()) {
+            None => state.lanes.iter().position(|e| e.len > 0),
+            Some(priorities) => {
+                for _ in 0..=state.lanes.len() {
+                    if state.credits > 0 && state.lanes[state.current_lane].len > 0 {
+                        state.credits -= 1;
+                        return Some(state.current_lane);
+                    }
+                    state.current_lane = (state.current_lane + 1) % state.lanes.len();
+                    state.credits = priorities.weight(state.current_lane);
+                }
+                None
+            }
+        }
+    }
+
+    /// Tries to add the item to the lane dropping messages if required. Returns the item back
+    /// if it should wait for free space.
+    fn try_push(&self, state: &mut QueueState, item: QueueItem, lane: usize) -> Option<QueueItem> {
+        if self.policy == QueueOverflowPolicy::DropGop {
+            if let MessageClass::Frame {
+                source_id,
+                keyframe,
+            } = &item.class
+            {
+                if *keyframe {
+                    state.skipped_sources.remove(source_id);
+                } else if state.skipped_sources.contains(source_id) {
+                    self.drop_item(item);
+                    return None;
+                }
+            }
+        }
+        if state.lanes[lane].len < self.capacity {
+            state.lanes[lane].push(item, self.fair_queuing.as_ref());
+            return None;
+        }
+        match self.policy {
+            QueueOverflowPolicy::Block => return Some(item),
+            QueueOverflowPolicy::DropNewest => {
+                if item.class != MessageClass::Control {
+                    self.drop_item(item);
+                    return None;
+                }
+                self.drop_oldest(&mut state.lanes[lane]);
+            }
+            QueueOverflowPolicy::DropOldest => {
+                if !self.drop_oldest(&mut state.lanes[lane]) && item.class != MessageClass::Control
+                {
+                    self.drop_item(item);
+                    return None;
+                }
+            }
+            QueueOverflowPolicy::DropGop => match &item.class {
+                MessageClass::Frame {
+                    source_id,
+                    keyframe: false,
+                } => {
+                    state.skipped_sources.insert(source_id.clone());
+                    self.drop_item(item);
+                    return None;
+                }
+                MessageClass::Frame {
+                    source_id,
+                    keyframe: true,
+                } => {
+                    if !self.drop_oldest_gop(state, lane) {
+                        state.skipped_sources.insert(source_id.clone());
+                        self.drop_item(item);
+                        return None;
+                    }
+                }
+                MessageClass::Other => {
+                    if !self.drop_oldest_gop(state, lane) {
+                        self.drop_item(item);
+                        return None;
+                    }
+                }
+                MessageClass::Control => {
+                    self.drop_oldest_gop(state, lane);
+                }
+            },
+        }
+        state.lanes[lane].push(item, self.fair_queuing.as_ref());
+        None
+    }
+
+    /// Drops the oldest message in the lane that is not a control one. Returns `false` if there
+    /// are no such messages.
+    fn drop_oldest(&self, lane: &mut Lane) -> bool {
+        let item = lane
+            .find_oldest(|e| e.class != MessageClass::Control)
+            .and_then(|(source, position)| lane.remove(&source, position));
+        match item {
+            Some(item) => {
+                self.drop_item(item);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drops the oldest frame in the lane and following non-keyframes of the same source up to
+    /// its next keyframe. If there is no next keyframe in the lane the rest of the GOP is dropped
+    /// on arrival. Returns `false` if there are no frames in the lane.
+    fn drop_oldest_gop(&self, state: &mut QueueState, lane: usize) -> bool {
+        let lane = &mut state.lanes[lane];
+        let (source, position) =
+            match lane.find_oldest(|e| matches!(e.class, MessageClass::Frame { .. })) {
+                Some(found) => found,
+                None => return false,
+            };
+        let mut dropped = vec![lane.remove(&source, position).unwrap()];
+        let mut next_keyframe = false;
+        // messages of other sources are in separate queues
+        let mut index = position;
+        while let Some(item) = lane.sources.get(&source).and_then(|e| e.items.get(index)) {
+            let keyframe = match &item.class {
+                MessageClass::Frame { keyframe, .. } => Some(*keyframe),
+                _ => None,
+            };
+            match keyframe {
+                Some(true) => {
+                    next_keyframe = true;
+                    break;
+                }
+                Some(false) => dropped.push(lane.remove(&source, index).unwrap()),
+                None => index += 1,
+            }
+        }
+        if !next_keyframe {
+            state.skipped_sources.insert(source);
+        }
+        for item in dropped {
+            self.drop_item(item);
+        }
+        true
+    }
+
+    fn drop_item(&self, item: QueueItem) {
+        log::debug!(
+            "Message from {} is dropped by queue overflow policy",
+            String::from_utf8_lossy(&item.media.topic)
+        );
+        if let Some(service) = self.statistics_service.as_ref() {
+            if let Some(id) = item.context.statistics_id {
+                if let Err(e) = service.register_message_end(id) {
+                    log::warn!("Error while ending message statistics: {:?}", e);
+                }
+            }
+            if let Err(e) = service.register_stage_message(QUEUE_STAGE_NAME) {
+                log::warn!("Error while registering dropped message: {:?}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use media_gateway_common::model::Media;
+
+    use crate::configuration::{QueueOverflowPolicy, RouteFilter};
+    use crate::fair_queuing::FairQueuing;
+    use crate::forwarder::MessageContext;
+    use crate::priority::Priorities;
+    use crate::queue::{MessageClass, MessageInfo, MessageQueue};
+
+    #[tokio::test]
+    async fn drop_newest() {
+        let queue = MessageQueue::new(
+            QueueOverflowPolicy::DropNewest,
+            2,
+            None,
+            None,
+            Arc::new(None),
+        );
+
+        for (topic, class) in [
+            ("1", MessageClass::Other),
+            ("2", MessageClass::Other),
+            ("3", MessageClass::Other),
+            ("eos", MessageClass::Control),
+        ] {
+            push(&queue, topic, class).await;
+        }
+
+        assert_eq!(pop_all(&queue).await, vec!["2", "eos"]);
+    }
+
+    #[tokio::test]
+    async fn drop_oldest() {
+        let queue = MessageQueue::new(
+            QueueOverflowPolicy::DropOldest,
+            2,
+            None,
+            None,
+            Arc::new(None),
+        );
+
+        for (topic, class) in [
+            ("eos", MessageClass::Control),
+            ("1", MessageClass::Other),
+            ("2", MessageClass::Other),
+            ("3", MessageClass::Other),
+        ] {
+            push(&queue, topic, class).await;
+        }
+
+        assert_eq!(pop_all(&queue).await, vec!["eos", "3"]);
+    }
+
+    #[tokio::test]
+    async fn drop_gop() {
+        let queue = MessageQueue::new(QueueOverflowPolicy::DropGop, 4, None, None, Arc::new(None));
+
+        for (topic, class) in [
+            ("a-key-1", frame("a", true)),
+            ("a-1", frame("a", false)),
+            ("b-key-1", frame("b", true)),
+            ("a-2", frame("a", false)),
+            // the queue is full, the first GOP of "a" is dropped including following frames
+            ("b-key-2", frame("b", true)),
+            ("a-3", frame("a", false)),
+            ("eos", MessageClass::Control),
+            ("a-key-2", frame("a", true)),
+        ] {
+            push(&queue, topic, class).await;
+        }
+
+        assert_eq!(
+            pop_all(&queue).await,
+            vec!["b-key-1", "b-key-2", "eos", "a-key-2"]
+        );
+    }
+
+    #[tokio::test]
+    async fn drop_gop_non_keyframe() {
+        let queue = MessageQueue::new(QueueOverflowPolicy::DropGop, 1, None, None, Arc::new(None));
+
+        for (topic, class) in [
+            ("a-key-1", frame("a", true)),
+            // the queue is full, the rest of the GOP is dropped
+            ("a-1", frame("a", false)),
+        ] {
+            push(&queue, topic, class).await;
+        }
+        assert_eq!(pop(&queue).await, "a-key-1");
+        for (topic, class) in [("a-2", frame("a", false)), ("a-key-2", frame("a", true))] {
+            push(&queue, topic, class).await;
+        }
+
+        assert_eq!(pop_all(&queue).await, vec!["a-key-2"]);
+    }
+
+    #[tokio::test]
+    async fn block() {
+        let queue = Arc::new(MessageQueue::new(
+            QueueOverflowPolicy::Block,
+            1,
+            None,
+            None,
+            Arc::new(None),
+        ));
+        push(&queue, "1", MessageClass::Other).await;
+
+        let pushing_queue = queue.clone();
+        let pushing = tokio::spawn(async move {
+            push(&pushing_queue, "2", MessageClass::Control).await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!pushing.is_finished());
+
+        assert_eq!(pop(&queue).await, "1");
+        pushing.await.unwrap();
+        assert_eq!(pop_all(&queue).await, vec!["2"]);
+    }
+
+    #[tokio::test]
+    async fn strict_priorities() {
+        let queue = MessageQueue::new(
+            QueueOverflowPolicy::Block,
+            10,
+            Some(Priorities::new(vec![new_filter(), new_filter()], None)),
+            None,
+            Arc::new(None),
+        );
+
+        for (topic, lane) in [("2-1", 2), ("1-1", 1), ("0-1", 0), ("2-2", 2), ("0-2", 0)] {
+            push_to_lane(&queue, topic, MessageClass::Other, lane).await;
+        }
+
+        assert_eq!(
+            pop_all(&queue).await,
+            vec!["0-1", "0-2", "1-1", "2-1", "2-2"]
+        );
+    }
+
+    #[tokio::test]
+    async fn weighted_priorities() {
+        let queue = MessageQueue::new(
+            QueueOverflowPolicy::Block,
+            10,
+            Some(Priorities::new(vec![new_filter()], Some(vec![2, 1]))),
+            None,
+            Arc::new(None),
+        );
+
+        for i in 1..=4 {
+            push_to_lane(&queue, &format!("0-{}", i), MessageClass::Other, 0).await;
+            push_to_lane(&queue, &format!("1-{}", i), MessageClass::Other, 1).await;
+        }
+
+        assert_eq!(
+            pop_all(&queue).await,
+            vec!["0-1", "0-2", "1-1", "0-3", "0-4", "1-2", "1-3", "1-4"]
+        );
+    }
+
+    #[tokio::test]
+    async fn fair_queuing() {
+        let queue = MessageQueue::new(
+            QueueOverflowPolicy::Block,
+            10,
+            None,
+            Some(FairQueuing::new(
+                100,
+                1,
+                HashMap::new(),
+                HashMap::from([("big".to_string(), 2)]),
+            )),
+            Arc::new(None),
+        );
+
+        for i in 1..=2 {
+            push_to_source(&queue, &format!("big-{}", i), "big", 300).await;
+        }
+        for i in 1..=3 {
+            push_to_source(&queue, &format!("small-{}", i), "small", 60).await;
+        }
+        assert_eq!(
+            queue.depths(),
+            HashMap::from([("big".to_string(), 2), ("small".to_string(), 3)])
+        );
+
+        // the big source accumulates its quantum while the small one sends messages
+        assert_eq!(
+            pop_all(&queue).await,
+            vec!["small-1", "big-1", "small-2", "small-3", "big-2"]
+        );
+        assert!(queue.depths().is_empty());
+    }
+
+    #[tokio::test]
+    async fn pop_matching() {
+        let queue = MessageQueue::new(QueueOverflowPolicy::Block, 10, None, None, Arc::new(None));
+
+        push(&queue, "frame", frame("source", true)).await;
+        push(&queue, "eos", MessageClass::Control).await;
+
+        let (_, media) = queue
+            .pop_matching(|e| *e == MessageClass::Control)
+            .await
+            .unwrap();
+        assert_eq!(media.topic, "eos".as_bytes());
+        assert_eq!(queue.depths(), HashMap::from([("source".to_string(), 1)]));
+
+        // a closed queue is drained regardless of classes
+        queue.close();
+        let (_, media) = queue
+            .pop_matching(|e| *e == MessageClass::Control)
+            .await
+            .unwrap();
+        assert_eq!(media.topic, "frame".as_bytes());
+        assert!(queue.pop_matching(|_| true).await.is_none());
+    }
+
+    fn new_filter() -> RouteFilter {
+        RouteFilter {
+            topic: None,
+            source_id: None,
+            routing_labels: None,
+            message_type: None,
+        }
+    }
+
+    fn frame(source_id: &str, keyframe: bool) -> MessageClass {
+        MessageClass::Frame {
+            source_id: source_id.to_string(),
+            keyframe,
+        }
+    }
+
+    async fn push(queue: &MessageQueue, topic: &str, class: MessageClass) {
+        push_to_lane(queue, topic, class, 0).await;
+    }
+
+    async fn push_to_lane(queue: &MessageQueue, topic: &str, class: MessageClass, lane: usize) {
+        let source = match &class {
+            MessageClass::Frame { source_id, .. } => source_id.clone(),
+            _ => "source".to_string(),
+        };
+        let info = MessageInfo {
+            class,
+            source,
+            lane,
+        };
+        queue
+            .push(MessageContext::default(), new_media(topic, 0), info)
+            .await
+            .unwrap();
+    }
+
+    async fn push_to_source(queue: &MessageQueue, topic: &str, source: &str, size: usize) {
+        let info = MessageInfo {
+            class: MessageClass::Other,
+            source: source.to_string(),
+            lane: 0,
+        };
+        queue
+            .push(MessageContext::default(), new_media(topic, size), info)
+            .await
+            .unwrap();
+    }
+
+    fn new_media(topic: &str, size: usize) -> Media {
+        Media {
+            message: None,
+            topic: topic.as_bytes().to_vec(),
+            data: vec![vec![0; size]],
+        }
+    }
+
+    async fn pop(queue: &MessageQueue) -> String {
+        let (_, media) = queue.pop().await.unwrap();
+        String::from_utf8(media.topic).unwrap()
+    }
+
+    async fn pop_all(queue: &MessageQueue) -> Vec<String> {
+        queue.close();
+        let mut topics = Vec::new();
+        while let Some((_, media)) = queue.pop().await {
+            topics.push(String::from_utf8(media.topic).unwrap());
+        }
+        topics
+    }
+}
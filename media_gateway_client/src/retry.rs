@@ -1,7 +1,12 @@
+use std::collections::HashMap;
 use std::time::Duration;
 
+use rand::Rng;
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 
+use crate::client::ForwardResult;
+
 const INITIAL_RETRY_NUMBER: u32 = 1;
 
 #[derive(Debug)]
@@ -26,16 +31,51 @@ impl Retry {
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum RetryStrategy {
+    /// The delay is multiplied by the multiplier for each attempt up to the maximum delay
     #[serde(rename = "exponential")]
     Exponential {
         initial_delay: Duration,
         maximum_delay: Duration,
         multiplier: u32,
     },
+    /// The same delay for all attempts
+    #[serde(rename = "fixed")]
+    Fixed { delay: Duration },
+    /// The delay is increased by the increment for each attempt up to the maximum delay
+    #[serde(rename = "linear")]
+    Linear {
+        initial_delay: Duration,
+        increment: Duration,
+        maximum_delay: Duration,
+    },
+    /// The delay is random between the base delay and the last delay multiplied by 3 but not
+    /// greater than the maximum delay ("decorrelated jitter")
+    #[serde(rename = "decorrelated_jitter")]
+    DecorrelatedJitter {
+        base_delay: Duration,
+        maximum_delay: Duration,
+    },
+}
+
+impl Default for RetryStrategy {
+    fn default() -> Self {
+        RetryStrategy::Exponential {
+            initial_delay: Duration::from_millis(1),
+            maximum_delay: Duration::from_secs(1),
+            multiplier: 2,
+        }
+    }
 }
 
 impl RetryStrategy {
     pub fn next_retry(&self, previous_retry: Option<Retry>) -> Retry {
+        let number = match &previous_retry {
+            None => INITIAL_RETRY_NUMBER,
+            Some(previous) => previous.number.checked_add(1).unwrap_or_else(|| {
+                log::warn!("Retry number overflow, resetting");
+                0
+            }),
+        };
         match self {
             RetryStrategy::Exponential {
                 initial_delay,
@@ -44,12 +84,6 @@ impl RetryStrategy {
             } => match previous_retry {
                 None => Retry::new(INITIAL_RETRY_NUMBER, *initial_delay),
                 Some(previous) => {
-                    let number = if let Some(n) = previous.number.checked_add(1) {
-                        n
-                    } else {
-                        log::warn!("Exponential retry number overflow, resetting");
-                        0
-                    };
                     let duration = match previous.delay.checked_mul(*multiplier) {
                         Some(d) if d <= *maximum_delay => d,
                         _ => *maximum_delay,
@@ -57,16 +91,114 @@ impl RetryStrategy {
                     Retry::new(number, duration)
                 }
             },
+            RetryStrategy::Fixed { delay } => Retry::new(number, *delay),
+            RetryStrategy::Linear {
+                initial_delay,
+                increment,
+                maximum_delay,
+            } => {
+                let delay = match previous_retry {
+                    None => *initial_delay,
+                    Some(previous) => previous
+                        .delay
+                        .saturating_add(*increment)
+                        .min(*maximum_delay),
+                };
+                Retry::new(number, delay)
+            }
+            RetryStrategy::DecorrelatedJitter {
+                base_delay,
+                maximum_delay,
+            } => {
+                let delay = match previous_retry {
+                    None => *base_delay,
+                    Some(previous) => {
+                        let upper = previous.delay.saturating_mul(3).max(*base_delay);
+                        rand::thread_rng()
+                            .gen_range(*base_delay..=upper)
+                            .min(*maximum_delay)
+                    }
+                };
+                Retry::new(number, delay)
+            }
         }
     }
 }
 
+/// What to do with a message after an unsuccessful attempt to send it.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum RetryAction {
+    /// The message is sent again according to [`RetryStrategy`]
+    #[serde(rename = "retry")]
+    Retry,
+    /// The message is dropped
+    #[serde(rename = "drop")]
+    Drop,
+    /// The message is written to the dead letter sink
+    #[serde(rename = "dead_letter")]
+    DeadLetter,
+}
+
+/// Actions for outcomes of unsuccessful attempts to send a message. By default timeouts, transport
+/// errors and HTTP statuses 408, 429 and 5xx are retried, other HTTP statuses are dead-lettered.
+/// For streaming transports HTTP statuses corresponding to media statuses are used. Retries of
+/// overloaded responses (429 and 503) are delayed at least by `Retry-After` header.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct RetryPolicy {
+    /// An action for [`ForwardResult::SendTimeout`]
+    pub send_timeout: Option<RetryAction>,
+    /// An action for [`ForwardResult::AckTimeout`]
+    pub ack_timeout: Option<RetryAction>,
+    /// An action for transport errors (e.g. the server is unreachable)
+    pub transport_error: Option<RetryAction>,
+    /// Actions for HTTP statuses
+    pub http_statuses: Option<HashMap<u16, RetryAction>>,
+}
+
+impl RetryPolicy {
+    /// Returns the action for the result of an unsuccessful attempt.
+    pub fn action(&self, result: &anyhow::Result<ForwardResult>) -> RetryAction {
+        match result {
+            Ok(ForwardResult::Success) => RetryAction::Retry,
+            Ok(ForwardResult::SendTimeout) => self.send_timeout.unwrap_or(RetryAction::Retry),
+            Ok(ForwardResult::AckTimeout) => self.ack_timeout.unwrap_or(RetryAction::Retry),
+            Ok(ForwardResult::HttpStatus(status)) => self
+                .http_statuses
+                .as_ref()
+                .and_then(|e| e.get(&status.as_u16()).copied())
+                .unwrap_or_else(|| default_http_status_action(*status)),
+            Ok(ForwardResult::Overloaded { status, .. }) => self
+                .http_statuses
+                .as_ref()
+                .and_then(|e| e.get(&status.as_u16()).copied())
+                .unwrap_or(RetryAction::Retry),
+            Err(_) => self.transport_error.unwrap_or(RetryAction::Retry),
+        }
+    }
+}
+
+fn default_http_status_action(status: StatusCode) -> RetryAction {
+    if status.is_client_error()
+        && status != StatusCode::REQUEST_TIMEOUT
+        && status != StatusCode::TOO_MANY_REQUESTS
+    {
+        RetryAction::DeadLetter
+    } else {
+        RetryAction::Retry
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
     use std::ops::Mul;
     use std::time::Duration;
 
-    use crate::retry::{Retry, RetryStrategy, INITIAL_RETRY_NUMBER};
+    use anyhow::anyhow;
+    use reqwest::StatusCode;
+
+    use crate::client::ForwardResult;
+    use crate::retry::{Retry, RetryAction, RetryPolicy, RetryStrategy, INITIAL_RETRY_NUMBER};
 
     #[test]
     fn exponential_next_retry_no_previous() {
@@ -123,4 +255,124 @@ mod tests {
         assert_eq!(result.number, number + 1);
         assert_eq!(result.delay, maximum_delay);
     }
+
+    #[test]
+    fn fixed_next_retry() {
+        let delay = Duration::from_millis(5);
+        let retry_strategy = RetryStrategy::Fixed { delay };
+
+        let first = retry_strategy.next_retry(None);
+        let second = retry_strategy.next_retry(Some(Retry::new(first.number, first.delay)));
+
+        assert_eq!(first.delay, delay);
+        assert_eq!(second.number, INITIAL_RETRY_NUMBER + 1);
+        assert_eq!(second.delay, delay);
+    }
+
+    #[test]
+    fn linear_next_retry() {
+        let retry_strategy = RetryStrategy::Linear {
+            initial_delay: Duration::from_millis(2),
+            increment: Duration::from_millis(3),
+            maximum_delay: Duration::from_millis(6),
+        };
+
+        let first = retry_strategy.next_retry(None);
+        let second = retry_strategy.next_retry(Some(Retry::new(first.number, first.delay)));
+        let third = retry_strategy.next_retry(Some(Retry::new(second.number, second.delay)));
+
+        assert_eq!(first.delay, Duration::from_millis(2));
+        assert_eq!(second.delay, Duration::from_millis(5));
+        assert_eq!(third.delay, Duration::from_millis(6));
+    }
+
+    #[test]
+    fn decorrelated_jitter_next_retry() {
+        let base_delay = Duration::from_millis(2);
+        let maximum_delay = Duration::from_millis(50);
+        let retry_strategy = RetryStrategy::DecorrelatedJitter {
+            base_delay,
+            maximum_delay,
+        };
+
+        let mut retry = retry_strategy.next_retry(None);
+        assert_eq!(retry.delay, base_delay);
+        for _ in 0..100 {
+            let previous_delay = retry.delay;
+            retry = retry_strategy.next_retry(Some(retry));
+            assert!(retry.delay >= base_delay);
+            assert!(retry.delay <= maximum_delay);
+            assert!(retry.delay <= previous_delay.mul(3));
+        }
+    }
+
+    #[test]
+    fn retry_policy_default_actions() {
+        let retry_policy = RetryPolicy::default();
+
+        assert_eq!(
+            retry_policy.action(&Ok(ForwardResult::SendTimeout)),
+            RetryAction::Retry
+        );
+        assert_eq!(
+            retry_policy.action(&Err(anyhow!("error"))),
+            RetryAction::Retry
+        );
+        assert_eq!(
+            retry_policy.action(&Ok(ForwardResult::HttpStatus(
+                StatusCode::SERVICE_UNAVAILABLE
+            ))),
+            RetryAction::Retry
+        );
+        assert_eq!(
+            retry_policy.action(&Ok(ForwardResult::HttpStatus(
+                StatusCode::TOO_MANY_REQUESTS
+            ))),
+            RetryAction::Retry
+        );
+        assert_eq!(
+            retry_policy.action(&Ok(ForwardResult::HttpStatus(StatusCode::UNAUTHORIZED))),
+            RetryAction::DeadLetter
+        );
+        assert_eq!(
+            retry_policy.action(&Ok(ForwardResult::Overloaded {
+                status: StatusCode::TOO_MANY_REQUESTS,
+                retry_after: None,
+            })),
+            RetryAction::Retry
+        );
+    }
+
+    #[test]
+    fn retry_policy_configured_actions() {
+        let retry_policy = RetryPolicy {
+            send_timeout: Some(RetryAction::Drop),
+            ack_timeout: Some(RetryAction::DeadLetter),
+            transport_error: None,
+            http_statuses: Some(HashMap::from([
+                (401, RetryAction::Retry),
+                (503, RetryAction::Drop),
+            ])),
+        };
+
+        assert_eq!(
+            retry_policy.action(&Ok(ForwardResult::SendTimeout)),
+            RetryAction::Drop
+        );
+        assert_eq!(
+            retry_policy.action(&Ok(ForwardResult::AckTimeout)),
+            RetryAction::DeadLetter
+        );
+        assert_eq!(
+            retry_policy.action(&Ok(ForwardResult::HttpStatus(StatusCode::UNAUTHORIZED))),
+            RetryAction::Retry
+        );
+        assert_eq!(
+            retry_policy.action(&Ok(ForwardResult::Overloaded {
+                status: StatusCode::SERVICE_UNAVAILABLE,
+                retry_after: Some(Duration::from_secs(1)),
+            })),
+            RetryAction::Drop
+        );
+    }
 }
@@ -0,0 +1,342 @@
+//! Routing of messages to different media gateway servers.
+//!
+//! The module provides [`Router`] and [`route`] function.
+use std::cell::OnceCell;
+use std::sync::Arc;
+
+use savant_core::message::Message;
+use tokio::sync::mpsc::Receiver;
+
+use media_gateway_common::model::Media;
+
+use crate::configuration::{MessageType, RouteFilter};
+use crate::forwarder::{DispatchOptions, Dispatcher, Forwarder, MessageContext};
+
+/// An index of the default destination.
+pub const DEFAULT_DESTINATION: usize = 0;
+
+/// Selects a destination for each message. Filters are checked in order and the first matching
+/// one is applied.
+pub struct Router {
+    filters: Vec<RouteFilter>,
+}
+
+impl Router {
+    /// Constructs a new instance.
+    ///
+    /// # Arguments
+    /// * `filters` - filters of routes in order of priority
+    pub fn new(filters: Vec<RouteFilter>) -> Self {
+        Self { filters }
+    }
+
+    /// Returns an index of the destination for the message: `i + 1` for the route with the
+    /// filter `i` or [`DEFAULT_DESTINATION`] if the message matches no filters. The message is
+    /// decoded only if a filter with a matching topic has conditions on the message.
+    pub fn select(&self, media: &Media) -> usize {
+        let message = OnceCell::new();
+        self.filters
+            .iter()
+            .position(|filter| {
+                if !matches_topic(filter, &media.topic) {
+                    return false;
+                }
+                if !requires_message(filter) {
+                    return true;
+                }
+                let message = message.get_or_init(|| {
+                    media
+                        .message
+                        .as_ref()
+                        .and_then(|e| Message::try_from(e).ok())
+                });
+                matches(filter, &media.topic, message.as_ref())
+            })
+            .map_or(DEFAULT_DESTINATION, |e| e + 1)
+    }
+}
+
+/// Returns `true` if the message with the topic meets all conditions of the filter.
+pub fn matches(filter: &RouteFilter, topic: &[u8], message: Option<&Message>) -> bool {
+    if !matches_topic(filter, topic) {
+        return false;
+    }
+    if !requires_message(filter) {
+        return true;
+    }
+    let message = match message {
+        Some(message) => message,
+        None => return false,
+    };
+    if let Some(filter_source_id) = &filter.source_id {
+        if source_id(message).as_ref() != Some(filter_source_id) {
+            return false;
+        }
+    }
+    if let Some(routing_labels) = &filter.routing_labels {
+        if !routing_labels.matches(&message.meta().routing_labels) {
+            return false;
+        }
+    }
+    if let Some(expected_type) = filter.message_type {
+        if expected_type != message_type(message) {
+            return false;
+        }
+    }
+    true
+}
+
+fn matches_topic(filter: &RouteFilter, topic: &[u8]) -> bool {
+    filter
+        .topic
+        .as_ref()
+        .map_or(true, |e| e.as_bytes() == topic)
+}
+
+/// Returns `true` if the filter has conditions on the message besides its topic.
+fn requires_message(filter: &RouteFilter) -> bool {
+    filter.source_id.is_some() || filter.routing_labels.is_some() || filter.message_type.is_some()
+}
+
+/// Returns a source id of a video frame, user data or end of stream message.
+pub fn source_id(message: &Message) -> Option<String> {
+    if let Some(frame) = message.as_video_frame() {
+        Some(frame.get_source_id())
+    } else if let Some(user_data) = message.as_user_data() {
+        Some(user_data.get_source_id().to_string())
+    } else {
+        message
+            .as_end_of_stream()
+            .map(|e| e.get_source_id().to_string())
+    }
+}
+
+/// Returns a source id of the message or its topic if the message has no source id.
+pub fn source_key(topic: &[u8], message: Option<&Message>) -> String {
+    message
+        .and_then(source_id)
+        .unwrap_or_else(|| String::from_utf8_lossy(topic).to_string())
+}
+
+fn message_type(message: &Message) -> MessageType {
+    if message.is_video_frame() {
+        MessageType::VideoFrame
+    } else if message.is_video_frame_batch() {
+        MessageType::VideoFrameBatch
+    } else if message.is_video_frame_update() {
+        MessageType::VideoFrameUpdate
+    } else if message.is_user_data() {
+        MessageType::UserData
+    } else if message.is_end_of_stream() {
+        MessageType::EndOfStream
+    } else if message.is_shutdown() {
+        MessageType::Shutdown
+    } else {
+        MessageType::Unknown
+    }
+}
+
+/// Distributes messages between destinations. Messages of each destination (and each source if
+/// `per_source` is set) are queued in a separate lane forwarded by a separate task with the
+/// forwarder of the destination, so messages of different destinations are forwarded and retried
+/// independently. See [`Dispatcher`].
+///
+/// # Arguments
+/// * `router` - a router to select a destination
+/// * `forwarders` - forwarders for destinations in order of their indexes
+/// * `receiver` - a queue of messages
+/// * `options` - settings of lanes
+pub async fn route(
+    router: &Router,
+    forwarders: Vec<Arc<Forwarder>>,
+    receiver: &mut Receiver<(MessageContext, Media)>,
+    options: DispatchOptions,
+) {
+    Dispatcher::new(forwarders, options)
+        .run(receiver, |media| router.select(media))
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use reqwest::StatusCode;
+    use savant_core::message::label_filter::LabelFilterRule;
+    use savant_core::message::Message;
+    use savant_core::primitives::frame::{
+        VideoFrameContent, VideoFrameProxy, VideoFrameTranscodingMethod,
+    };
+
+    use tokio::sync::mpsc;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use media_gateway_common::model::Media;
+
+    use crate::configuration::{MessageType, QueueOverflowPolicy, RouteFilter};
+    use crate::forwarder::{DispatchOptions, Forwarder, ForwarderOptions, MessageContext};
+    use crate::routing::{route, Router, DEFAULT_DESTINATION};
+
+    #[tokio::test]
+    async fn route_independently() {
+        let default_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(StatusCode::OK))
+            .mount(&default_server)
+            .await;
+        // the route server responds slowly
+        let route_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(StatusCode::OK).set_delay(Duration::from_secs(1)))
+            .mount(&route_server)
+            .await;
+        let router = Router::new(vec![new_filter(Some("route"), None, None, None)]);
+        let forwarders = [default_server.uri(), route_server.uri()]
+            .into_iter()
+            .map(|url| Arc::new(Forwarder::with_url(url, ForwarderOptions::default())))
+            .collect();
+        let (sender, mut receiver) = mpsc::channel(20);
+        // the queue of the route overflows
+        for topic in ["route"; 10].into_iter().chain(["default", "default"]) {
+            sender
+                .send((MessageContext::default(), new_media(topic, None)))
+                .await
+                .unwrap();
+        }
+        let routing = tokio::spawn(async move {
+            let options = DispatchOptions {
+                lane_size: 2,
+                overflow: QueueOverflowPolicy::DropOldest,
+                ..Default::default()
+            };
+            route(&router, forwarders, &mut receiver, options).await;
+        });
+
+        // messages of the default destination are not blocked by the slow route
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(default_server.received_requests().await.unwrap().len(), 2);
+
+        drop(sender);
+        routing.await.unwrap();
+        let route_requests = route_server.received_requests().await.unwrap();
+        assert!(!route_requests.is_empty() && route_requests.len() < 10);
+        for request in route_requests {
+            assert_eq!(
+                Media::from_proto(&request.body).unwrap().topic,
+                "route".as_bytes()
+            );
+        }
+    }
+
+    #[test]
+    fn select_by_topic() {
+        let router = Router::new(vec![new_filter(Some("first"), None, None, None)]);
+
+        assert_eq!(router.select(&new_media("first", None)), 1);
+        assert_eq!(
+            router.select(&new_media("second", None)),
+            DEFAULT_DESTINATION
+        );
+    }
+
+    #[test]
+    fn select_by_message_type() {
+        let router = Router::new(vec![
+            new_filter(None, None, None, Some(MessageType::Unknown)),
+            new_filter(None, None, None, Some(MessageType::VideoFrame)),
+        ]);
+
+        assert_eq!(
+            router.select(&new_media("topic", Some(new_frame_message("source")))),
+            2
+        );
+        assert_eq!(
+            router.select(&new_media(
+                "topic",
+                Some(Message::unknown("message".to_string()))
+            )),
+            1
+        );
+        assert_eq!(
+            router.select(&new_media("topic", None)),
+            DEFAULT_DESTINATION
+        );
+    }
+
+    #[test]
+    fn select_by_source_id() {
+        let router = Router::new(vec![new_filter(None, Some("first"), None, None)]);
+
+        assert_eq!(
+            router.select(&new_media("topic", Some(new_frame_message("first")))),
+            1
+        );
+        assert_eq!(
+            router.select(&new_media("topic", Some(new_frame_message("second")))),
+            DEFAULT_DESTINATION
+        );
+    }
+
+    #[test]
+    fn select_by_routing_labels() {
+        let router = Router::new(vec![new_filter(
+            None,
+            None,
+            Some(LabelFilterRule::Set("analytics".to_string())),
+            None,
+        )]);
+        let mut message = Message::unknown("message".to_string());
+        message.meta_mut().routing_labels = vec!["analytics".to_string()];
+
+        assert_eq!(router.select(&new_media("topic", Some(message))), 1);
+        assert_eq!(
+            router.select(&new_media(
+                "topic",
+                Some(Message::unknown("message".to_string()))
+            )),
+            DEFAULT_DESTINATION
+        );
+    }
+
+    fn new_filter(
+        topic: Option<&str>,
+        source_id: Option<&str>,
+        routing_labels: Option<LabelFilterRule>,
+        message_type: Option<MessageType>,
+    ) -> RouteFilter {
+        RouteFilter {
+            topic: topic.map(|e| e.to_string()),
+            source_id: source_id.map(|e| e.to_string()),
+            routing_labels,
+            message_type,
+        }
+    }
+
+    fn new_media(topic: &str, message: Option<Message>) -> Media {
+        Media {
+            message: message.map(|e| savant_protobuf::generated::Message::from(&e)),
+            topic: topic.as_bytes().to_vec(),
+            data: vec![],
+        }
+    }
+
+    fn new_frame_message(source_id: &str) -> Message {
+        let frame = VideoFrameProxy::new(
+            source_id,
+            "30/1",
+            1280,
+            720,
+            VideoFrameContent::None,
+            VideoFrameTranscodingMethod::Copy,
+            &None,
+            None,
+            (1, 1000000),
+            0,
+            None,
+            None,
+        );
+        Message::video_frame(&frame)
+    }
+}
@@ -0,0 +1,219 @@
+//! Sampling of video frames before they are forwarded.
+//!
+//! The module provides [`Sampler`].
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use anyhow::bail;
+use savant_core::message::Message;
+
+use crate::configuration::{SamplingConfiguration, SamplingPolicy};
+
+/// Decides whether a message should be forwarded according to the sampling policy of its source.
+/// Only video frames are sampled, other messages are always forwarded.
+pub struct Sampler {
+    default: Option<SamplingPolicy>,
+    sources: HashMap<String, SamplingPolicy>,
+    states: Mutex<HashMap<String, SourceState>>,
+}
+
+/// A sampling state of a source.
+#[derive(Default)]
+struct SourceState {
+    /// The number of frames passed to `every_nth` condition
+    frames: u64,
+    /// PTS of the last forwarded frame in seconds
+    last_pts: Option<f64>,
+}
+
+impl Sampler {
+    /// Returns `true` if the message should be forwarded.
+    pub fn accept(&self, message: &Message) -> bool {
+        let frame = match message.as_video_frame() {
+            Some(frame) => frame,
+            None => return true,
+        };
+        let source_id = frame.get_source_id();
+        let policy = match self.sources.get(&source_id).or(self.default.as_ref()) {
+            Some(policy) => policy,
+            None => return true,
+        };
+        if policy.keyframes_only == Some(true) && frame.get_keyframe() != Some(true) {
+            return false;
+        }
+        let mut states = self.states.lock().unwrap();
+        let state = states.entry(source_id).or_default();
+        if let Some(every_nth) = policy.every_nth {
+            let number = state.frames;
+            state.frames += 1;
+            if number % every_nth != 0 {
+                return false;
+            }
+        }
+        if let Some(max_fps) = policy.max_fps {
+            let (numerator, denominator) = frame.get_time_base();
+            let pts = frame.get_pts() as f64 * numerator as f64 / denominator as f64;
+            // a frame with PTS less than the last one (e.g. after a stream restart) is forwarded
+            if let Some(last_pts) = state.last_pts {
+                if pts >= last_pts && pts - last_pts < 1.0 / max_fps {
+                    return false;
+                }
+            }
+            state.last_pts = Some(pts);
+        }
+        true
+    }
+}
+
+impl TryFrom<&SamplingConfiguration> for Sampler {
+    type Error = anyhow::Error;
+
+    fn try_from(configuration: &SamplingConfiguration) -> Result<Self, Self::Error> {
+        let sources = configuration.sources.clone().unwrap_or_default();
+        for policy in configuration.default.iter().chain(sources.values()) {
+            if policy.every_nth == Some(0) {
+                bail!("Invalid sampling every_nth: 0");
+            }
+            if let Some(max_fps) = policy.max_fps {
+                if max_fps <= 0.0 || !max_fps.is_finite() {
+                    bail!("Invalid sampling max_fps: {}", max_fps);
+                }
+            }
+        }
+        Ok(Self {
+            default: configuration.default.clone(),
+            sources,
+            states: Mutex::new(HashMap::new()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use savant_core::message::Message;
+    use savant_core::primitives::frame::{
+        VideoFrameContent, VideoFrameProxy, VideoFrameTranscodingMethod,
+    };
+
+    use crate::configuration::{SamplingConfiguration, SamplingPolicy};
+    use crate::sampling::Sampler;
+
+    #[test]
+    fn every_nth() {
+        let sampler = new_sampler(SamplingPolicy {
+            every_nth: Some(3),
+            max_fps: None,
+            keyframes_only: None,
+        });
+
+        let accepted = (0..7)
+            .map(|pts| sampler.accept(&new_frame("source", pts, None)))
+            .collect::<Vec<bool>>();
+
+        assert_eq!(accepted, vec![true, false, false, true, false, false, true]);
+    }
+
+    #[test]
+    fn max_fps() {
+        let sampler = new_sampler(SamplingPolicy {
+            every_nth: None,
+            max_fps: Some(2.0),
+            keyframes_only: None,
+        });
+
+        // PTS in milliseconds
+        let accepted = [0, 250, 500, 750, 1000, 100]
+            .into_iter()
+            .map(|pts| sampler.accept(&new_frame("source", pts, None)))
+            .collect::<Vec<bool>>();
+
+        assert_eq!(accepted, vec![true, false, true, false, true, true]);
+    }
+
+    #[test]
+    fn keyframes_only() {
+        let sampler = new_sampler(SamplingPolicy {
+            every_nth: None,
+            max_fps: None,
+            keyframes_only: Some(true),
+        });
+
+        assert!(sampler.accept(&new_frame("source", 0, Some(true))));
+        assert!(!sampler.accept(&new_frame("source", 1, Some(false))));
+        assert!(!sampler.accept(&new_frame("source", 2, None)));
+    }
+
+    #[test]
+    fn per_source_policies() {
+        let sampler = Sampler::try_from(&SamplingConfiguration {
+            default: None,
+            sources: Some(HashMap::from([(
+                "sampled".to_string(),
+                SamplingPolicy {
+                    every_nth: Some(2),
+                    max_fps: None,
+                    keyframes_only: None,
+                },
+            )])),
+        })
+        .unwrap();
+
+        assert!(sampler.accept(&new_frame("sampled", 0, None)));
+        assert!(!sampler.accept(&new_frame("sampled", 1, None)));
+        assert!(sampler.accept(&new_frame("other", 0, None)));
+        assert!(sampler.accept(&new_frame("other", 1, None)));
+    }
+
+    #[test]
+    fn non_frame_messages() {
+        let sampler = new_sampler(SamplingPolicy {
+            every_nth: None,
+            max_fps: None,
+            keyframes_only: Some(true),
+        });
+
+        assert!(sampler.accept(&Message::unknown("message".to_string())));
+    }
+
+    #[test]
+    fn invalid_every_nth() {
+        let configuration = SamplingConfiguration {
+            default: Some(SamplingPolicy {
+                every_nth: Some(0),
+                max_fps: None,
+                keyframes_only: None,
+            }),
+            sources: None,
+        };
+
+        assert!(Sampler::try_from(&configuration).is_err());
+    }
+
+    fn new_sampler(policy: SamplingPolicy) -> Sampler {
+        Sampler::try_from(&SamplingConfiguration {
+            default: Some(policy),
+            sources: None,
+        })
+        .unwrap()
+    }
+
+    fn new_frame(source_id: &str, pts: i64, keyframe: Option<bool>) -> Message {
+        let frame = VideoFrameProxy::new(
+            source_id,
+            "30/1",
+            1280,
+            720,
+            VideoFrameContent::None,
+            VideoFrameTranscodingMethod::Copy,
+            &None,
+            keyframe,
+            (1, 1000),
+            pts,
+            None,
+            None,
+        );
+        Message::video_frame(&frame)
+    }
+}
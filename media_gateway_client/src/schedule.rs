@@ -0,0 +1,182 @@
+//! Time windows when messages are buffered instead of being forwarded.
+//!
+//! The module provides [`UploadSchedule`].
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use chrono::{DateTime, TimeDelta, Utc};
+use chrono_tz::Tz;
+use cron::Schedule;
+
+use crate::configuration::{ScheduleClass, UploadScheduleConfiguration};
+use crate::queue::MessageClass;
+
+/// Buffer windows start at times of cron schedules in the timezone and last for their
+/// durations. During a window only messages of always forwarded classes are sent, other messages
+/// wait in the queue or stay in the spool (deferred after they are read) until the window is
+/// over.
+pub struct UploadSchedule {
+    /// Schedules of starts of windows and their durations
+    windows: Vec<(Schedule, TimeDelta)>,
+    timezone: Tz,
+    always_forward: Vec<ScheduleClass>,
+}
+
+impl UploadSchedule {
+    /// Returns the time left until the end of the buffer window containing the moment or
+    /// [`None`] if messages can be forwarded at the moment.
+    pub fn remaining_buffering(&self, now: DateTime<Utc>) -> Option<Duration> {
+        let local_now = now.with_timezone(&self.timezone);
+        self.windows
+            .iter()
+            .filter_map(|(schedule, duration)| {
+                // the first start after the beginning of the window ending now
+                let start = schedule.after(&(local_now - *duration)).next()?;
+                (start <= local_now).then(|| start + *duration - local_now)
+            })
+            .max()
+            .map(|e| e.to_std().unwrap_or_default())
+    }
+
+    /// Returns `true` if messages of the class are forwarded during buffer windows.
+    pub fn allows(&self, class: &MessageClass) -> bool {
+        self.always_forward.iter().any(|e| {
+            matches!(
+                (e, class),
+                (ScheduleClass::Control, MessageClass::Control)
+                    | (ScheduleClass::VideoFrame, MessageClass::Frame { .. })
+                    | (ScheduleClass::Metadata, MessageClass::Other)
+            )
+        })
+    }
+}
+
+impl TryFrom<&UploadScheduleConfiguration> for UploadSchedule {
+    type Error = anyhow::Error;
+
+    fn try_from(configuration: &UploadScheduleConfiguration) -> Result<Self, Self::Error> {
+        if configuration.buffer_windows.is_empty() {
+            bail!("Invalid upload schedule buffer_windows: empty");
+        }
+        let timezone = match &configuration.timezone {
+            Some(timezone) => timezone
+                .parse::<Tz>()
+                .map_err(|e| anyhow!("Invalid upload schedule timezone {}: {}", timezone, e))?,
+            None => Tz::UTC,
+        };
+        let windows = configuration
+            .buffer_windows
+            .iter()
+            .map(|window| {
+                let schedule = Schedule::from_str(&window.start).map_err(|e| {
+                    anyhow!("Invalid upload schedule start {}: {}", window.start, e)
+                })?;
+                if window.duration.is_zero() {
+                    bail!("Invalid upload schedule duration: 0");
+                }
+                let duration = TimeDelta::from_std(window.duration)
+                    .map_err(|e| anyhow!("Invalid upload schedule duration: {}", e))?;
+                Ok((schedule, duration))
+            })
+            .collect::<anyhow::Result<Vec<(Schedule, TimeDelta)>>>()?;
+        Ok(Self {
+            windows,
+            timezone,
+            always_forward: configuration
+                .always_forward
+                .clone()
+                .unwrap_or_else(|| vec![ScheduleClass::Control]),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::configuration::{
+        BufferWindowConfiguration, ScheduleClass, UploadScheduleConfiguration,
+    };
+    use crate::queue::MessageClass;
+    use crate::schedule::UploadSchedule;
+
+    #[test]
+    fn business_hours() {
+        // buffering from 9:00 to 18:00 on weekdays in Berlin (UTC+2 in summer)
+        let schedule = new_schedule(Some("Europe/Berlin"), "0 0 9 * * Mon-Fri", 9 * 3600);
+
+        // Friday, 9:30 in Berlin
+        assert_eq!(
+            schedule.remaining_buffering(Utc.with_ymd_and_hms(2024, 7, 5, 7, 30, 0).unwrap()),
+            Some(Duration::from_secs(8 * 3600 + 30 * 60))
+        );
+        // Friday, 8:30 in Berlin
+        assert_eq!(
+            schedule.remaining_buffering(Utc.with_ymd_and_hms(2024, 7, 5, 6, 30, 0).unwrap()),
+            None
+        );
+        // Friday, 18:00 in Berlin
+        assert_eq!(
+            schedule.remaining_buffering(Utc.with_ymd_and_hms(2024, 7, 5, 16, 0, 0).unwrap()),
+            None
+        );
+        // Saturday, 10:00 in Berlin
+        assert_eq!(
+            schedule.remaining_buffering(Utc.with_ymd_and_hms(2024, 7, 6, 8, 0, 0).unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn always_forward() {
+        let schedule = new_schedule(None, "0 0 9 * * *", 3600);
+        let frame = MessageClass::Frame {
+            source_id: "source".to_string(),
+            keyframe: true,
+        };
+
+        assert!(schedule.allows(&MessageClass::Control));
+        assert!(!schedule.allows(&MessageClass::Other));
+        assert!(!schedule.allows(&frame));
+    }
+
+    #[test]
+    fn invalid_configuration() {
+        let mut configuration = new_configuration(Some("Mars/Olympus"), "0 0 9 * * *", 3600);
+        assert!(UploadSchedule::try_from(&configuration).is_err());
+
+        configuration.timezone = None;
+        configuration.buffer_windows[0].start = "every day".to_string();
+        assert!(UploadSchedule::try_from(&configuration).is_err());
+
+        configuration.buffer_windows[0].start = "0 0 9 * * *".to_string();
+        configuration.buffer_windows[0].duration = Duration::ZERO;
+        assert!(UploadSchedule::try_from(&configuration).is_err());
+
+        configuration.buffer_windows.clear();
+        assert!(UploadSchedule::try_from(&configuration).is_err());
+    }
+
+    fn new_schedule(timezone: Option<&str>, start: &str, duration: u64) -> UploadSchedule {
+        UploadSchedule::try_from(&new_configuration(timezone, start, duration)).unwrap()
+    }
+
+    fn new_configuration(
+        timezone: Option<&str>,
+        start: &str,
+        duration: u64,
+    ) -> UploadScheduleConfiguration {
+        UploadScheduleConfiguration {
+            timezone: timezone.map(|e| e.to_string()),
+            buffer_windows: vec![BufferWindowConfiguration {
+                start: start.to_string(),
+                duration: Duration::from_secs(duration),
+            }],
+            always_forward: Some(vec![ScheduleClass::Control]),
+            buffer_size: None,
+        }
+    }
+}
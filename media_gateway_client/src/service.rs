@@ -1,48 +1,405 @@
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, OnceLock};
-use std::time::Duration;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
 use anyhow::{anyhow, bail, Result};
-use savant_core::transport::zeromq::{NonBlockingReader, ReaderResult};
-use tokio::sync::{mpsc, Mutex};
+use chrono::Utc;
+use futures_util::future::join_all;
+use savant_core::message::Message;
+use savant_core::transport::zeromq::{ReaderConfig, ReaderResult, SyncReader};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
 use tokio_timerfd::sleep;
 
 use media_gateway_common::model::Media;
 use media_gateway_common::statistics::StatisticsService;
 
-use crate::client::{ForwardResult, GatewayClient};
-use crate::configuration::GatewayClientConfiguration;
-use crate::retry::{Retry, RetryStrategy};
-use crate::wait::WaitStrategy;
+use crate::client::GatewayClient;
+use crate::configuration::{GatewayClientConfiguration, QueueOverflowPolicy};
+use crate::congestion::{CongestionController, CongestionStats};
+use crate::dead_letter::DeadLetterSink;
+use crate::deduplication::Deduplicator;
+use crate::enrichment::Enricher;
+use crate::expiry::Expiry;
+use crate::fair_queuing::FairQueuing;
+use crate::forwarder::{
+    DispatchOptions, Forwarder, ForwarderOptions, MessageContext, LANE_IDLE_TIMEOUT,
+};
+use crate::priority::Priorities;
+use crate::queue::{MessageQueue, QUEUE_STAGE_NAME};
+use crate::retry::RetryStrategy;
+use crate::routing::{route, Router};
+use crate::sampling::Sampler;
+use crate::schedule::UploadSchedule;
+use crate::shaper::{Shaper, ShaperStats};
+use crate::spool::Spool;
+use crate::transcoding::Transcoder;
 
 const STAT_STAGE_NAME: &str = "client-relay";
+const IN_STREAM_NAME: &str = "in_stream";
+const SAMPLING_STAGE_NAME: &str = "client-sampling-dropped";
+const CONGESTION_STAGE_NAME: &str = "client-congestion-dropped";
+const DEDUPLICATION_STAGE_NAME: &str = "client-deduplication-suppressed";
+const INGEST_STAGE_NAME: &str = "client-ingest";
+/// A name of the source of ingested messages used in logs
+const INGEST_SOURCE_NAME: &str = "ingest";
+/// The maximum period to wait for a message during a buffer window before checking if the
+/// window is over
+const SCHEDULE_CHECK_PERIOD: Duration = Duration::from_secs(1);
+/// A delay before the next attempt to persist a message if the spool fails
+const SPOOL_RETRY_PERIOD: Duration = Duration::from_secs(1);
+
+/// Times when a message is received by the client. Both are taken before the message is
+/// filtered or transcoded.
+#[derive(Clone, Copy)]
+struct Arrival {
+    /// A monotonic time used for expiry
+    instant: Instant,
+    /// A system time used for enrichment
+    time: SystemTime,
+}
+
+impl Arrival {
+    fn now() -> Self {
+        Self {
+            instant: Instant::now(),
+            time: SystemTime::now(),
+        }
+    }
+}
+
+/// A source of messages received by a dedicated thread and processed by a separate task.
+#[derive(Clone)]
+pub struct Source {
+    /// A name of the source used in logs
+    pub name: String,
+    /// A configuration of the reader of the source
+    pub reader_config: ReaderConfig,
+    /// The maximum number of received messages waiting to be processed
+    pub inflight_ops: usize,
+    /// A statistics stage of messages from the source
+    pub statistics_stage: String,
+}
+
+impl Source {
+    /// Receives messages with the blocking reader and sends them with times of their arrival to
+    /// the channel until the channel is closed. Timeouts are sent as well so that the receiving
+    /// task can check if the service is stopped. Returns the result of the reader shutdown.
+    fn receive(
+        reader_config: ReaderConfig,
+        sender: mpsc::Sender<(Result<ReaderResult>, Arrival)>,
+    ) -> Result<()> {
+        let mut reader = match SyncReader::new(&reader_config) {
+            Ok(reader) => reader,
+            Err(e) => {
+                let _ = sender.blocking_send((Err(e), Arrival::now()));
+                return Ok(());
+            }
+        };
+        loop {
+            let receive_result = reader.receive();
+            if sender
+                .blocking_send((receive_result, Arrival::now()))
+                .is_err()
+            {
+                break;
+            }
+        }
+        reader.destroy()
+    }
+
+    /// Persists the message in the spool. If the spool fails (e.g. due to an I/O error) reading is
+    /// paused and the attempt is repeated until the service is stopped, so the message is not
+    /// lost and the backpressure is applied to the source.
+    async fn spool(
+        spool: &Arc<Spool>,
+        media: &Media,
+        context: MessageContext,
+        stopped: &OnceLock<()>,
+    ) -> Result<()> {
+        loop {
+            match spool.append(media, context.clone()).await {
+                // an evicted message is logged by the spool
+                Ok(_) => return Ok(()),
+                Err(e) if stopped.get().is_none() => {
+                    log::warn!("Error while spooling message, retrying: {:?}", e);
+                    sleep(SPOOL_RETRY_PERIOD)
+                        .await
+                        .expect("Error while waiting for spool retry");
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Reads messages until the service is stopped and shuts down the reader.
+    async fn read(
+        self,
+        queue: Option<Arc<MessageQueue>>,
+        spool: Option<Arc<Spool>>,
+        pipeline: MessagePipeline,
+        stopped: Arc<OnceLock<()>>,
+    ) -> Result<()> {
+        log::info!("Message reading from {} is started", self.name);
+        let (sender, mut receiver) = mpsc::channel(self.inflight_ops);
+        let reader_config = self.reader_config.clone();
+        let reader_thread = thread::Builder::new()
+            .name(format!("reader-{}", self.name))
+            .spawn(move || Source::receive(reader_config, sender))?;
+        let mut result = Ok(());
+        loop {
+            if stopped.get().is_some() {
+                log::info!("Message reading from {} is being stopped", self.name);
+                break;
+            }
+            let (receive_result, arrival) = match receiver.recv().await {
+                Some(item) => item,
+                None => break,
+            };
+            match receive_result {
+                Ok(reader_result) => match reader_result {
+                    ReaderResult::Message {
+                        message,
+                        topic,
+                        data,
+                        ..
+                    } => {
+                        log::debug!("Success while reading message from {}", self.name);
+                        let (context, media) = match pipeline
+                            .process(
+                                &self.name,
+                                &self.statistics_stage,
+                                message.as_ref(),
+                                topic,
+                                data,
+                                arrival,
+                            )
+                            .await
+                        {
+                            Some(item) => item,
+                            None => continue,
+                        };
+                        if let Some(spool) = spool.as_ref() {
+                            if let Err(e) = Source::spool(spool, &media, context, &stopped).await {
+                                result = Err(e);
+                                break;
+                            }
+                        } else if let Some(queue) = queue.as_ref() {
+                            let info = queue.info(&media.topic, message.as_ref());
+                            if let Err(e) = queue.push(context, media, info).await {
+                                log::warn!("Error while sharing message: {:?}", e);
+                                break;
+                            }
+                        }
+                    }
+                    ReaderResult::Timeout => {
+                        log::debug!(
+                            "Timeout while receiving message, waiting for the next message"
+                        );
+                    }
+                    _ => {
+                        log::warn!("Unexpected reader result: {:?}", reader_result)
+                    }
+                },
+                Err(e) => {
+                    result = Err(anyhow!("Error while receiving message: {:?}", e));
+                    break;
+                }
+            };
+        }
+        // the reader thread is finished after the next receive attempt when the channel is closed
+        drop(receiver);
+        let thread_result = tokio::task::spawn_blocking(move || reader_thread.join())
+            .await
+            .expect("Error in reader joining task")
+            .expect("Error in reader thread");
+        if let Some(e) = thread_result.err() {
+            log::warn!("Error while shutting down reader of {}: {:?}", self.name, e);
+        }
+        log::info!("Message reading from {} is stopped", self.name);
+        result
+    }
+}
+
+/// Steps applied to each message before it is queued or spooled regardless of whether it is
+/// read from a source or ingested via HTTP: sampling, suppression of duplicates, congestion
+/// control, transcoding and enrichment.
+#[derive(Clone)]
+struct MessagePipeline {
+    sampler: Option<Arc<Sampler>>,
+    deduplicator: Option<Arc<Deduplicator>>,
+    congestion_controller: Option<Arc<CongestionController>>,
+    transcoder: Option<Arc<Transcoder>>,
+    enricher: Option<Arc<Enricher>>,
+    statistics_service: Arc<Option<StatisticsService>>,
+}
+
+impl MessagePipeline {
+    /// Applies the steps to the message. Returns the message to forward with its context or
+    /// [`None`] if the message is dropped.
+    ///
+    /// # Arguments
+    /// * `source` - a name of the source used in logs
+    /// * `statistics_stage` - a statistics stage of messages of the source
+    /// * `message` - the decoded message
+    /// * `topic` - a topic of the message
+    /// * `data` - extra data of the message
+    /// * `arrival` - times when the message is received
+    async fn process(
+        &self,
+        source: &str,
+        statistics_stage: &str,
+        message: &Message,
+        topic: Vec<u8>,
+        data: Vec<Vec<u8>>,
+        arrival: Arrival,
+    ) -> Option<(MessageContext, Media)> {
+        if self.sampler.as_ref().is_some_and(|e| !e.accept(message)) {
+            log::trace!("Message from {} is dropped by sampling", source);
+            self.register_dropped(SAMPLING_STAGE_NAME);
+            return None;
+        }
+        if self
+            .deduplicator
+            .as_ref()
+            .is_some_and(|e| !e.accept(message, &data))
+        {
+            log::trace!("Duplicate message from {} is suppressed", source);
+            self.register_dropped(DEDUPLICATION_STAGE_NAME);
+            return None;
+        }
+        if self
+            .congestion_controller
+            .as_ref()
+            .is_some_and(|e| !e.accept(message))
+        {
+            log::trace!("Message from {} is dropped by congestion control", source);
+            self.register_dropped(CONGESTION_STAGE_NAME);
+            return None;
+        }
+        if let (Some(transcoder), Some(mut frame)) =
+            (self.transcoder.as_ref(), message.as_video_frame())
+        {
+            // images are decoded and encoded by a blocking thread
+            let transcoder = transcoder.clone();
+            let transcode_result =
+                tokio::task::spawn_blocking(move || transcoder.transcode(&mut frame))
+                    .await
+                    .expect("Error in frame transcoding task");
+            if let Err(e) = transcode_result {
+                log::warn!("Error while transcoding frame from {}: {:?}", source, e);
+            }
+        }
+        let id = match self.statistics_service.as_ref() {
+            Some(service) => match service.register_stage_message_start(statistics_stage) {
+                Ok(id) => Some(id),
+                Err(e) => {
+                    log::warn!("Error while starting message statistics: {:?}", e);
+                    None
+                }
+            },
+            None => None,
+        };
+        let mut proto_message = savant_protobuf::generated::Message::from(message);
+        if let Some(enricher) = self.enricher.as_ref() {
+            enricher.enrich(&mut proto_message, arrival.time);
+        }
+        let media = Media {
+            message: Some(proto_message),
+            topic,
+            data,
+        };
+        let context = MessageContext {
+            received_at: arrival.instant,
+            ..MessageContext::new(id, &media.topic, message)
+        };
+        Some((context, media))
+    }
+
+    fn register_dropped(&self, stage: &str) {
+        if let Some(service) = self.statistics_service.as_ref() {
+            if let Err(e) = service.register_stage_message(stage) {
+                log::warn!("Error while registering dropped message: {:?}", e);
+            }
+        }
+    }
+}
 
 pub struct GatewayClientService {
     channel_size: usize,
-    client: Arc<GatewayClient>,
-    reader: Arc<Mutex<NonBlockingReader>>,
-    wait_strategy: WaitStrategy,
-    retry_strategy: RetryStrategy,
+    router: Arc<Router>,
+    forwarders: Vec<Arc<Forwarder>>,
+    dispatch_options: DispatchOptions,
+    sources: Vec<Source>,
     statistics_service: Arc<Option<StatisticsService>>,
+    spool: Option<Arc<Spool>>,
+    pipeline: MessagePipeline,
+    queue: Arc<MessageQueue>,
+    shaper: Option<Arc<Shaper>>,
+    congestion_controller: Option<Arc<CongestionController>>,
+    upload_schedule: Option<Arc<UploadSchedule>>,
     started: Arc<OnceLock<()>>,
     stopped: Arc<OnceLock<()>>,
 }
 
 impl GatewayClientService {
+    /// Constructs a new instance.
+    ///
+    /// # Arguments
+    /// * `router` - a router to select a forwarder for each message
+    /// * `forwarders` - forwarders for destinations of the router in order of their indexes
+    /// * `dispatch_options` - settings of lanes of messages forwarded independently
+    /// * `sources` - sources of messages
+    /// * `channel_size` - a size of queues of messages
+    /// * `statistics_service` - a statistics service
+    /// * `spool` - a spool to persist messages
+    /// * `sampler` - a sampler of video frames
+    /// * `queue` - a queue of messages between readers and the sender (not used with the spool)
+    /// * `shaper` - a shaper of the bandwidth shared by forwarders
+    /// * `congestion_controller` - a controller of frame rates shared by forwarders
+    /// * `transcoder` - a transcoder of video frames
+    /// * `deduplicator` - a filter of duplicate video frames
+    /// * `enricher` - an enricher of messages
+    /// * `upload_schedule` - time windows when messages are buffered
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        client: GatewayClient,
-        reader: NonBlockingReader,
-        wait_strategy: WaitStrategy,
-        retry_strategy: RetryStrategy,
+        router: Router,
+        forwarders: Vec<Forwarder>,
+        dispatch_options: DispatchOptions,
+        sources: Vec<Source>,
         channel_size: usize,
-        statistics_service: Option<StatisticsService>,
+        statistics_service: Arc<Option<StatisticsService>>,
+        spool: Option<Arc<Spool>>,
+        sampler: Option<Sampler>,
+        queue: MessageQueue,
+        shaper: Option<Arc<Shaper>>,
+        congestion_controller: Option<Arc<CongestionController>>,
+        transcoder: Option<Transcoder>,
+        deduplicator: Option<Deduplicator>,
+        enricher: Option<Enricher>,
+        upload_schedule: Option<UploadSchedule>,
     ) -> Self {
         Self {
             channel_size,
-            client: Arc::new(client),
-            reader: Arc::new(Mutex::new(reader)),
-            wait_strategy,
-            retry_strategy,
-            statistics_service: Arc::new(statistics_service),
+            router: Arc::new(router),
+            forwarders: forwarders.into_iter().map(Arc::new).collect(),
+            dispatch_options,
+            sources,
+            pipeline: MessagePipeline {
+                sampler: sampler.map(Arc::new),
+                deduplicator: deduplicator.map(Arc::new),
+                congestion_controller: congestion_controller.clone(),
+                transcoder: transcoder.map(Arc::new),
+                enricher: enricher.map(Arc::new),
+                statistics_service: statistics_service.clone(),
+            },
+            statistics_service,
+            spool,
+            queue: Arc::new(queue),
+            shaper,
+            congestion_controller,
+            upload_schedule: upload_schedule.map(Arc::new),
             started: Arc::new(OnceLock::new()),
             stopped: Arc::new(OnceLock::new()),
         }
@@ -55,147 +412,200 @@ impl GatewayClientService {
         }
         log::info!("Service is being started");
 
-        let (sender, mut receiver) = mpsc::channel(self.channel_size);
-
-        let reader_lock = self.reader.clone();
-        let reader_stopped = self.stopped.clone();
-        let reader_wait_strategy = self.wait_strategy.clone();
-        let reader_statistics_service = self.statistics_service.clone();
-
-        let reader_task = tokio::spawn(async move {
-            log::info!("Message reading is started");
-            loop {
-                if reader_stopped.get().is_some() {
-                    log::info!("Message reading is being stopped");
-                    break;
-                }
-                let reader = reader_lock.lock().await;
-                let receive_result = reader.try_receive();
-                if receive_result.is_none() {
-                    log::trace!("No message received, yielding");
-                    reader_wait_strategy.wait().await;
-                    continue;
-                }
-                match receive_result.unwrap() {
-                    Ok(reader_result) => match reader_result {
-                        ReaderResult::Message {
-                            message,
-                            topic,
-                            data,
-                            ..
-                        } => {
-                            log::debug!("Success while reading message");
-                            let id = match reader_statistics_service.as_ref() {
-                                Some(service) => match service.register_message_start() {
-                                    Ok(id) => Some(id),
-                                    Err(e) => {
-                                        log::warn!(
-                                            "Error while starting message statistics: {:?}",
-                                            e
-                                        );
-                                        None
-                                    }
-                                },
-                                None => None,
-                            };
-                            let media = Media {
-                                message: Option::from(savant_protobuf::generated::Message::from(
-                                    message.as_ref(),
-                                )),
-                                topic,
-                                data,
-                            };
-                            if let Err(e) = sender.send((id, media)).await {
-                                log::warn!("Error while sharing message: {:?}", e);
-                                break;
+        // if the spool is enabled messages are read from the spool, otherwise they are read
+        // from the queue filled by the reader tasks, and sent to the channel by a separate task
+        let (queue, channel_task, mut receiver) = match &self.spool {
+            Some(spool) => {
+                let (sender, receiver) = mpsc::channel(self.channel_size);
+                let spool = spool.clone();
+                let schedule = self.upload_schedule.clone();
+                let spool_task = tokio::spawn(async move {
+                    log::info!("Message replaying from spool is started");
+                    loop {
+                        let buffering = schedule.as_ref().and_then(|schedule| {
+                            let remaining = schedule.remaining_buffering(Utc::now())?;
+                            Some((schedule, remaining))
+                        });
+                        let record = match buffering {
+                            // only messages of always forwarded classes are read until the end
+                            // of the buffer window, other messages are deferred and read after
+                            // the window or replayed after a restart if the service is stopped
+                            Some((schedule, remaining)) => {
+                                let timeout = remaining.min(SCHEDULE_CHECK_PERIOD);
+                                let record = match spool.next_new(timeout).await {
+                                    Ok(Some(record)) => record,
+                                    Ok(None) => continue,
+                                    Err(_) => break,
+                                };
+                                if !schedule.allows(&record.context.class(&record.media)) {
+                                    spool.defer(record);
+                                    continue;
+                                }
+                                record
                             }
+                            None => match spool.next().await {
+                                Some(record) => record,
+                                None => break,
+                            },
+                        };
+                        if let Err(e) = sender.send((record.context, record.media)).await {
+                            log::warn!("Error while sharing message: {:?}", e);
+                            break;
                         }
-                        ReaderResult::Timeout => {
-                            log::debug!(
-                                "Timeout while receiving message, waiting for the next message"
-                            );
-                        }
-                        _ => {
-                            log::warn!("Unexpected reader result: {:?}", reader_result)
-                        }
-                    },
-                    Err(e) => bail!("Error while receiving message: {:?}", e),
-                };
+                    }
+                    log::info!("Message replaying from spool is stopped");
+                });
+                (None, spool_task, receiver)
             }
-            let shutdown_result = reader_lock.lock().await.shutdown();
-            if let Some(e) = shutdown_result.err() {
-                log::warn!("Error while shutting down reader: {:?}", e);
+            None => {
+                // the queue holds messages while the channel is full
+                let (sender, receiver) = mpsc::channel(1);
+                let queue = self.queue.clone();
+                let channel_queue = queue.clone();
+                let schedule = self.upload_schedule.clone();
+                let queue_task = tokio::spawn(async move {
+                    loop {
+                        let buffering = schedule.as_ref().and_then(|schedule| {
+                            let remaining = schedule.remaining_buffering(Utc::now())?;
+                            Some((schedule, remaining))
+                        });
+                        let item = match buffering {
+                            // only messages of always forwarded classes are taken until the end
+                            // of the buffer window
+                            Some((schedule, remaining)) => tokio::select! {
+                                item = channel_queue.pop_matching(|e| schedule.allows(e)) => item,
+                                result = sleep(remaining) => {
+                                    result.expect("Error while waiting for buffer window");
+                                    continue;
+                                }
+                            },
+                            None => channel_queue.pop().await,
+                        };
+                        let item = match item {
+                            Some(item) => item,
+                            None => break,
+                        };
+                        if let Err(e) = sender.send(item).await {
+                            log::warn!("Error while sharing message: {:?}", e);
+                            break;
+                        }
+                    }
+                });
+                (Some(queue), queue_task, receiver)
             }
-            log::info!("Message reading is stopped");
-            Ok(())
+        };
+
+        let reader_tasks = self
+            .sources
+            .iter()
+            .map(|source| {
+                tokio::spawn(source.clone().read(
+                    queue.clone(),
+                    self.spool.clone(),
+                    self.pipeline.clone(),
+                    self.stopped.clone(),
+                ))
+            })
+            .collect::<Vec<JoinHandle<Result<()>>>>();
+
+        let congestion_task = self.congestion_controller.clone().map(|controller| {
+            let queue = self.queue.clone();
+            let stopped = self.stopped.clone();
+            tokio::spawn(async move { controller.run(&queue, &stopped).await })
         });
 
-        let client = self.client.clone();
-        let sender_statistics_service = self.statistics_service.clone();
-        let sender_retry_strategy = self.retry_strategy.clone();
+        let router = self.router.clone();
+        let forwarders = self.forwarders.clone();
+        let dispatch_options = self.dispatch_options.clone();
 
         let sender_task: tokio::task::JoinHandle<Result<()>> = tokio::spawn(async move {
             log::info!("Message sending is started");
-            while let Some((id, media)) = receiver.recv().await {
-                let mut retry: Option<Retry> = None;
-                loop {
-                    let forward_result = client.forward_message(&media).await;
-                    match forward_result {
-                        Ok(ForwardResult::Success) => {
-                            if let Some(stat_id) = id {
-                                if let Err(e) = sender_statistics_service
-                                    .as_ref()
-                                    .as_ref()
-                                    .unwrap()
-                                    .register_message_end(stat_id)
-                                {
-                                    log::warn!("Error while ending message statistics: {:?}", e)
-                                }
-                            }
-                            if retry.is_some() {
-                                log::info!(
-                                    "Success while sending message on {} retry",
-                                    retry.unwrap().number()
-                                );
-                            } else {
-                                log::debug!("Success while sending message (retry=0)");
-                            }
-                            break;
-                        }
-                        Ok(result) => {
-                            log::warn!(
-                                "Failure while sending message (retry={}): {:?}",
-                                retry.as_ref().map_or(0, |e| e.number()),
-                                result
-                            );
-                        }
-                        Err(e) => {
-                            log::warn!(
-                                "Error while sending message (retry={}): {:?}",
-                                retry.as_ref().map_or(0, |e| e.number()),
-                                e
-                            )
-                        }
-                    }
-                    let next_retry = sender_retry_strategy.next_retry(retry);
-                    let sleep_duration = next_retry.delay();
-                    retry = Some(next_retry);
-                    log::warn!("Next retry after {} nanoseconds", sleep_duration.as_nanos());
-                    sleep(sleep_duration)
-                        .await
-                        .expect("Error while sleeping between attmpts to send a message")
-                }
-            }
+            route(&router, forwarders, &mut receiver, dispatch_options).await;
             log::info!("Message sending is being stopped");
             log::info!("Message sending is stopped");
             Ok(())
         });
-        let _ = reader_task.await.expect("Error in message reading task");
+        for (source, reader_result) in self.sources.iter().zip(join_all(reader_tasks).await) {
+            if let Err(e) = reader_result.expect("Error in message reading task") {
+                log::warn!("Error while reading messages from {}: {:?}", source.name, e);
+            }
+        }
+        // the channel is closed when all reader tasks are finished
+        if let Some(spool) = &self.spool {
+            spool.close().await;
+        }
+        if let Some(queue) = &queue {
+            queue.close();
+        }
+        channel_task.await.expect("Error in message sharing task");
         let _ = sender_task.await.expect("Error in message sending task");
+        for forwarder in self.forwarders.iter() {
+            forwarder.shutdown().await;
+        }
+        if let Some(congestion_task) = congestion_task {
+            congestion_task
+                .await
+                .expect("Error in frame rate adjustment task");
+        }
         log::info!("Service is stopped");
         Ok(())
     }
 
+    /// Injects the message received via HTTP into the spool or the queue of messages read from
+    /// sources. The message is processed the same way as messages read from sources, so it may
+    /// be dropped by sampling, suppression of duplicates or congestion control.
+    ///
+    /// # Arguments
+    /// * `media` - a message to forward
+    /// * `message` - the decoded message
+    pub async fn ingest(&self, media: Media, message: &Message) -> Result<()> {
+        let arrival = Arrival::now();
+        if self.stopped.get().is_some() {
+            bail!("Service is stopped");
+        }
+        let (context, media) = match self
+            .pipeline
+            .process(
+                INGEST_SOURCE_NAME,
+                INGEST_STAGE_NAME,
+                message,
+                media.topic,
+                media.data,
+                arrival,
+            )
+            .await
+        {
+            Some(item) => item,
+            None => return Ok(()),
+        };
+        match &self.spool {
+            // the error is returned to the sender to retry later if the message is evicted
+            Some(spool) => match spool.append(&media, context).await? {
+                Some(_) => Ok(()),
+                None => bail!("Message is not spooled"),
+            },
+            None => {
+                let info = self.queue.info(&media.topic, message);
+                self.queue.push(context, media, info).await
+            }
+        }
+    }
+
+    /// Returns the number of messages in the queue by sources.
+    pub fn queue_depths(&self) -> HashMap<String, usize> {
+        self.queue.depths()
+    }
+
+    /// Returns the state of the bandwidth shaper if it is enabled.
+    pub fn shaper_stats(&self) -> Option<ShaperStats> {
+        self.shaper.as_ref().map(|e| e.stats())
+    }
+
+    /// Returns the state of the congestion controller if it is enabled.
+    pub fn congestion_stats(&self) -> Option<CongestionStats> {
+        self.congestion_controller.as_ref().map(|e| e.stats())
+    }
+
     pub fn stop(&self) -> Result<()> {
         log::info!("Service is being stopped");
         let stopped_result = self.stopped.set(());
@@ -212,20 +622,98 @@ impl TryFrom<&GatewayClientConfiguration> for GatewayClientService {
     fn try_from(
         configuration: &GatewayClientConfiguration,
     ) -> std::result::Result<Self, Self::Error> {
-        let reader = NonBlockingReader::try_from(&configuration.in_stream)?;
-        let client = GatewayClient::try_from(configuration)?;
-        let statistics_service = if let Some(statistics_conf) = &configuration.statistics {
-            Some(StatisticsService::try_from((
-                statistics_conf,
-                STAT_STAGE_NAME,
-            ))?)
-        } else {
-            None
-        };
-        let wait_strategy = match &configuration.wait_strategy {
-            Some(strategy) => strategy.clone(),
-            None => WaitStrategy::Sleep(Duration::from_millis(1)),
+        if configuration.wait_strategy.is_some() {
+            log::warn!(
+                "wait_strategy is deprecated and ignored, sources are read by dedicated blocking \
+                threads"
+            );
+        }
+        // (name, source configuration, statistics stage)
+        let source_configurations = match (&configuration.in_stream, &configuration.in_streams) {
+            (Some(in_stream), None) => vec![(
+                IN_STREAM_NAME.to_string(),
+                in_stream,
+                STAT_STAGE_NAME.to_string(),
+            )],
+            (None, Some(in_streams)) => {
+                if in_streams.is_empty() {
+                    return Err(anyhow!("Invalid in_streams: empty"));
+                }
+                let mut names = HashSet::new();
+                for in_stream in in_streams {
+                    if !names.insert(in_stream.name.as_str()) {
+                        return Err(anyhow!(
+                            "Invalid in_streams: duplicate name {}",
+                            in_stream.name
+                        ));
+                    }
+                    if in_stream.wait_strategy.is_some() {
+                        log::warn!(
+                            "wait_strategy of {} is deprecated and ignored, the source is read \
+                            by a dedicated blocking thread",
+                            in_stream.name
+                        );
+                    }
+                }
+                in_streams
+                    .iter()
+                    .map(|e| {
+                        (
+                            e.name.clone(),
+                            &e.source,
+                            format!("{}-{}", STAT_STAGE_NAME, e.name),
+                        )
+                    })
+                    .collect()
+            }
+            _ => bail!("Exactly one of in_stream and in_streams must be specified"),
         };
+        let channel_size = source_configurations
+            .iter()
+            .map(|(_, source_configuration, _)| source_configuration.inflight_ops)
+            .sum();
+        let routes = configuration.routes.as_deref().unwrap_or_default();
+        let mut route_names = HashSet::new();
+        for route in routes {
+            if !route_names.insert(route.name.as_str()) {
+                return Err(anyhow!("Invalid routes: duplicate name {}", route.name));
+            }
+        }
+        let mut clients = vec![GatewayClient::try_from(configuration)?];
+        for route in routes {
+            clients.push(GatewayClient::try_from((configuration, route))?);
+        }
+        let statistics_service =
+            Arc::new(if let Some(statistics_conf) = &configuration.statistics {
+                let mut stages = source_configurations
+                    .iter()
+                    .map(|(_, _, stage)| stage.as_str())
+                    .collect::<Vec<&str>>();
+                if configuration.sampling.is_some() {
+                    stages.push(SAMPLING_STAGE_NAME);
+                }
+                if configuration.ingest.is_some() {
+                    stages.push(INGEST_STAGE_NAME);
+                }
+                if configuration.deduplication.is_some() {
+                    stages.push(DEDUPLICATION_STAGE_NAME);
+                }
+                if configuration.congestion_control.is_some() {
+                    stages.push(CONGESTION_STAGE_NAME);
+                }
+                if configuration.spool.is_none()
+                    && configuration.queue_overflow.unwrap_or_default()
+                        != QueueOverflowPolicy::Block
+                {
+                    stages.push(QUEUE_STAGE_NAME);
+                }
+                Some(StatisticsService::try_from((
+                    statistics_conf,
+                    stages.as_slice(),
+                ))?)
+            } else {
+                None
+            });
         let retry_strategy = match &configuration.retry_strategy {
             Some(RetryStrategy::Exponential {
                 initial_delay,
@@ -244,19 +732,566 @@ impl TryFrom<&GatewayClientConfiguration> for GatewayClientService {
                     multiplier: *multiplier,
                 }
             }
-            None => RetryStrategy::Exponential {
-                initial_delay: Duration::from_millis(1),
-                maximum_delay: Duration::from_secs(1),
-                multiplier: 2,
+            Some(RetryStrategy::Fixed { delay }) => RetryStrategy::Fixed { delay: *delay },
+            Some(RetryStrategy::Linear {
+                initial_delay,
+                increment,
+                maximum_delay,
+            }) => {
+                if initial_delay > maximum_delay {
+                    return Err(anyhow!("Invalid initial_delay: greater than maximum_delay"));
+                }
+                RetryStrategy::Linear {
+                    initial_delay: *initial_delay,
+                    increment: *increment,
+                    maximum_delay: *maximum_delay,
+                }
+            }
+            Some(RetryStrategy::DecorrelatedJitter {
+                base_delay,
+                maximum_delay,
+            }) => {
+                if base_delay.is_zero() {
+                    return Err(anyhow!("Invalid base_delay: 0"));
+                }
+                if base_delay > maximum_delay {
+                    return Err(anyhow!("Invalid base_delay: greater than maximum_delay"));
+                }
+                RetryStrategy::DecorrelatedJitter {
+                    base_delay: *base_delay,
+                    maximum_delay: *maximum_delay,
+                }
+            }
+            None => RetryStrategy::default(),
+        };
+        let retry_policy = configuration.retry_policy.clone().unwrap_or_default();
+        if let Some(http_statuses) = &retry_policy.http_statuses {
+            if let Some(status) = http_statuses.keys().find(|e| !(100..600).contains(*e)) {
+                return Err(anyhow!("Invalid retry_policy HTTP status: {}", status));
+            }
+        }
+        let batch_configuration = match &configuration.batch {
+            Some(batch_configuration) => {
+                if batch_configuration.max_count == 0 {
+                    return Err(anyhow!("Invalid batch max_count: 0"));
+                }
+                if batch_configuration.max_bytes == 0 {
+                    return Err(anyhow!("Invalid batch max_bytes: 0"));
+                }
+                Some(batch_configuration.clone())
+            }
+            None => None,
+        };
+        let max_inflight = match configuration.max_inflight {
+            Some(0) => return Err(anyhow!("Invalid max_inflight: 0")),
+            Some(max_inflight) => max_inflight,
+            None => 1,
+        };
+        let spool = match &configuration.spool {
+            Some(spool_configuration) => Some(Arc::new(Spool::try_from(spool_configuration)?)),
+            None => None,
+        };
+        let expiry = match &configuration.expiry {
+            Some(expiry_configuration) => Some(Expiry::try_from(expiry_configuration)?),
+            None => None,
+        };
+        let dead_letter = match &configuration.dead_letter {
+            Some(dead_letter_configuration) => Some(Arc::new(DeadLetterSink::try_from(
+                dead_letter_configuration,
+            )?)),
+            None => None,
+        };
+        let sampler = match &configuration.sampling {
+            Some(sampling_configuration) => Some(Sampler::try_from(sampling_configuration)?),
+            None => None,
+        };
+        let priorities = match &configuration.priorities {
+            Some(priority_configuration) => Some(Priorities::try_from(priority_configuration)?),
+            None => None,
+        };
+        let fair_queuing = match &configuration.fair_queuing {
+            Some(fair_queuing_configuration) => {
+                Some(FairQueuing::try_from(fair_queuing_configuration)?)
+            }
+            None => None,
+        };
+        let upload_schedule = match &configuration.upload_schedule {
+            Some(upload_schedule_configuration) => {
+                Some(UploadSchedule::try_from(upload_schedule_configuration)?)
+            }
+            None => None,
+        };
+        let queue_capacity = match configuration
+            .upload_schedule
+            .as_ref()
+            .and_then(|e| e.buffer_size)
+        {
+            Some(0) => return Err(anyhow!("Invalid upload schedule buffer_size: 0")),
+            Some(buffer_size) => buffer_size,
+            None => channel_size,
+        };
+        let queue = MessageQueue::new(
+            configuration.queue_overflow.unwrap_or_default(),
+            queue_capacity,
+            priorities,
+            fair_queuing,
+            statistics_service.clone(),
+        );
+        let shaper = match &configuration.shaping {
+            Some(shaping_configuration) => Some(Arc::new(Shaper::try_from(shaping_configuration)?)),
+            None => None,
+        };
+        let congestion_controller = match &configuration.congestion_control {
+            Some(congestion_control_configuration) => Some(Arc::new(
+                CongestionController::try_from(congestion_control_configuration)?,
+            )),
+            None => None,
+        };
+        let transcoder = match &configuration.transcoding {
+            Some(transcoding_configuration) => {
+                Some(Transcoder::try_from(transcoding_configuration)?)
+            }
+            None => None,
+        };
+        let deduplicator = match &configuration.deduplication {
+            Some(deduplication_configuration) => {
+                Some(Deduplicator::try_from(deduplication_configuration)?)
+            }
+            None => None,
+        };
+        let enricher = match &configuration.enrichment {
+            Some(enrichment_configuration) => Some(Enricher::try_from(enrichment_configuration)?),
+            None => None,
+        };
+        // the spool keeps messages which are not forwarded, so lanes are never dropped
+        let dispatch_options = DispatchOptions {
+            per_source: configuration.max_inflight.is_some(),
+            lane_size: channel_size,
+            overflow: match &configuration.spool {
+                Some(_) => QueueOverflowPolicy::Block,
+                None => configuration.queue_overflow.unwrap_or_default(),
             },
+            idle_timeout: LANE_IDLE_TIMEOUT,
+            statistics_service: statistics_service.clone(),
         };
-        Ok(GatewayClientService::new(
-            client,
-            reader,
-            wait_strategy,
+        let router = Router::new(routes.iter().map(|e| e.filter.clone()).collect());
+        // the default destination is followed by routes as clients
+        let metadata_only = std::iter::once(configuration.metadata_only)
+            .chain(routes.iter().map(|e| e.metadata_only))
+            .map(|e| e.unwrap_or(false));
+        let options = ForwarderOptions {
             retry_strategy,
-            configuration.in_stream.inflight_ops,
+            retry_policy,
+            batch_configuration,
+            max_inflight,
+            statistics_service: statistics_service.clone(),
+            spool: spool.clone(),
+            expiry,
+            dead_letter,
+            shaper: shaper.clone(),
+            congestion_controller: congestion_controller.clone(),
+            metadata_only: false,
+        };
+        let forwarders = clients
+            .into_iter()
+            .zip(metadata_only)
+            .map(|(client, metadata_only)| {
+                Forwarder::new(
+                    client,
+                    ForwarderOptions {
+                        metadata_only,
+                        ..options.clone()
+                    },
+                )
+            })
+            .collect();
+        let sources = source_configurations
+            .into_iter()
+            .map(|(name, source_configuration, statistics_stage)| {
+                if source_configuration.inflight_ops == 0 {
+                    bail!("Invalid inflight_ops of {}: 0", name);
+                }
+                Ok(Source {
+                    name,
+                    reader_config: ReaderConfig::try_from(source_configuration)?,
+                    inflight_ops: source_configuration.inflight_ops,
+                    statistics_stage,
+                })
+            })
+            .collect::<Result<Vec<Source>>>()?;
+        Ok(GatewayClientService::new(
+            router,
+            forwarders,
+            dispatch_options,
+            sources,
+            channel_size,
             statistics_service,
+            spool,
+            sampler,
+            queue,
+            shaper,
+            congestion_controller,
+            transcoder,
+            deduplicator,
+            enricher,
+            upload_schedule,
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use reqwest::StatusCode;
+    use savant_core::message::Message;
+    use savant_core::primitives::frame::{
+        VideoFrameContent, VideoFrameProxy, VideoFrameTranscodingMethod,
+    };
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use media_gateway_common::model::Media;
+
+    use crate::configuration::GatewayClientConfiguration;
+    use crate::service::GatewayClientService;
+
+    #[tokio::test]
+    async fn in_streams_stop() {
+        let service = GatewayClientService::try_from(&new_configuration(&format!(
+            r#""in_streams": [{}, {}]"#,
+            new_source("first"),
+            new_source("second")
+        )))
+        .unwrap();
+
+        assert_eq!(
+            service
+                .sources
+                .iter()
+                .map(|e| e.name.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["first", "second"]
+        );
+        assert_eq!(service.channel_size, 20);
+
+        service.stop().unwrap();
+        service.run().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn deprecated_wait_strategy() {
+        let source = new_source("first").replacen('{', r#"{"wait_strategy": "yield","#, 1);
+        let configuration = new_configuration(&format!(
+            r#""in_streams": [{}], "wait_strategy": {{"sleep": {{"secs": 0, "nanos": 1000000}}}}"#,
+            source
+        ));
+
+        assert!(configuration.wait_strategy.is_some());
+        assert!(configuration.in_streams.as_ref().unwrap()[0]
+            .wait_strategy
+            .is_some());
+        assert!(GatewayClientService::try_from(&configuration).is_ok());
+    }
+
+    #[tokio::test]
+    async fn ingest() {
+        let service = GatewayClientService::try_from(&new_configuration(&format!(
+            r#""in_stream": {}, "ingest": {{}}"#,
+            new_source("in_stream")
+        )))
+        .unwrap();
+        let message = Message::unknown("message".to_string());
+        let media = Media {
+            message: Some(savant_protobuf::generated::Message::from(&message)),
+            topic: "topic".as_bytes().to_vec(),
+            data: vec![],
+        };
+
+        service.ingest(media.clone(), &message).await.unwrap();
+
+        assert_eq!(
+            service.queue_depths(),
+            HashMap::from([("topic".to_string(), 1)])
+        );
+
+        service.stop().unwrap();
+        assert!(service.ingest(media, &message).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn ingest_samples_and_enriches() {
+        let service = GatewayClientService::try_from(&new_configuration(&format!(
+            r#""in_stream": {}, "ingest": {{}},
+            "sampling": {{"default": {{"every_nth": 2}}}},
+            "enrichment": {{"routing_labels": ["edge"]}}"#,
+            new_source("in_stream")
+        )))
+        .unwrap();
+
+        for _ in 0..2 {
+            let message = Message::video_frame(&new_frame());
+            let media = Media {
+                message: Some(savant_protobuf::generated::Message::from(&message)),
+                topic: "frame".as_bytes().to_vec(),
+                data: vec![],
+            };
+            service.ingest(media, &message).await.unwrap();
+        }
+
+        assert_eq!(
+            service.queue_depths(),
+            HashMap::from([("frame".to_string(), 1)])
+        );
+        let (_, media) = service.queue.pop().await.unwrap();
+        assert_eq!(media.message.unwrap().routing_labels, vec!["edge"]);
+    }
+
+    #[tokio::test]
+    async fn heavy_source_does_not_starve_light_source() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(StatusCode::OK).set_delay(Duration::from_millis(50)),
+            )
+            .mount(&server)
+            .await;
+        let service = Arc::new(
+            GatewayClientService::try_from(&new_configuration_with_url(
+                &server.uri(),
+                &format!(
+                    r#""in_stream": {}, "ingest": {{}}, "max_inflight": 2,
+                    "queue_overflow": "drop_oldest", "fair_queuing": {{}}"#,
+                    new_source("in_stream")
+                ),
+            ))
+            .unwrap(),
+        );
+        let running_service = service.clone();
+        let running = tokio::spawn(async move { running_service.run().await });
+        // the heavy source floods the queue before messages of the light source are received
+        for (topic, i) in (0..50u8)
+            .map(|e| ("heavy", e))
+            .chain((0..3u8).map(|e| ("light", e)))
+        {
+            let message = Message::unknown("message".to_string());
+            let media = Media {
+                message: Some(savant_protobuf::generated::Message::from(&message)),
+                topic: topic.as_bytes().to_vec(),
+                data: vec![vec![i]],
+            };
+            service.ingest(media, &message).await.unwrap();
+        }
+
+        // queued messages are forwarded before the service is stopped
+        service.stop().unwrap();
+        running.await.unwrap().unwrap();
+        assert_eq!(received_data(&server, "light").await, vec![0, 1, 2]);
+        let heavy_data = received_data(&server, "heavy").await;
+        assert!(heavy_data.len() < 50);
+        assert_eq!(heavy_data.last(), Some(&49));
+        // messages of the light source do not wait until the backlog of the heavy source is sent
+        let topics = received_topics(&server).await;
+        let last_light = topics.iter().rposition(|e| e == "light").unwrap();
+        let last_heavy = topics.iter().rposition(|e| e == "heavy").unwrap();
+        assert!(last_light < last_heavy);
+    }
+
+    #[tokio::test]
+    async fn spool_forwards_always_forwarded_classes_during_buffer_window() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(StatusCode::OK))
+            .mount(&server)
+            .await;
+        let directory =
+            std::env::temp_dir().join(format!("media_gateway_spool_{}", rand::random::<u64>()));
+        // the buffer window is started every second and lasts for an hour
+        let service = Arc::new(
+            GatewayClientService::try_from(&new_configuration_with_url(
+                &server.uri(),
+                &format!(
+                    r#""in_stream": {}, "ingest": {{}},
+                    "spool": {{"directory": "{}", "segment_size": 1024, "max_size": 65536}},
+                    "upload_schedule": {{
+                        "buffer_windows": [
+                            {{"start": "* * * * * *", "duration": {{"secs": 3600, "nanos": 0}}}}
+                        ],
+                        "always_forward": ["metadata"]
+                    }}"#,
+                    new_source("in_stream"),
+                    directory.to_str().unwrap()
+                ),
+            ))
+            .unwrap(),
+        );
+        let running_service = service.clone();
+        let running = tokio::spawn(async move { running_service.run().await });
+        let messages = [
+            ("frame", Message::video_frame(&new_frame())),
+            ("metadata", Message::unknown("message".to_string())),
+        ];
+        for (i, (topic, message)) in messages.into_iter().enumerate() {
+            let media = Media {
+                message: Some(savant_protobuf::generated::Message::from(&message)),
+                topic: topic.as_bytes().to_vec(),
+                data: vec![vec![i as u8]],
+            };
+            service.ingest(media, &message).await.unwrap();
+        }
+
+        // the frame is spooled first, so it is deferred by the time the metadata is forwarded
+        for _ in 0..100 {
+            if !received_data(&server, "metadata").await.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(received_data(&server, "metadata").await, vec![1]);
+        assert!(received_data(&server, "frame").await.is_empty());
+
+        service.stop().unwrap();
+        running.await.unwrap().unwrap();
+        assert!(received_data(&server, "frame").await.is_empty());
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn invalid_linear_retry_strategy() {
+        let configuration = new_configuration(&format!(
+            r#""in_stream": {}, "retry_strategy": {{"linear": {{
+                "initial_delay": {{"secs": 2, "nanos": 0}},
+                "increment": {{"secs": 1, "nanos": 0}},
+                "maximum_delay": {{"secs": 1, "nanos": 0}}
+            }}}}"#,
+            new_source("in_stream")
+        ));
+
+        let error = GatewayClientService::try_from(&configuration)
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("Invalid initial_delay"));
+    }
+
+    #[test]
+    fn invalid_decorrelated_jitter_retry_strategy() {
+        for (base_delay, maximum_delay) in [(0, 1), (2, 1)] {
+            let configuration = new_configuration(&format!(
+                r#""in_stream": {}, "retry_strategy": {{"decorrelated_jitter": {{
+                    "base_delay": {{"secs": {}, "nanos": 0}},
+                    "maximum_delay": {{"secs": {}, "nanos": 0}}
+                }}}}"#,
+                new_source("in_stream"),
+                base_delay,
+                maximum_delay
+            ));
+
+            let error = GatewayClientService::try_from(&configuration)
+                .err()
+                .unwrap();
+            assert!(error.to_string().contains("Invalid base_delay"));
+        }
+    }
+
+    #[test]
+    fn in_streams_duplicate_name() {
+        let configuration = new_configuration(&format!(
+            r#""in_streams": [{}, {}]"#,
+            new_source("first"),
+            new_source("first")
+        ));
+
+        assert!(GatewayClientService::try_from(&configuration).is_err());
+    }
+
+    #[test]
+    fn in_stream_and_in_streams() {
+        let configuration = new_configuration(&format!(
+            r#""in_stream": {}, "in_streams": [{}]"#,
+            new_source("first"),
+            new_source("second")
+        ));
+
+        assert!(GatewayClientService::try_from(&configuration).is_err());
+    }
+
+    fn new_source(name: &str) -> String {
+        format!(
+            r#"{{
+                "name": "{}",
+                "url": "sub+bind:ipc:///tmp/test-{}-{}",
+                "receive_timeout": {{"secs": 1, "nanos": 0}},
+                "receive_hwm": 10,
+                "topic_prefix_spec": {{"none": null}},
+                "source_cache_size": 10,
+                "inflight_ops": 10
+            }}"#,
+            name,
+            name,
+            rand::random::<u64>()
+        )
+    }
+
+    /// Returns the first bytes of data of messages of the topic received by the server.
+    fn new_frame() -> VideoFrameProxy {
+        VideoFrameProxy::new(
+            "source",
+            "30/1",
+            1280,
+            720,
+            VideoFrameContent::None,
+            VideoFrameTranscodingMethod::Copy,
+            &None,
+            None,
+            (1, 1000000),
+            0,
+            None,
+            None,
+        )
+    }
+
+    async fn received_topics(server: &MockServer) -> Vec<String> {
+        server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|e| Media::from_proto(&e.body).unwrap())
+            .map(|e| String::from_utf8(e.topic).unwrap())
+            .collect()
+    }
+
+    async fn received_data(server: &MockServer, topic: &str) -> Vec<u8> {
+        server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|e| Media::from_proto(&e.body).unwrap())
+            .filter(|e| e.topic == topic.as_bytes())
+            .map(|e| e.data[0][0])
+            .collect()
+    }
+
+    fn new_configuration(in_streams: &str) -> GatewayClientConfiguration {
+        new_configuration_with_url("http://127.0.0.1:8080", in_streams)
+    }
+
+    fn new_configuration_with_url(url: &str, in_streams: &str) -> GatewayClientConfiguration {
+        let path = std::env::temp_dir().join(format!("client-{}.json", rand::random::<u64>()));
+        fs::write(
+            &path,
+            format!(
+                r#"{{"ip": "127.0.0.1", "port": 8081, "url": "{}", {}}}"#,
+                url, in_streams
+            ),
+        )
+        .unwrap();
+        let configuration = GatewayClientConfiguration::new(path.to_str().unwrap()).unwrap();
+        fs::remove_file(path).unwrap();
+        configuration
+    }
+}
@@ -0,0 +1,251 @@
+//! Bandwidth shaping of forwarded messages.
+//!
+//! The module provides [`Shaper`].
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+use savant_core::message::Message;
+use serde::Serialize;
+use tokio_timerfd::sleep;
+
+use media_gateway_common::model::Media;
+
+use crate::configuration::{ShapingConfiguration, ShapingPolicy};
+use crate::sampling::Sampler;
+
+/// A period to measure the current rate
+const RATE_PERIOD: Duration = Duration::from_secs(1);
+
+/// Limits the number of bytes of encoded messages sent per second by a token bucket. Tokens
+/// (bytes) are added at the specified rate up to the burst size. A message is sent if the bucket
+/// is not empty and the size of the message is subtracted from it, so a message larger than the
+/// burst size is sent too. If the bucket is empty the message waits for tokens or video frames
+/// are dropped according to the sampling policies.
+pub struct Shaper {
+    /// The number of bytes per second
+    rate: f64,
+    /// The maximum number of tokens in the bucket
+    burst: f64,
+    /// A sampler of video frames if the bucket is empty. If not specified messages wait.
+    sampler: Option<Sampler>,
+    state: Mutex<ShaperState>,
+}
+
+struct ShaperState {
+    tokens: f64,
+    updated: Instant,
+    /// The start of the current period to measure the rate
+    period_started: Instant,
+    /// The number of bytes sent in the current period
+    period_bytes: u64,
+    /// The number of bytes sent in the previous period
+    previous_period_bytes: u64,
+    throttled: Duration,
+    dropped: u64,
+}
+
+/// The current state of [`Shaper`].
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ShaperStats {
+    /// The number of bytes sent in the last second
+    pub rate: u64,
+    /// The total time messages waited for tokens
+    pub throttled: Duration,
+    /// The number of dropped video frames
+    pub dropped: u64,
+}
+
+impl Shaper {
+    /// Constructs a new instance.
+    ///
+    /// # Arguments
+    /// * `rate` - the number of bytes per second
+    /// * `burst` - the maximum number of bytes sent at once
+    /// * `sampler` - a sampler of video frames if the limit is reached. If not specified messages
+    ///   wait.
+    pub fn new(rate: u64, burst: u64, sampler: Option<Sampler>) -> Self {
+        let now = Instant::now();
+        Self {
+            rate: rate as f64,
+            burst: burst as f64,
+            sampler,
+            state: Mutex::new(ShaperState {
+                tokens: burst as f64,
+                updated: now,
+                period_started: now,
+                period_bytes: 0,
+                previous_period_bytes: 0,
+                throttled: Duration::ZERO,
+                dropped: 0,
+            }),
+        }
+    }
+
+    /// Returns `true` if the limit is reached and the message is a video frame rejected by the
+    /// sampler, so it should be dropped.
+    pub fn drops(&self, media: &Media) -> bool {
+        let sampler = match &self.sampler {
+            Some(sampler) => sampler,
+            None => return false,
+        };
+        let mut state = self.state.lock().unwrap();
+        self.refill(&mut state);
+        if state.tokens > 0.0 {
+            return false;
+        }
+        let accepted = match media.message.as_ref().map(Message::try_from) {
+            Some(Ok(message)) => sampler.accept(&message),
+            _ => true,
+        };
+        if !accepted {
+            state.dropped += 1;
+        }
+        !accepted
+    }
+
+    /// Waits until the message of the size can be sent.
+    pub async fn acquire(&self, size: usize) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                self.refill(&mut state);
+                if state.tokens > 0.0 {
+                    state.tokens -= size as f64;
+                    state.period_bytes += size as u64;
+                    return;
+                }
+                let wait = Duration::from_secs_f64(-state.tokens / self.rate)
+                    .max(Duration::from_millis(1));
+                state.throttled += wait;
+                wait
+            };
+            log::debug!("Message is throttled for {} nanoseconds", wait.as_nanos());
+            sleep(wait)
+                .await
+                .expect("Error while waiting for bandwidth");
+        }
+    }
+
+    /// Returns the current state.
+    pub fn stats(&self) -> ShaperStats {
+        let mut state = self.state.lock().unwrap();
+        self.refill(&mut state);
+        ShaperStats {
+            rate: state.previous_period_bytes,
+            throttled: state.throttled,
+            dropped: state.dropped,
+        }
+    }
+
+    fn refill(&self, state: &mut ShaperState) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.updated).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.rate).min(self.burst);
+        state.updated = now;
+        let period_elapsed = now.duration_since(state.period_started);
+        if period_elapsed >= RATE_PERIOD {
+            state.previous_period_bytes = if period_elapsed < RATE_PERIOD * 2 {
+                state.period_bytes
+            } else {
+                0
+            };
+            state.period_bytes = 0;
+            state.period_started = now;
+        }
+    }
+}
+
+impl TryFrom<&ShapingConfiguration> for Shaper {
+    type Error = anyhow::Error;
+
+    fn try_from(configuration: &ShapingConfiguration) -> Result<Self, Self::Error> {
+        if configuration.rate == 0 {
+            bail!("Invalid shaping rate: 0");
+        }
+        let burst = configuration.burst.unwrap_or(configuration.rate);
+        if burst == 0 {
+            bail!("Invalid shaping burst: 0");
+        }
+        let sampler = match &configuration.policy {
+            Some(ShapingPolicy::Sample(sampling_configuration)) => {
+                Some(Sampler::try_from(sampling_configuration)?)
+            }
+            Some(ShapingPolicy::Wait) | None => None,
+        };
+        Ok(Shaper::new(configuration.rate, burst, sampler))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use savant_core::message::Message;
+    use savant_core::primitives::frame::{
+        VideoFrameContent, VideoFrameProxy, VideoFrameTranscodingMethod,
+    };
+
+    use media_gateway_common::model::Media;
+
+    use crate::configuration::{SamplingConfiguration, SamplingPolicy};
+    use crate::sampling::Sampler;
+    use crate::shaper::Shaper;
+
+    #[tokio::test]
+    async fn acquire() {
+        let shaper = Shaper::new(1000, 100, None);
+        let started = Instant::now();
+
+        // the burst is spent, the next message waits until 200 bytes of debt are paid off
+        shaper.acquire(300).await;
+        shaper.acquire(100).await;
+
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(200));
+        assert!(elapsed < Duration::from_millis(400));
+        assert!(shaper.stats().throttled >= Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn drops() {
+        let sampler = Sampler::try_from(&SamplingConfiguration {
+            default: Some(SamplingPolicy {
+                keyframes_only: Some(true),
+                every_nth: None,
+                max_fps: None,
+            }),
+            sources: None,
+        })
+        .unwrap();
+        let shaper = Shaper::new(1000, 100, Some(sampler));
+        let media = Media {
+            message: Some(savant_protobuf::generated::Message::from(&new_frame())),
+            topic: "topic".as_bytes().to_vec(),
+            data: vec![],
+        };
+
+        assert!(!shaper.drops(&media));
+        shaper.acquire(1000).await;
+        assert!(shaper.drops(&media));
+        assert_eq!(shaper.stats().dropped, 1);
+    }
+
+    fn new_frame() -> Message {
+        let frame = VideoFrameProxy::new(
+            "source",
+            "30/1",
+            1280,
+            720,
+            VideoFrameContent::None,
+            VideoFrameTranscodingMethod::Copy,
+            &None,
+            Some(false),
+            (1, 1000000),
+            0,
+            None,
+            None,
+        );
+        Message::video_frame(&frame)
+    }
+}
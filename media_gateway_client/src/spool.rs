@@ -0,0 +1,844 @@
+//! A disk-backed store-and-forward spool.
+//!
+//! The module provides [`Spool`], an append-only log split into segments. Messages are persisted
+//! on receipt and deleted only after they are acknowledged. Unacknowledged messages are replayed
+//! after a restart.
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail};
+use tokio::sync::Notify;
+use tokio_timerfd::sleep;
+
+use media_gateway_common::model::Media;
+
+use crate::configuration::{SpoolConfiguration, SpoolEviction};
+use crate::forwarder::MessageContext;
+
+const SEGMENT_EXTENSION: &str = "seg";
+const ACK_EXTENSION: &str = "ack";
+const RECORD_HEADER_SIZE: u64 = 4;
+
+/// An identifier of a message in [`Spool`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SpoolId {
+    segment: u64,
+    offset: u64,
+}
+
+/// A message read from [`Spool`].
+#[derive(Debug)]
+pub struct SpoolRecord {
+    pub id: SpoolId,
+    pub media: Media,
+    /// The context passed to [`Spool::append`] if the message was appended by the current
+    /// process or a new one otherwise. The spool id is set to [`SpoolRecord::id`].
+    pub context: MessageContext,
+}
+
+/// A disk-backed queue of messages.
+///
+/// Each segment is stored in `<number>.seg` file as a sequence of records (a length as 4 bytes in
+/// little endian followed by [`Media`] in protobuf). Offsets of acknowledged records are appended
+/// to `<number>.ack` file. A segment is deleted when it is full and all its records are
+/// acknowledged. A segment is synced to disk when it is full, when the spool is closed and at
+/// the sync interval if it is specified.
+///
+/// Async methods perform file I/O on blocking threads.
+pub struct Spool {
+    directory: PathBuf,
+    segment_size: u64,
+    max_size: u64,
+    eviction: SpoolEviction,
+    sync_interval: Option<Duration>,
+    state: Mutex<SpoolState>,
+    notify: Notify,
+}
+
+struct SpoolState {
+    segments: BTreeMap<u64, Segment>,
+    writer: Option<(u64, File)>,
+    reader: Option<(u64, File)>,
+    next_segment: u64,
+    read_position: SpoolId,
+    /// Messages returned by [`Spool::defer`] in order to be read again
+    deferred: VecDeque<SpoolId>,
+    size: u64,
+    /// The time when the segment being written was synced to disk last time
+    synced_at: Instant,
+    closed: bool,
+}
+
+#[derive(Default)]
+struct Segment {
+    size: u64,
+    records: u64,
+    acknowledged: u64,
+    sealed: bool,
+    /// Offsets acknowledged before the restart
+    recovered_acks: HashSet<u64>,
+    /// Contexts of messages appended by the current process
+    contexts: HashMap<u64, MessageContext>,
+    ack_file: Option<File>,
+}
+
+enum ReadResult {
+    Record(SpoolRecord),
+    Skip,
+    Wait,
+    Closed,
+}
+
+impl Spool {
+    /// Opens the spool in the directory. The directory is created if it does not exist.
+    /// Unacknowledged messages from existing segments are read first.
+    ///
+    /// # Arguments
+    /// * `directory` - a directory to store segments
+    /// * `segment_size` - the size of a segment in bytes after which a new segment is started
+    /// * `max_size` - the maximum total size of segments in bytes
+    /// * `eviction` - what to do if `max_size` is reached
+    /// * `sync_interval` - the maximum interval between syncs of written messages to disk, if not
+    ///   specified messages are synced only when a segment is full or the spool is closed
+    pub fn open(
+        directory: &Path,
+        segment_size: u64,
+        max_size: u64,
+        eviction: SpoolEviction,
+        sync_interval: Option<Duration>,
+    ) -> anyhow::Result<Self> {
+        fs::create_dir_all(directory)?;
+        let mut segments = BTreeMap::new();
+        let mut size = 0;
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(SEGMENT_EXTENSION) {
+                continue;
+            }
+            let number = match path
+                .file_stem()
+                .and_then(|e| e.to_str())
+                .and_then(|e| e.parse::<u64>().ok())
+            {
+                Some(number) => number,
+                None => continue,
+            };
+            let segment = recover_segment(directory, number)?;
+            size += segment.size;
+            segments.insert(number, segment);
+        }
+        let next_segment = segments.keys().next_back().map_or(0, |e| e + 1);
+        let read_position = SpoolId {
+            segment: segments.keys().next().copied().unwrap_or(0),
+            offset: 0,
+        };
+        let unacknowledged = segments
+            .values()
+            .map(|e| e.records - e.acknowledged)
+            .sum::<u64>();
+        log::info!(
+            "Spool is opened with {} segments ({} bytes, {} unacknowledged messages)",
+            segments.len(),
+            size,
+            unacknowledged
+        );
+        let spool = Self {
+            directory: directory.to_path_buf(),
+            segment_size,
+            max_size,
+            eviction,
+            sync_interval,
+            state: Mutex::new(SpoolState {
+                segments,
+                writer: None,
+                reader: None,
+                next_segment,
+                read_position,
+                deferred: VecDeque::new(),
+                size,
+                synced_at: Instant::now(),
+                closed: false,
+            }),
+            notify: Notify::new(),
+        };
+        {
+            let mut state = spool.state.lock().unwrap();
+            let completed = state
+                .segments
+                .iter()
+                .filter(|(_, e)| e.acknowledged >= e.records)
+                .map(|(number, _)| *number)
+                .collect::<Vec<u64>>();
+            for number in completed {
+                spool.delete_segment(&mut state, number);
+            }
+        }
+        Ok(spool)
+    }
+
+    /// Persists the message. If the spool is full the message or the oldest segment is evicted
+    /// according to the eviction policy. Returns [`None`] if the message is evicted. An error is
+    /// returned if the message is not persisted (e.g. due to an I/O error), so the attempt may be
+    /// repeated.
+    pub async fn append(
+        self: &Arc<Self>,
+        media: &Media,
+        context: MessageContext,
+    ) -> anyhow::Result<Option<SpoolId>> {
+        let data = media.to_proto()?;
+        let spool = self.clone();
+        tokio::task::spawn_blocking(move || spool.write(data, context))
+            .await
+            .expect("Error in spool writing task")
+    }
+
+    fn write(&self, data: Vec<u8>, context: MessageContext) -> anyhow::Result<Option<SpoolId>> {
+        let record_size = RECORD_HEADER_SIZE + data.len() as u64;
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            bail!("Spool is closed");
+        }
+
+        while state.size + record_size > self.max_size {
+            match self.eviction {
+                SpoolEviction::DropNewest => {
+                    log::warn!("Spool is full, dropping the new message");
+                    return Ok(None);
+                }
+                SpoolEviction::DropOldest => {
+                    let oldest = match state.segments.keys().next() {
+                        Some(oldest) => *oldest,
+                        None => {
+                            log::warn!("Message is larger than the spool max_size, dropping it");
+                            return Ok(None);
+                        }
+                    };
+                    let segment = &state.segments[&oldest];
+                    log::warn!(
+                        "Spool is full, evicting segment {} with {} unacknowledged messages",
+                        oldest,
+                        segment.records - segment.acknowledged
+                    );
+                    self.delete_segment(&mut state, oldest);
+                }
+            }
+        }
+
+        let number = match state.writer.as_ref().map(|(e, _)| *e) {
+            Some(number) => number,
+            None => {
+                let number = state.next_segment;
+                state.next_segment += 1;
+                let file = OpenOptions::new()
+                    .create_new(true)
+                    .append(true)
+                    .open(self.segment_path(number, SEGMENT_EXTENSION))?;
+                state.segments.insert(number, Segment::default());
+                state.writer = Some((number, file));
+                state.synced_at = Instant::now();
+                number
+            }
+        };
+        let segment_size = state.segments[&number].size;
+        let sealed = segment_size + record_size >= self.segment_size;
+        let sync = sealed
+            || self
+                .sync_interval
+                .is_some_and(|e| state.synced_at.elapsed() >= e);
+        let (_, file) = state.writer.as_mut().unwrap();
+        let write_result = file
+            .write_all(&(data.len() as u32).to_le_bytes())
+            .and_then(|_| file.write_all(&data))
+            .and_then(|_| if sync { file.sync_data() } else { Ok(()) });
+        if let Err(e) = write_result {
+            // an incomplete or not synced record is removed and new messages are written to a
+            // new segment
+            if let Err(e) = file.set_len(segment_size) {
+                log::warn!("Error while truncating spool segment: {:?}", e);
+            }
+            state.writer = None;
+            state.segments.get_mut(&number).unwrap().sealed = true;
+            return Err(anyhow!("Error while writing to spool").context(e.to_string()));
+        }
+
+        let segment = state.segments.get_mut(&number).unwrap();
+        let id = SpoolId {
+            segment: number,
+            offset: segment.size,
+        };
+        segment.size += record_size;
+        segment.records += 1;
+        segment.contexts.insert(id.offset, context);
+        if sealed {
+            segment.sealed = true;
+            state.writer = None;
+        }
+        if sync {
+            state.synced_at = Instant::now();
+        }
+        state.size += record_size;
+        drop(state);
+
+        self.notify.notify_one();
+        Ok(Some(id))
+    }
+
+    /// Reads the next message waiting for it if necessary. Deferred messages are read first.
+    /// Returns [`None`] if the spool is closed.
+    pub async fn next(self: &Arc<Self>) -> Option<SpoolRecord> {
+        self.read(true, None).await.ok().flatten()
+    }
+
+    /// Reads the next message which has not been read yet skipping deferred messages. Returns
+    /// [`None`] if no message is appended within the timeout and an error if the spool is
+    /// closed.
+    pub async fn next_new(
+        self: &Arc<Self>,
+        timeout: Duration,
+    ) -> anyhow::Result<Option<SpoolRecord>> {
+        self.read(false, Some(timeout)).await
+    }
+
+    /// Returns the message to be read again by [`Spool::next`] before messages which have not
+    /// been read yet. Deferred messages are read in order of deferring.
+    pub fn defer(&self, record: SpoolRecord) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(segment) = state.segments.get_mut(&record.id.segment) {
+            segment.contexts.insert(record.id.offset, record.context);
+            state.deferred.push_back(record.id);
+        }
+    }
+
+    async fn read(
+        self: &Arc<Self>,
+        deferred: bool,
+        timeout: Option<Duration>,
+    ) -> anyhow::Result<Option<SpoolRecord>> {
+        loop {
+            let spool = self.clone();
+            let read_result = tokio::task::spawn_blocking(move || spool.try_next(deferred))
+                .await
+                .expect("Error in spool reading task");
+            match read_result {
+                Ok(ReadResult::Record(record)) => return Ok(Some(record)),
+                Ok(ReadResult::Skip) => continue,
+                Ok(ReadResult::Wait) => match timeout {
+                    Some(timeout) => tokio::select! {
+                        _ = self.notify.notified() => {}
+                        result = sleep(timeout) => {
+                            result.expect("Error while waiting for spool message");
+                            return Ok(None);
+                        }
+                    },
+                    None => self.notify.notified().await,
+                },
+                Ok(ReadResult::Closed) => bail!("Spool is closed"),
+                Err(e) => {
+                    log::warn!("Error while reading spool: {:?}", e);
+                    // the rest of the segment cannot be read, new messages are written to a new one
+                    let mut state = self.state.lock().unwrap();
+                    let number = state.read_position.segment;
+                    if state.writer.as_ref().map(|(e, _)| *e) == Some(number) {
+                        state.writer = None;
+                    }
+                    if let Some(segment) = state.segments.get_mut(&number) {
+                        segment.sealed = true;
+                    }
+                    state.read_position = SpoolId {
+                        segment: number + 1,
+                        offset: 0,
+                    };
+                }
+            }
+        }
+    }
+
+    /// Marks the message as delivered. A segment is deleted when all its messages are
+    /// acknowledged. The method performs file I/O, so it should not be called on async threads.
+    pub fn acknowledge(&self, id: SpoolId) {
+        let mut state = self.state.lock().unwrap();
+        let ack_path = self.segment_path(id.segment, ACK_EXTENSION);
+        let segment = match state.segments.get_mut(&id.segment) {
+            Some(segment) => segment,
+            // the segment is evicted
+            None => return,
+        };
+        if segment.ack_file.is_none() {
+            match OpenOptions::new().create(true).append(true).open(ack_path) {
+                Ok(file) => segment.ack_file = Some(file),
+                Err(e) => log::warn!("Error while opening spool ack file: {:?}", e),
+            }
+        }
+        if let Some(file) = segment.ack_file.as_mut() {
+            if let Err(e) = file.write_all(&id.offset.to_le_bytes()) {
+                log::warn!("Error while acknowledging spool message: {:?}", e);
+            }
+        }
+        segment.acknowledged += 1;
+        if segment.sealed && segment.acknowledged >= segment.records {
+            self.delete_segment(&mut state, id.segment);
+        }
+    }
+
+    /// Stops reading and syncs written messages to disk. Unacknowledged messages are kept to be
+    /// replayed after a restart.
+    pub async fn close(self: &Arc<Self>) {
+        let spool = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut state = spool.state.lock().unwrap();
+            state.closed = true;
+            if let Some((_, file)) = state.writer.as_ref() {
+                if let Err(e) = file.sync_data() {
+                    log::warn!("Error while syncing spool segment: {:?}", e);
+                }
+            }
+        })
+        .await
+        .expect("Error in spool closing task");
+        self.notify.notify_one();
+    }
+
+    fn try_next(&self, deferred: bool) -> anyhow::Result<ReadResult> {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return Ok(ReadResult::Closed);
+        }
+        if deferred {
+            if let Some(id) = state.deferred.pop_front() {
+                // the segment of the deferred message may be evicted
+                if !state.segments.contains_key(&id.segment) {
+                    return Ok(ReadResult::Skip);
+                }
+                let record = self
+                    .read_record(&mut state, id)
+                    .and_then(|data| to_record(&mut state, id, &data));
+                return match record {
+                    Ok(record) => Ok(ReadResult::Record(record)),
+                    Err(e) => {
+                        log::warn!("Error while reading deferred spool message: {:?}", e);
+                        Ok(ReadResult::Skip)
+                    }
+                };
+            }
+        }
+        let position = state.read_position;
+        let (number, segment) = match state.segments.range(position.segment..).next() {
+            Some((number, segment)) => (*number, segment),
+            None => return Ok(ReadResult::Wait),
+        };
+        if number != position.segment {
+            state.read_position = SpoolId {
+                segment: number,
+                offset: 0,
+            };
+            return Ok(ReadResult::Skip);
+        }
+        if position.offset >= segment.size {
+            return if segment.sealed {
+                state.read_position = SpoolId {
+                    segment: number + 1,
+                    offset: 0,
+                };
+                Ok(ReadResult::Skip)
+            } else {
+                Ok(ReadResult::Wait)
+            };
+        }
+        let acknowledged = segment.recovered_acks.contains(&position.offset);
+
+        let data = self.read_record(&mut state, position)?;
+        state.read_position.offset += RECORD_HEADER_SIZE + data.len() as u64;
+
+        if acknowledged {
+            return Ok(ReadResult::Skip);
+        }
+        Ok(ReadResult::Record(to_record(&mut state, position, &data)?))
+    }
+
+    /// Reads data of the record from the segment file.
+    fn read_record(&self, state: &mut SpoolState, id: SpoolId) -> anyhow::Result<Vec<u8>> {
+        if state.reader.as_ref().map(|(e, _)| *e) != Some(id.segment) {
+            let file = File::open(self.segment_path(id.segment, SEGMENT_EXTENSION))?;
+            state.reader = Some((id.segment, file));
+        }
+        let (_, file) = state.reader.as_mut().unwrap();
+        file.seek(SeekFrom::Start(id.offset))?;
+        let mut length = [0u8; RECORD_HEADER_SIZE as usize];
+        file.read_exact(&mut length)?;
+        let mut data = vec![0u8; u32::from_le_bytes(length) as usize];
+        file.read_exact(&mut data)?;
+        Ok(data)
+    }
+
+    fn delete_segment(&self, state: &mut SpoolState, number: u64) {
+        if let Some(segment) = state.segments.remove(&number) {
+            state.size -= segment.size;
+        }
+        if state.writer.as_ref().map(|(e, _)| *e) == Some(number) {
+            state.writer = None;
+        }
+        if state.reader.as_ref().map(|(e, _)| *e) == Some(number) {
+            state.reader = None;
+        }
+        for extension in [SEGMENT_EXTENSION, ACK_EXTENSION] {
+            let path = self.segment_path(number, extension);
+            if let Err(e) = fs::remove_file(&path) {
+                if e.kind() != ErrorKind::NotFound {
+                    log::warn!("Error while deleting spool file {:?}: {:?}", path, e);
+                }
+            }
+        }
+    }
+
+    fn segment_path(&self, number: u64, extension: &str) -> PathBuf {
+        segment_path(&self.directory, number, extension)
+    }
+}
+
+impl TryFrom<&SpoolConfiguration> for Spool {
+    type Error = anyhow::Error;
+
+    fn try_from(configuration: &SpoolConfiguration) -> Result<Self, Self::Error> {
+        if configuration.segment_size == 0 {
+            bail!("Invalid spool segment_size: 0");
+        }
+        if configuration.max_size < configuration.segment_size {
+            bail!("Invalid spool max_size: less than segment_size");
+        }
+        Spool::open(
+            Path::new(&configuration.directory),
+            configuration.segment_size,
+            configuration.max_size,
+            configuration
+                .eviction
+                .clone()
+                .unwrap_or(SpoolEviction::DropOldest),
+            configuration.sync_interval,
+        )
+    }
+}
+
+/// Decodes the record with the context passed to [`Spool::append`] if it is known.
+fn to_record(state: &mut SpoolState, id: SpoolId, data: &[u8]) -> anyhow::Result<SpoolRecord> {
+    let media = Media::from_proto(data)
+        .map_err(|e| anyhow!("Invalid spool record at {:?}", id).context(e.to_string()))?;
+    let mut context = state
+        .segments
+        .get_mut(&id.segment)
+        .and_then(|e| e.contexts.remove(&id.offset))
+        .unwrap_or_default();
+    context.spool_id = Some(id);
+    Ok(SpoolRecord { id, media, context })
+}
+
+fn segment_path(directory: &Path, number: u64, extension: &str) -> PathBuf {
+    directory.join(format!("{:020}.{}", number, extension))
+}
+
+/// Reads a segment written before the restart. An incomplete record at the end (if the process
+/// crashed while writing) is truncated.
+fn recover_segment(directory: &Path, number: u64) -> anyhow::Result<Segment> {
+    let path = segment_path(directory, number, SEGMENT_EXTENSION);
+    let data = fs::read(&path)?;
+    let mut offsets = Vec::new();
+    let mut offset = 0;
+    while offset + RECORD_HEADER_SIZE <= data.len() as u64 {
+        let start = offset as usize;
+        let length = u32::from_le_bytes(data[start..start + 4].try_into().unwrap()) as u64;
+        if offset + RECORD_HEADER_SIZE + length > data.len() as u64 {
+            break;
+        }
+        offsets.push(offset);
+        offset += RECORD_HEADER_SIZE + length;
+    }
+    if offset < data.len() as u64 {
+        log::warn!(
+            "Truncating incomplete spool record in {:?} at {}",
+            path,
+            offset
+        );
+        OpenOptions::new()
+            .write(true)
+            .open(&path)?
+            .set_len(offset)?;
+    }
+
+    let acks = match fs::read(segment_path(directory, number, ACK_EXTENSION)) {
+        Ok(acks) => acks,
+        Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let recovered_acks = acks
+        .chunks_exact(8)
+        .map(|e| u64::from_le_bytes(e.try_into().unwrap()))
+        .filter(|e| offsets.binary_search(e).is_ok())
+        .collect::<HashSet<u64>>();
+
+    Ok(Segment {
+        size: offset,
+        records: offsets.len() as u64,
+        acknowledged: recovered_acks.len() as u64,
+        sealed: true,
+        recovered_acks,
+        contexts: HashMap::new(),
+        ack_file: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use rand::Rng;
+
+    use media_gateway_common::model::Media;
+
+    use crate::configuration::SpoolEviction;
+    use crate::forwarder::MessageContext;
+    use crate::spool::Spool;
+
+    #[tokio::test]
+    async fn append_and_acknowledge() {
+        let directory = new_directory();
+        let spool =
+            Arc::new(Spool::open(&directory, 1, 1024, SpoolEviction::DropOldest, None).unwrap());
+
+        spool
+            .append(&new_media(1), new_context(Some(10)))
+            .await
+            .unwrap();
+        spool
+            .append(&new_media(2), new_context(None))
+            .await
+            .unwrap();
+        let first = spool.next().await.unwrap();
+        let second = spool.next().await.unwrap();
+
+        assert_eq!(first.media.data, vec![vec![1]]);
+        assert_eq!(first.context.statistics_id, Some(10));
+        assert_eq!(first.context.spool_id, Some(first.id));
+        assert_eq!(second.media.data, vec![vec![2]]);
+        assert_eq!(segment_count(&directory), 2);
+
+        spool.acknowledge(second.id);
+        spool.acknowledge(first.id);
+
+        assert_eq!(segment_count(&directory), 0);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn replay_unacknowledged() {
+        let directory = new_directory();
+        {
+            let spool = Arc::new(
+                Spool::open(&directory, 1024, 4096, SpoolEviction::DropOldest, None).unwrap(),
+            );
+            for i in 0..3 {
+                spool
+                    .append(&new_media(i), new_context(Some(i as i64)))
+                    .await
+                    .unwrap();
+            }
+            let _ = spool.next().await.unwrap();
+            let second = spool.next().await.unwrap();
+            spool.acknowledge(second.id);
+        }
+
+        let spool =
+            Arc::new(Spool::open(&directory, 1024, 4096, SpoolEviction::DropOldest, None).unwrap());
+        spool
+            .append(&new_media(3), new_context(None))
+            .await
+            .unwrap();
+
+        let mut data = Vec::new();
+        for _ in 0..3 {
+            let record = spool.next().await.unwrap();
+            assert_eq!(record.context.statistics_id, None);
+            data.push(record.media.data[0][0]);
+        }
+        assert_eq!(data, vec![0, 2, 3]);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn drop_oldest() {
+        let directory = new_directory();
+        let media_size = 4 + new_media(0).to_proto().unwrap().len() as u64;
+        let spool = Arc::new(
+            Spool::open(
+                &directory,
+                1,
+                media_size * 2,
+                SpoolEviction::DropOldest,
+                None,
+            )
+            .unwrap(),
+        );
+
+        for i in 0..3 {
+            spool
+                .append(&new_media(i), new_context(None))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(spool.next().await.unwrap().media.data, vec![vec![1]]);
+        assert_eq!(spool.next().await.unwrap().media.data, vec![vec![2]]);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn drop_newest() {
+        let directory = new_directory();
+        let media_size = 4 + new_media(0).to_proto().unwrap().len() as u64;
+        let spool = Arc::new(
+            Spool::open(
+                &directory,
+                1,
+                media_size * 2,
+                SpoolEviction::DropNewest,
+                None,
+            )
+            .unwrap(),
+        );
+
+        for i in 0..2 {
+            let result = spool.append(&new_media(i), new_context(None)).await;
+            assert!(result.unwrap().is_some());
+        }
+        let result = spool.append(&new_media(2), new_context(None)).await;
+        assert!(result.unwrap().is_none());
+
+        assert_eq!(spool.next().await.unwrap().media.data, vec![vec![0]]);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn defer() {
+        let directory = new_directory();
+        let spool =
+            Arc::new(Spool::open(&directory, 1024, 4096, SpoolEviction::DropOldest, None).unwrap());
+        for i in 0..3 {
+            spool
+                .append(&new_media(i), new_context(Some(i as i64)))
+                .await
+                .unwrap();
+        }
+
+        let first = spool
+            .next_new(Duration::from_millis(10))
+            .await
+            .unwrap()
+            .unwrap();
+        spool.defer(first);
+        let second = spool
+            .next_new(Duration::from_millis(10))
+            .await
+            .unwrap()
+            .unwrap();
+        let deferred = spool.next().await.unwrap();
+        let third = spool.next().await.unwrap();
+
+        assert_eq!(second.media.data, vec![vec![1]]);
+        assert_eq!(deferred.media.data, vec![vec![0]]);
+        assert_eq!(deferred.context.statistics_id, Some(0));
+        assert_eq!(third.media.data, vec![vec![2]]);
+        assert!(spool
+            .next_new(Duration::from_millis(10))
+            .await
+            .unwrap()
+            .is_none());
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn sync_interval() {
+        let directory = new_directory();
+        {
+            let spool = Arc::new(
+                Spool::open(
+                    &directory,
+                    1024,
+                    4096,
+                    SpoolEviction::DropOldest,
+                    Some(Duration::ZERO),
+                )
+                .unwrap(),
+            );
+            for i in 0..2 {
+                spool
+                    .append(&new_media(i), new_context(None))
+                    .await
+                    .unwrap();
+            }
+        }
+
+        let spool =
+            Arc::new(Spool::open(&directory, 1024, 4096, SpoolEviction::DropOldest, None).unwrap());
+
+        assert_eq!(spool.next().await.unwrap().media.data, vec![vec![0]]);
+        assert_eq!(spool.next().await.unwrap().media.data, vec![vec![1]]);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn close() {
+        let directory = new_directory();
+        let spool =
+            Arc::new(Spool::open(&directory, 1024, 4096, SpoolEviction::DropOldest, None).unwrap());
+
+        spool.close().await;
+
+        assert!(spool.next().await.is_none());
+        assert!(spool
+            .append(&new_media(0), new_context(None))
+            .await
+            .is_err());
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    fn new_directory() -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "media_gateway_spool_{}",
+            rand::thread_rng().gen::<u64>()
+        ))
+    }
+
+    fn segment_count(directory: &Path) -> usize {
+        fs::read_dir(directory)
+            .unwrap()
+            .filter(|e| {
+                e.as_ref()
+                    .unwrap()
+                    .path()
+                    .extension()
+                    .is_some_and(|e| e == "seg")
+            })
+            .count()
+    }
+
+    fn new_context(statistics_id: Option<i64>) -> MessageContext {
+        MessageContext {
+            statistics_id,
+            ..Default::default()
+        }
+    }
+
+    fn new_media(value: u8) -> Media {
+        Media {
+            message: None,
+            topic: "topic".as_bytes().to_vec(),
+            data: vec![vec![value]],
+        }
+    }
+}
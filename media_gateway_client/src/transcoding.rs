@@ -0,0 +1,242 @@
+//! Downscaling and re-encoding of video frames before they are forwarded.
+//!
+//! The module provides [`Transcoder`].
+use std::collections::HashMap;
+
+use anyhow::bail;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::ImageFormat;
+use savant_core::primitives::frame::{VideoFrameContent, VideoFrameProxy};
+use savant_core::primitives::object::VideoObjectBBoxTransformation;
+
+use crate::configuration::{TranscodingConfiguration, TranscodingPolicy};
+
+const DEFAULT_QUALITY: u8 = 75;
+const JPEG_CODEC: &str = "jpeg";
+
+/// Downscales and re-encodes JPEG or PNG images stored in video frames to JPEG according to
+/// the transcoding policy of their sources. Boxes of objects of a downscaled frame are scaled
+/// to the new resolution. Frames with other content are not changed.
+pub struct Transcoder {
+    default: Option<TranscodingPolicy>,
+    sources: HashMap<String, TranscodingPolicy>,
+}
+
+impl Transcoder {
+    /// Transcodes the content of the frame in place if required by the policy of its source.
+    /// Returns `true` if the frame is changed.
+    pub fn transcode(&self, frame: &mut VideoFrameProxy) -> anyhow::Result<bool> {
+        let source_id = frame.get_source_id();
+        let policy = match self.sources.get(&source_id).or(self.default.as_ref()) {
+            Some(policy) => policy,
+            None => return Ok(false),
+        };
+        let content = frame.get_content();
+        let data = match content.as_ref() {
+            VideoFrameContent::Internal(data) => data,
+            _ => return Ok(false),
+        };
+        let format = match image::guess_format(data) {
+            Ok(format @ (ImageFormat::Jpeg | ImageFormat::Png)) => format,
+            _ => return Ok(false),
+        };
+        let image = image::load_from_memory_with_format(data, format)?;
+        let max_width = policy.max_width.unwrap_or(u32::MAX);
+        let max_height = policy.max_height.unwrap_or(u32::MAX);
+        let downscale = image.width() > max_width || image.height() > max_height;
+        // a JPEG image which fits the maximum resolution is re-encoded only to change quality
+        if !downscale && format == ImageFormat::Jpeg && policy.quality.is_none() {
+            return Ok(false);
+        }
+        let image = if downscale {
+            // the aspect ratio is preserved
+            image.resize(max_width, max_height, FilterType::Triangle)
+        } else {
+            image
+        };
+        let mut encoded = Vec::new();
+        JpegEncoder::new_with_quality(&mut encoded, policy.quality.unwrap_or(DEFAULT_QUALITY))
+            .encode_image(&image.to_rgb8())?;
+        if downscale {
+            let (width, height) = (frame.get_width(), frame.get_height());
+            if width > 0 && height > 0 {
+                frame.transform_geometry(&vec![VideoObjectBBoxTransformation::Scale(
+                    image.width() as f32 / width as f32,
+                    image.height() as f32 / height as f32,
+                )]);
+            }
+        }
+        frame.set_width(image.width() as i64)?;
+        frame.set_height(image.height() as i64)?;
+        frame.set_codec(Some(JPEG_CODEC.to_string()));
+        frame.set_content(VideoFrameContent::Internal(encoded));
+        Ok(true)
+    }
+}
+
+impl TryFrom<&TranscodingConfiguration> for Transcoder {
+    type Error = anyhow::Error;
+
+    fn try_from(configuration: &TranscodingConfiguration) -> Result<Self, Self::Error> {
+        let sources = configuration.sources.clone().unwrap_or_default();
+        for policy in configuration.default.iter().chain(sources.values()) {
+            if policy.max_width == Some(0) {
+                bail!("Invalid transcoding max_width: 0");
+            }
+            if policy.max_height == Some(0) {
+                bail!("Invalid transcoding max_height: 0");
+            }
+            if let Some(quality) = policy.quality {
+                if !(1..=100).contains(&quality) {
+                    bail!("Invalid transcoding quality: {}", quality);
+                }
+            }
+        }
+        Ok(Self {
+            default: configuration.default.clone(),
+            sources,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{ImageFormat, RgbImage};
+    use savant_core::primitives::frame::{
+        VideoFrameContent, VideoFrameProxy, VideoFrameTranscodingMethod,
+    };
+    use savant_core::primitives::RBBox;
+
+    use crate::configuration::{TranscodingConfiguration, TranscodingPolicy};
+    use crate::transcoding::Transcoder;
+
+    #[test]
+    fn downscale_png() {
+        let transcoder = new_transcoder(TranscodingPolicy {
+            max_width: Some(32),
+            max_height: Some(32),
+            quality: Some(50),
+        });
+        let mut frame = new_frame(VideoFrameContent::Internal(new_png(64, 32)), 64, 32);
+
+        assert!(transcoder.transcode(&mut frame).unwrap());
+
+        assert_eq!(frame.get_width(), 32);
+        assert_eq!(frame.get_height(), 16);
+        match frame.get_content().as_ref() {
+            VideoFrameContent::Internal(data) => {
+                assert_eq!(image::guess_format(data).unwrap(), ImageFormat::Jpeg)
+            }
+            _ => panic!("Unexpected content"),
+        }
+    }
+
+    #[test]
+    fn downscale_objects() {
+        let transcoder = new_transcoder(TranscodingPolicy {
+            max_width: Some(32),
+            max_height: Some(32),
+            quality: None,
+        });
+        let mut frame = new_frame(VideoFrameContent::Internal(new_png(64, 32)), 64, 32);
+        frame
+            .create_object(
+                "detector",
+                "person",
+                None,
+                RBBox::new(32.0, 16.0, 16.0, 8.0, None),
+                None,
+                None,
+                None,
+                vec![],
+            )
+            .unwrap();
+
+        assert!(transcoder.transcode(&mut frame).unwrap());
+
+        let detection_box = frame.get_all_objects()[0].get_detection_box();
+        assert_eq!(detection_box.get_xc(), 16.0);
+        assert_eq!(detection_box.get_yc(), 8.0);
+        assert_eq!(detection_box.get_width(), 8.0);
+        assert_eq!(detection_box.get_height(), 4.0);
+    }
+
+    #[test]
+    fn keep_small_jpeg() {
+        let transcoder = new_transcoder(TranscodingPolicy {
+            max_width: Some(32),
+            max_height: Some(32),
+            quality: None,
+        });
+        let mut encoded = Cursor::new(Vec::new());
+        RgbImage::new(16, 16)
+            .write_to(&mut encoded, ImageFormat::Jpeg)
+            .unwrap();
+        let mut frame = new_frame(VideoFrameContent::Internal(encoded.into_inner()), 16, 16);
+
+        assert!(!transcoder.transcode(&mut frame).unwrap());
+    }
+
+    #[test]
+    fn keep_other_content() {
+        let transcoder = new_transcoder(TranscodingPolicy {
+            max_width: Some(32),
+            max_height: None,
+            quality: None,
+        });
+        let mut frame = new_frame(VideoFrameContent::None, 64, 32);
+
+        assert!(!transcoder.transcode(&mut frame).unwrap());
+        assert_eq!(frame.get_width(), 64);
+    }
+
+    #[test]
+    fn invalid_quality() {
+        let result = Transcoder::try_from(&TranscodingConfiguration {
+            default: Some(TranscodingPolicy {
+                max_width: None,
+                max_height: None,
+                quality: Some(101),
+            }),
+            sources: None,
+        });
+
+        assert!(result.is_err());
+    }
+
+    fn new_transcoder(policy: TranscodingPolicy) -> Transcoder {
+        Transcoder::try_from(&TranscodingConfiguration {
+            default: Some(policy),
+            sources: None,
+        })
+        .unwrap()
+    }
+
+    fn new_png(width: u32, height: u32) -> Vec<u8> {
+        let mut encoded = Cursor::new(Vec::new());
+        RgbImage::new(width, height)
+            .write_to(&mut encoded, ImageFormat::Png)
+            .unwrap();
+        encoded.into_inner()
+    }
+
+    fn new_frame(content: VideoFrameContent, width: i64, height: i64) -> VideoFrameProxy {
+        VideoFrameProxy::new(
+            "source",
+            "30/1",
+            width,
+            height,
+            content,
+            VideoFrameTranscodingMethod::Copy,
+            &Some("png"),
+            None,
+            (1, 1000000),
+            0,
+            None,
+            None,
+        )
+    }
+}
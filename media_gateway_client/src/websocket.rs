@@ -0,0 +1,273 @@
+//! A WebSocket transport for the media gateway client.
+//!
+//! The module provides [`WebSocketClient`] that streams messages over a single long-lived
+//! connection and matches responses with requests by ids.
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use futures_util::stream::{SplitSink, SplitStream};
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio_tungstenite::tungstenite::handshake::client::Request;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{
+    connect_async_tls_with_config, Connector, MaybeTlsStream, WebSocketStream,
+};
+
+use media_gateway_common::model::{Media, MediaRequest, MediaResponse};
+
+use crate::client::ForwardResult;
+use crate::pending::PendingRequests;
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// A client that streams messages to the media gateway server over WebSocket.
+///
+/// The connection is established on the first message. If the connection is lost all pending
+/// messages fail and the connection is reestablished on the next message. If the connection
+/// cannot be established the message fails, so attempts to connect are retried by the forwarder.
+/// If the response is not received within the response timeout the message fails with
+/// [`ForwardResult::AckTimeout`].
+pub struct WebSocketClient {
+    request: Request,
+    connector: Option<Connector>,
+    response_timeout: Duration,
+    connection: Mutex<Option<Arc<Connection>>>,
+}
+
+struct Connection {
+    sink: Mutex<SplitSink<WsStream, Message>>,
+    pending: Arc<PendingRequests>,
+}
+
+impl WebSocketClient {
+    /// Constructs a new instance of the client.
+    ///
+    /// # Arguments
+    /// * `request` - a request to open the connection (an endpoint and headers)
+    /// * `connector` - a TLS connector
+    /// * `response_timeout` - the maximum time to wait for the response to a message
+    pub fn new(request: Request, connector: Option<Connector>, response_timeout: Duration) -> Self {
+        Self {
+            request,
+            connector,
+            response_timeout,
+            connection: Mutex::new(None),
+        }
+    }
+
+    /// Sends the message to the media gateway server and waits for the response.
+    pub async fn forward_message(&self, media: &Media) -> anyhow::Result<ForwardResult> {
+        let connection = self.connection().await?;
+        let (id, receiver) = connection.pending.register()?;
+
+        let data = MediaRequest::to_proto_from_parts(id, media);
+        let send_result = connection
+            .sink
+            .lock()
+            .await
+            .send(Message::Binary(data))
+            .await;
+        if let Err(e) = send_result {
+            self.close(&connection).await;
+            return Err(anyhow!("Error while sending a message").context(e.to_string()));
+        }
+
+        connection
+            .pending
+            .wait(id, receiver, self.response_timeout)
+            .await
+    }
+
+    /// Closes the connection if it is open.
+    pub async fn shutdown(&self) {
+        if let Some(connection) = self.connection.lock().await.take() {
+            self.close(&connection).await;
+        }
+    }
+
+    /// Returns the open connection or opens a new one.
+    async fn connection(&self) -> anyhow::Result<Arc<Connection>> {
+        let mut connection = self.connection.lock().await;
+        if let Some(current) = connection.as_ref() {
+            if !current.pending.is_closed() {
+                return Ok(current.clone());
+            }
+        }
+        let new_connection = self
+            .connect()
+            .await
+            .map_err(|e| e.context("Error while connecting"))?;
+        log::info!("WebSocket connection is established");
+        *connection = Some(new_connection.clone());
+        Ok(new_connection)
+    }
+
+    async fn connect(&self) -> anyhow::Result<Arc<Connection>> {
+        let (stream, _) =
+            connect_async_tls_with_config(self.request.clone(), None, true, self.connector.clone())
+                .await?;
+        let (sink, stream) = stream.split();
+        let pending = Arc::new(PendingRequests::new());
+        tokio::spawn(receive(stream, pending.clone()));
+        Ok(Arc::new(Connection {
+            sink: Mutex::new(sink),
+            pending,
+        }))
+    }
+
+    async fn close(&self, connection: &Connection) {
+        connection.pending.close();
+        if let Err(e) = connection.sink.lock().await.close().await {
+            log::debug!("Error while closing WebSocket connection: {:?}", e);
+        }
+    }
+}
+
+async fn receive(mut stream: SplitStream<WsStream>, pending: Arc<PendingRequests>) {
+    while let Some(message) = stream.next().await {
+        let data = match message {
+            Ok(Message::Binary(data)) => data,
+            Ok(Message::Close(frame)) => {
+                log::info!("WebSocket connection is closed by the server: {:?}", frame);
+                break;
+            }
+            Ok(_) => continue,
+            Err(e) => {
+                log::warn!("Error while receiving a response: {:?}", e);
+                break;
+            }
+        };
+        let response = match MediaResponse::from_proto(&data) {
+            Ok(response) => response,
+            Err(e) => {
+                log::warn!("Invalid response: {:?}", e);
+                break;
+            }
+        };
+        pending.complete(&response);
+    }
+    pending.close();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures_util::{SinkExt, StreamExt};
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+    use tokio_tungstenite::tungstenite::Message;
+
+    use media_gateway_common::model::{Media, MediaRequest, MediaResponse, MediaStatus};
+
+    use crate::client::ForwardResult;
+    use crate::websocket::WebSocketClient;
+
+    #[tokio::test]
+    async fn forward_message() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let statuses = vec![MediaStatus::Success, MediaStatus::AckTimeout];
+        let server_statuses = statuses.clone();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = tokio_tungstenite::accept_async(stream).await.unwrap();
+            for status in server_statuses {
+                let request = match stream.next().await {
+                    Some(Ok(Message::Binary(data))) => MediaRequest::from_proto(&data).unwrap(),
+                    message => panic!("Unexpected message: {:?}", message),
+                };
+                assert_eq!(request.media.unwrap().topic, "topic".as_bytes());
+                let response = MediaResponse {
+                    id: request.id,
+                    status: status as i32,
+                };
+                stream
+                    .send(Message::Binary(response.to_proto().unwrap()))
+                    .await
+                    .unwrap();
+            }
+        });
+        let client = WebSocketClient::new(
+            format!("ws://{}/ws", address)
+                .into_client_request()
+                .unwrap(),
+            None,
+            Duration::from_secs(1),
+        );
+        let media = Media {
+            message: None,
+            topic: "topic".as_bytes().to_vec(),
+            data: vec![vec![1]],
+        };
+
+        let first = client.forward_message(&media).await;
+        let second = client.forward_message(&media).await;
+
+        assert!(matches!(first, Ok(ForwardResult::Success)));
+        assert!(matches!(second, Ok(ForwardResult::AckTimeout)));
+        server.await.unwrap();
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn response_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = tokio_tungstenite::accept_async(stream).await.unwrap();
+            // the request is not answered until the connection is closed by the client
+            while let Some(Ok(message)) = stream.next().await {
+                if message.is_close() {
+                    break;
+                }
+            }
+        });
+        let client = WebSocketClient::new(
+            format!("ws://{}/ws", address)
+                .into_client_request()
+                .unwrap(),
+            None,
+            Duration::from_millis(100),
+        );
+        let media = Media {
+            message: None,
+            topic: "topic".as_bytes().to_vec(),
+            data: vec![vec![1]],
+        };
+
+        let result = client.forward_message(&media).await;
+
+        assert!(matches!(result, Ok(ForwardResult::AckTimeout)));
+        client.shutdown().await;
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn connection_error() {
+        // the port is free after the listener is dropped
+        let address = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let client = WebSocketClient::new(
+            format!("ws://{}/ws", address)
+                .into_client_request()
+                .unwrap(),
+            None,
+            Duration::from_secs(1),
+        );
+        let media = Media {
+            message: None,
+            topic: "topic".as_bytes().to_vec(),
+            data: vec![vec![1]],
+        };
+
+        assert!(client.forward_message(&media).await.is_err());
+    }
+}
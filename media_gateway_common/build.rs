@@ -0,0 +1,19 @@
+fn main() {
+    let gateway_service = tonic_build::manual::Service::builder()
+        .name("Gateway")
+        .package("media_gateway")
+        .method(
+            tonic_build::manual::Method::builder()
+                .name("forward")
+                .route_name("Forward")
+                .input_type("crate::model::MediaRequest")
+                .output_type("crate::model::MediaResponse")
+                .codec_path("tonic::codec::ProstCodec")
+                .client_streaming()
+                .server_streaming()
+                .build(),
+        )
+        .build();
+
+    tonic_build::manual::Builder::new().compile(&[gateway_service]);
+}
@@ -1,9 +1,10 @@
 //! Models for media gateway client and server configurations.
 //!
-//! The module provides [`Credentials`].
+//! The module provides [`Credentials`] and [`SinkConfiguration`].
 use core::fmt;
 use std::time::Duration;
 
+use savant_core::transport::zeromq::{SyncWriter, WriterConfigBuilder};
 use serde::{Deserialize, Serialize};
 
 /// Credentials for basic authentication.
@@ -64,3 +65,99 @@ pub struct StatisticsConfiguration {
     /// Statistics based on timestamp period
     pub timestamp_period: Option<Duration>,
 }
+
+impl TryFrom<&SinkConfiguration> for SyncWriter {
+    type Error = anyhow::Error;
+
+    fn try_from(configuration: &SinkConfiguration) -> Result<Self, Self::Error> {
+        let mut builder = WriterConfigBuilder::default()
+            .url(&configuration.url)?
+            .with_receive_timeout(configuration.receive_timeout.as_millis() as i32)?
+            .with_send_timeout(configuration.send_timeout.as_millis() as i32)?
+            .with_receive_retries(configuration.receive_retries as i32)?
+            .with_send_retries(configuration.send_retries as i32)?
+            .with_receive_hwm(configuration.receive_hwm as i32)?
+            .with_send_hwm(configuration.send_hwm as i32)?;
+
+        builder = if configuration.fix_ipc_permissions.is_some() {
+            builder.with_fix_ipc_permissions(configuration.fix_ipc_permissions)?
+        } else {
+            builder
+        };
+
+        let conf = builder.build()?;
+        let w = SyncWriter::new(&conf)?;
+        w.is_started();
+        Ok(w)
+    }
+}
+
+// copy-paste from Replay except removal of inflight_ops and addition of fix_ipc_permissions to
+// SinkConfiguration
+/// A configuration for [`SyncWriter`].
+#[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, Clone)]
+pub struct SinkConfiguration {
+    pub url: String,
+    pub send_timeout: Duration,
+    pub send_retries: usize,
+    pub receive_timeout: Duration,
+    pub receive_retries: usize,
+    pub send_hwm: usize,
+    pub receive_hwm: usize,
+    pub fix_ipc_permissions: Option<u32>,
+}
+
+impl Default for SinkConfiguration {
+    fn default() -> Self {
+        Self {
+            url: String::from("dealer+connect:ipc:///tmp/in"),
+            send_timeout: Duration::from_secs(1),
+            send_retries: 3,
+            receive_timeout: Duration::from_secs(1),
+            receive_retries: 3,
+            send_hwm: 1000,
+            receive_hwm: 1000,
+            fix_ipc_permissions: None,
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+impl SinkConfiguration {
+    pub fn new(
+        url: &str,
+        send_timeout: Duration,
+        send_retries: usize,
+        receive_timeout: Duration,
+        receive_retries: usize,
+        send_hwm: usize,
+        receive_hwm: usize,
+        fix_ipc_permissions: Option<u32>,
+    ) -> Self {
+        Self {
+            url: url.to_string(),
+            send_timeout,
+            send_retries,
+            receive_timeout,
+            receive_retries,
+            send_hwm,
+            receive_hwm,
+            fix_ipc_permissions,
+        }
+    }
+
+    /// Returns a configuration of a `dealer+connect` sink for tests.
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn test_dealer_connect_sink() -> Self {
+        Self::new(
+            "dealer+connect:ipc:///tmp/in",
+            Duration::from_secs(1),
+            3,
+            Duration::from_secs(1),
+            3,
+            1000,
+            100,
+            None,
+        )
+    }
+}
@@ -0,0 +1,9 @@
+//! gRPC service for media gateway client-server communication.
+//!
+//! The service `media_gateway.Gateway` has the only bidirectional streaming method `Forward`
+//! that accepts a stream of [`MediaRequest`](crate::model::MediaRequest) and returns a stream
+//! of [`MediaResponse`](crate::model::MediaResponse) with the same ids.
+//!
+//! The module provides generated [`gateway_client::GatewayClient`] and
+//! [`gateway_server::GatewayServer`].
+include!(concat!(env!("OUT_DIR"), "/media_gateway.Gateway.rs"));
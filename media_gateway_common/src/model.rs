@@ -1,7 +1,9 @@
 //! Models for media gateway client-server communication.
 //!
-//! The module provides [`Media`] struct that can be converted from/to
-//! [protocol buffers](https://protobuf.dev/).
+//! The module provides [`Media`], [`MediaBatch`] and [`MediaBatchResult`] structs that can be
+//! converted from/to [protocol buffers](https://protobuf.dev/). [`MediaRequest`] and
+//! [`MediaResponse`] are used by streaming transports. [`DeadLetter`] is used to store messages
+//! that are not forwarded.
 use savant_protobuf::generated::Message;
 
 /// A struct that contains all information required to forward a message.
@@ -19,21 +21,172 @@ pub struct Media {
     pub data: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
 }
 
+/// A batch of messages to be forwarded in a single request.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MediaBatch {
+    /// Messages to be forwarded in the order of their processing
+    #[prost(message, repeated, tag = "1")]
+    pub items: ::prost::alloc::vec::Vec<Media>,
+}
+
+/// A status of processing of a single message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum MediaStatus {
+    /// The message is written (corresponds to HTTP status 200)
+    Success = 0,
+    /// The message is not written due to a send timeout (corresponds to HTTP status 504)
+    SendTimeout = 1,
+    /// The message is not acknowledged (corresponds to HTTP status 502)
+    AckTimeout = 2,
+    /// The message is invalid (corresponds to HTTP status 400)
+    BadRequest = 3,
+    /// The message is not allowed for the user (corresponds to HTTP status 401)
+    Unauthorized = 4,
+    /// The message is not written due to an internal error (corresponds to HTTP status 500)
+    InternalError = 5,
+    /// The message is rejected because the server is overloaded (corresponds to HTTP status 503)
+    Overloaded = 6,
+}
+
+/// A result of processing of [`MediaBatch`]. Statuses are in the same order as batch items.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MediaBatchResult {
+    /// Statuses of batch items
+    #[prost(enumeration = "MediaStatus", repeated, tag = "1")]
+    pub statuses: ::prost::alloc::vec::Vec<i32>,
+}
+
+/// A message sent via a streaming transport.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MediaRequest {
+    /// An id to match the request with the response
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+    /// A message to be forwarded
+    #[prost(message, optional, tag = "2")]
+    pub media: ::core::option::Option<Media>,
+}
+
+/// A result of processing of [`MediaRequest`] sent via a streaming transport.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MediaResponse {
+    /// An id of the request
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+    /// A status of processing
+    #[prost(enumeration = "MediaStatus", tag = "2")]
+    pub status: i32,
+}
+
+/// A message that is not forwarded with the reason.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DeadLetter {
+    /// A message that is not forwarded
+    #[prost(message, optional, tag = "1")]
+    pub media: ::core::option::Option<Media>,
+    /// A reason why the message is not forwarded
+    #[prost(string, tag = "2")]
+    pub reason: ::prost::alloc::string::String,
+}
+
 impl Media {
     /// Serializes the struct to protocol buffers.
     pub fn to_proto(&self) -> anyhow::Result<Vec<u8>> {
-        use prost::Message as ProstMessage;
-        let mut buf = Vec::new();
-        self.encode(&mut buf)?;
-        Ok(buf)
+        to_proto(self)
     }
 
     /// Deserializes the struct from protocol buffers.
     pub fn from_proto(bytes: &[u8]) -> anyhow::Result<Self> {
+        from_proto(bytes)
+    }
+
+    /// Returns the size of the struct serialized to protocol buffers.
+    pub fn proto_len(&self) -> usize {
         use prost::Message as ProstMessage;
-        let media = Media::decode(bytes)?;
-        Ok(media)
+        self.encoded_len()
+    }
+}
+
+impl MediaBatch {
+    /// Serializes the struct to protocol buffers.
+    pub fn to_proto(&self) -> anyhow::Result<Vec<u8>> {
+        to_proto(self)
+    }
+
+    /// Deserializes the struct from protocol buffers.
+    pub fn from_proto(bytes: &[u8]) -> anyhow::Result<Self> {
+        from_proto(bytes)
+    }
+}
+
+impl MediaBatchResult {
+    /// Serializes the struct to protocol buffers.
+    pub fn to_proto(&self) -> anyhow::Result<Vec<u8>> {
+        to_proto(self)
+    }
+
+    /// Deserializes the struct from protocol buffers.
+    pub fn from_proto(bytes: &[u8]) -> anyhow::Result<Self> {
+        from_proto(bytes)
+    }
+}
+
+impl MediaRequest {
+    /// Serializes a request with the specified id and message to protocol buffers without
+    /// copying the message.
+    pub fn to_proto_from_parts(id: u64, media: &Media) -> Vec<u8> {
+        use prost::encoding::{message, uint64};
+        let mut buf = Vec::with_capacity(media.proto_len() + 32);
+        uint64::encode(1, &id, &mut buf);
+        message::encode(2, media, &mut buf);
+        buf
     }
+
+    /// Deserializes the struct from protocol buffers.
+    pub fn from_proto(bytes: &[u8]) -> anyhow::Result<Self> {
+        from_proto(bytes)
+    }
+}
+
+impl MediaResponse {
+    /// Serializes the struct to protocol buffers.
+    pub fn to_proto(&self) -> anyhow::Result<Vec<u8>> {
+        to_proto(self)
+    }
+
+    /// Deserializes the struct from protocol buffers.
+    pub fn from_proto(bytes: &[u8]) -> anyhow::Result<Self> {
+        from_proto(bytes)
+    }
+}
+
+impl DeadLetter {
+    /// Serializes the struct to protocol buffers.
+    pub fn to_proto(&self) -> anyhow::Result<Vec<u8>> {
+        to_proto(self)
+    }
+
+    /// Deserializes the struct from protocol buffers.
+    pub fn from_proto(bytes: &[u8]) -> anyhow::Result<Self> {
+        from_proto(bytes)
+    }
+}
+
+fn to_proto<T: prost::Message>(value: &T) -> anyhow::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    value.encode(&mut buf)?;
+    Ok(buf)
+}
+
+fn from_proto<T: prost::Message + Default>(bytes: &[u8]) -> anyhow::Result<T> {
+    let value = T::decode(bytes)?;
+    Ok(value)
 }
 
 #[cfg(test)]
@@ -43,7 +196,9 @@ mod tests {
     use savant_protobuf::generated::message::Content;
     use savant_protobuf::generated::{Message, Unknown};
 
-    use crate::model::Media;
+    use crate::model::{
+        DeadLetter, Media, MediaBatch, MediaBatchResult, MediaRequest, MediaResponse, MediaStatus,
+    };
 
     #[test]
     fn to_from_proto() {
@@ -64,5 +219,86 @@ mod tests {
         let bytes = original_media.to_proto().expect("to_proto failed");
         let result_media = Media::from_proto(&bytes).expect("from_proto failed");
         assert_eq!(original_media, result_media);
+        assert_eq!(original_media.proto_len(), bytes.len());
+    }
+
+    #[test]
+    fn batch_to_from_proto() {
+        let original_batch = MediaBatch {
+            items: vec![
+                Media {
+                    message: None,
+                    topic: "topic1".as_bytes().to_vec(),
+                    data: vec![vec![1]],
+                },
+                Media {
+                    message: None,
+                    topic: "topic2".as_bytes().to_vec(),
+                    data: vec![],
+                },
+            ],
+        };
+        let bytes = original_batch.to_proto().expect("to_proto failed");
+        let result_batch = MediaBatch::from_proto(&bytes).expect("from_proto failed");
+        assert_eq!(original_batch, result_batch);
+    }
+
+    #[test]
+    fn dead_letter_to_from_proto() {
+        let original = DeadLetter {
+            media: Some(Media {
+                message: None,
+                topic: "topic".as_bytes().to_vec(),
+                data: vec![vec![1]],
+            }),
+            reason: "reason".to_string(),
+        };
+        let bytes = original.to_proto().expect("to_proto failed");
+        let result = DeadLetter::from_proto(&bytes).expect("from_proto failed");
+        assert_eq!(original, result);
+    }
+
+    #[test]
+    fn batch_result_to_from_proto() {
+        let original_result = MediaBatchResult {
+            statuses: vec![MediaStatus::Success as i32, MediaStatus::AckTimeout as i32],
+        };
+        let bytes = original_result.to_proto().expect("to_proto failed");
+        let result = MediaBatchResult::from_proto(&bytes).expect("from_proto failed");
+        assert_eq!(original_result, result);
+        assert_eq!(
+            result.statuses().collect::<Vec<MediaStatus>>(),
+            vec![MediaStatus::Success, MediaStatus::AckTimeout]
+        );
+    }
+
+    #[test]
+    fn request_to_from_proto() {
+        let media = Media {
+            message: None,
+            topic: "topic".as_bytes().to_vec(),
+            data: vec![vec![1]],
+        };
+        let bytes = MediaRequest::to_proto_from_parts(10, &media);
+        let result = MediaRequest::from_proto(&bytes).expect("from_proto failed");
+        assert_eq!(
+            result,
+            MediaRequest {
+                id: 10,
+                media: Some(media),
+            }
+        );
+    }
+
+    #[test]
+    fn response_to_from_proto() {
+        let original_response = MediaResponse {
+            id: 10,
+            status: MediaStatus::SendTimeout as i32,
+        };
+        let bytes = original_response.to_proto().expect("to_proto failed");
+        let result = MediaResponse::from_proto(&bytes).expect("from_proto failed");
+        assert_eq!(original_response, result);
+        assert_eq!(result.status(), MediaStatus::SendTimeout);
     }
 }
@@ -17,24 +17,40 @@ pub struct StatisticsService {
 
 impl StatisticsService {
     pub fn new(configuration: PipelineConfiguration, name: &str) -> Self {
+        Self::with_stages(configuration, &[name])
+    }
+
+    /// Constructs a new instance with a stage for each name. Messages are registered in the
+    /// first stage by [`StatisticsService::register_message_start`].
+    pub fn with_stages(configuration: PipelineConfiguration, names: &[&str]) -> Self {
         let pipeline = Pipeline::new(
-            vec![(
-                name.to_string(),
-                PipelineStagePayloadType::Frame,
-                None,
-                None,
-            )],
+            names
+                .iter()
+                .map(|name| {
+                    (
+                        name.to_string(),
+                        PipelineStagePayloadType::Frame,
+                        None,
+                        None,
+                    )
+                })
+                .collect(),
             configuration,
         )
         .expect("invalid pipeline");
 
         Self {
-            name: name.to_string(),
+            name: names[0].to_string(),
             pipeline,
         }
     }
 
     pub fn register_message_start(&self) -> anyhow::Result<i64> {
+        self.register_stage_message_start(&self.name)
+    }
+
+    /// Registers the start of processing of a message in the stage.
+    pub fn register_stage_message_start(&self, stage: &str) -> anyhow::Result<i64> {
         let video_frame_proxy = VideoFrameProxy::new(
             STAT_SOURCE_ID,
             "",
@@ -49,20 +65,36 @@ impl StatisticsService {
             None,
             None,
         );
-        self.pipeline
-            .add_frame(self.name.as_str(), video_frame_proxy)
+        self.pipeline.add_frame(stage, video_frame_proxy)
     }
 
     pub fn register_message_end(&self, id: i64) -> anyhow::Result<()> {
         self.pipeline.delete(id).map(|_e| ())
     }
+
+    /// Registers a message that is not processed further after the stage (e.g. dropped).
+    pub fn register_stage_message(&self, stage: &str) -> anyhow::Result<()> {
+        let id = self.register_stage_message_start(stage)?;
+        self.register_message_end(id)
+    }
 }
 
 impl TryFrom<(&StatisticsConfiguration, &str)> for StatisticsService {
     type Error = anyhow::Error;
 
     fn try_from(value: (&StatisticsConfiguration, &str)) -> Result<Self, Self::Error> {
+        StatisticsService::try_from((value.0, [value.1].as_slice()))
+    }
+}
+
+impl TryFrom<(&StatisticsConfiguration, &[&str])> for StatisticsService {
+    type Error = anyhow::Error;
+
+    fn try_from(value: (&StatisticsConfiguration, &[&str])) -> Result<Self, Self::Error> {
         let configuration = value.0;
+        if value.1.is_empty() {
+            bail!("No statistics stages")
+        }
         if configuration.frame_period.is_none() && configuration.timestamp_period.is_none()
             || configuration.frame_period.is_some() && configuration.timestamp_period.is_some()
         {
@@ -81,6 +113,9 @@ impl TryFrom<(&StatisticsConfiguration, &str)> for StatisticsService {
             .frame_period(configuration.frame_period)
             .build()?;
 
-        Ok(StatisticsService::new(pipeline_configuration, value.1))
+        Ok(StatisticsService::with_stages(
+            pipeline_configuration,
+            value.1,
+        ))
     }
 }
@@ -1,7 +1,8 @@
 //! The media gateway server.
 //!
-//! The server accepts messages via HTTP(s) and writers them to [ZeroMQ](https://zeromq.org/)
-//! using [`SyncWriter`](savant_core::transport::zeromq::SyncWriter) from `savant_core`.
+//! The server accepts messages via HTTP(s), WebSocket or gRPC and writers them to
+//! [ZeroMQ](https://zeromq.org/) using [`SyncWriter`](savant_core::transport::zeromq::SyncWriter)
+//! from `savant_core`.
 //!
 //! To run the server
 //! ```bash
@@ -12,6 +13,8 @@
 //! * TLS (including a self-signed PEM encoded certificate)
 //! * client certificate authentication
 //! * basic authentication with an in-memory user data storage
+//! * compressed request bodies
+//! * overload responses (429 or 503 with `Retry-After` header)
 //!
 //! # API
 //! * an endpoint to process messages
@@ -25,6 +28,10 @@
 //! ```
 //! where data is [`Media`](media_gateway_common::model::Media)
 //!
+//! The body may be compressed with `zstd`, `gzip` or `br` algorithm specified in
+//! `Content-Encoding` header (for all endpoints accepting a body). The size of the decompressed
+//! body is limited by `max_payload_size` configuration field.
+//!
 //! Responses:
 //!
 //!| HTTP status code | Description                                                                                                                                                                |
@@ -32,6 +39,52 @@
 //!| 200              | Corresponds to [`WriterResult::Ack`](savant_core::transport::zeromq::WriterResult::Ack) or [`WriterResult::Success`](savant_core::transport::zeromq::WriterResult::Success)|
 //!| 504              | Corresponds to [`WriterResult::SendTimeout`](savant_core::transport::zeromq::WriterResult::SendTimeout)                                                                    |
 //!| 502              | Corresponds to [`WriterResult::AckTimeout`](savant_core::transport::zeromq::WriterResult::AckTimeout)                                                                      |
+//!| 400              | The body is not a valid message or Content-Type is not application/protobuf                                                                                                |
+//!| 413              | The (decompressed) body is greater than `max_payload_size`                                                                                                                 |
+//!| 429 or 503       | The server is overloaded (if `overload` is configured), `Retry-After` header contains the number of seconds after which the request may be retried                         |
+//!
+//! * an endpoint to process a batch of messages
+//! ```
+//! POST /batch HTTP/1.1
+//! Host: <host>
+//! Content-Type: application/protobuf
+//! Content-Length: <length>
+//!
+//! <data>
+//! ```
+//! where data is [`MediaBatch`](media_gateway_common::model::MediaBatch)
+//!
+//! If the batch is processed an HTTP response with 200 OK status code and
+//! [`MediaBatchResult`](media_gateway_common::model::MediaBatchResult) as the body is returned.
+//! The result contains a [`MediaStatus`](media_gateway_common::model::MediaStatus) for each
+//! message in the batch. If the server is overloaded the whole batch is rejected with the same
+//! response as for a single message.
+//!
+//! * a WebSocket endpoint to stream messages (if enabled)
+//! ```
+//! GET /ws HTTP/1.1
+//! Host: <host>
+//! Upgrade: websocket
+//! Connection: Upgrade
+//! ```
+//! Each binary WebSocket message from the client is
+//! [`MediaRequest`](media_gateway_common::model::MediaRequest). For each request the server
+//! sends [`MediaResponse`](media_gateway_common::model::MediaResponse) with the same id as a
+//! binary WebSocket message. Authentication is the same as for other endpoints.
+//!
+//! * a gRPC service to stream messages (if enabled) on a separate port
+//! ```
+//! service Gateway {
+//!   rpc Forward(stream MediaRequest) returns (stream MediaResponse);
+//! }
+//! ```
+//! in `media_gateway` package. For each [`MediaRequest`](media_gateway_common::model::MediaRequest)
+//! the server sends [`MediaResponse`](media_gateway_common::model::MediaResponse) with the same id.
+//! Basic authentication credentials are passed in `authorization` metadata. TLS settings are the
+//! same as for HTTP(s).
+//!
+//! If the server is overloaded messages streamed over WebSocket and gRPC are answered with
+//! [`MediaStatus::Overloaded`](media_gateway_common::model::MediaStatus::Overloaded).
 //!
 //! * a health endpoint
 //! ```
@@ -68,7 +121,9 @@ use media_gateway_common::configuration::Credentials;
 use media_gateway_common::health::HealthService;
 use server::configuration::GatewayConfiguration;
 
-use crate::server::api::gateway;
+use crate::server::api::{gateway, gateway_batch, gateway_ws, payload_config};
+use crate::server::grpc;
+use crate::server::grpc::{GrpcAuthServices, GrpcGatewayService};
 use crate::server::security::quarantine::{
     AuthQuarantine, AuthQuarantineFactory, NoOpAuthQuarantine,
 };
@@ -83,6 +138,8 @@ use crate::server::storage::{EmptyStorage, Storage};
 
 mod server;
 
+const DEFAULT_MAX_PAYLOAD_SIZE: usize = 262_144;
+
 type AuthAppData = (
     Box<dyn Storage<UserData> + Sync + Send>,
     Cache<Credentials, BasicAuthCheckResult>,
@@ -108,7 +165,9 @@ fn main() -> Result<()> {
 
     let conf = GatewayConfiguration::new(&conf_arg)?;
     let bind_address = (conf.ip.as_str(), conf.port);
-    let gateway_service = web::Data::new(Mutex::new(GatewayService::try_from(&conf)?));
+    let gateway_service = GatewayService::try_from(&conf)?;
+    let overload_detector = gateway_service.overload_detector().map(web::Data::from);
+    let gateway_service = web::Data::new(Mutex::new(gateway_service));
     let health_service = web::Data::new(HealthService::new());
     let auth_enabled = conf.auth.is_some();
     let (user_storage, auth_cache, auth_quarantine): AuthAppData =
@@ -155,22 +214,50 @@ fn main() -> Result<()> {
     let password_service: web::Data<Box<dyn PasswordService + Sync + Send>> =
         web::Data::new(Box::new(Argon2PasswordService {}));
     let user_service = web::Data::new(UserService::new(user_storage));
+    let websocket_conf = conf.websocket.clone().map(web::Data::new);
+    let payload_conf = payload_config(conf.max_payload_size.unwrap_or(DEFAULT_MAX_PAYLOAD_SIZE));
+    let grpc_service = GrpcGatewayService::new(
+        gateway_service.clone(),
+        overload_detector.clone(),
+        if auth_enabled {
+            Some(GrpcAuthServices {
+                user_service: user_service.clone(),
+                password_service: password_service.clone(),
+                basic_auth_check_result_cache: basic_auth_cache.clone(),
+                basic_auth_quarantine: basic_auth_quarantine.clone(),
+            })
+        } else {
+            None
+        },
+    );
 
     let mut http_server = HttpServer::new(move || {
+        let gateway_scope = scope("/")
+            .app_data(gateway_service.clone())
+            .app_data(user_service.clone())
+            .app_data(password_service.clone())
+            .app_data(basic_auth_cache.clone())
+            .app_data(basic_auth_quarantine.clone())
+            .app_data(payload_conf.clone())
+            .route("", web::post().to(gateway))
+            .route("batch", web::post().to(gateway_batch));
+        let gateway_scope = if let Some(overload_detector) = &overload_detector {
+            gateway_scope.app_data(overload_detector.clone())
+        } else {
+            gateway_scope
+        };
+        let gateway_scope = if let Some(websocket_conf) = &websocket_conf {
+            gateway_scope
+                .app_data(websocket_conf.clone())
+                .route("ws", web::get().to(gateway_ws))
+        } else {
+            gateway_scope
+        };
         App::new()
-            .service(
-                scope("/")
-                    .app_data(gateway_service.clone())
-                    .app_data(user_service.clone())
-                    .app_data(password_service.clone())
-                    .app_data(basic_auth_cache.clone())
-                    .app_data(basic_auth_quarantine.clone())
-                    .route("", web::post().to(gateway))
-                    .wrap(Condition::new(
-                        auth_enabled,
-                        HttpAuthentication::basic(basic_auth_validator),
-                    )),
-            )
+            .service(gateway_scope.wrap(Condition::new(
+                auth_enabled,
+                HttpAuthentication::basic(basic_auth_validator),
+            )))
             .service(
                 scope("/health")
                     .app_data(health_service.clone())
@@ -178,10 +265,10 @@ fn main() -> Result<()> {
             )
     });
 
-    http_server = if let Some(ssl_conf) = conf.tls {
+    http_server = if let Some(ssl_conf) = &conf.tls {
         let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
-        builder.set_private_key_file(ssl_conf.identity.key, SslFiletype::PEM)?;
-        builder.set_certificate_chain_file(ssl_conf.identity.certificate)?;
+        builder.set_private_key_file(&ssl_conf.identity.key, SslFiletype::PEM)?;
+        builder.set_certificate_chain_file(&ssl_conf.identity.certificate)?;
 
         builder = if let Some(peer_tls_conf) = &ssl_conf.peers {
             let mut cert_store_builder = X509StoreBuilder::new().unwrap();
@@ -221,8 +308,17 @@ fn main() -> Result<()> {
 
         http_server.bind_openssl(bind_address, builder).unwrap()
     } else {
-        http_server.bind(bind_address).unwrap()
+        http_server.bind_auto_h2c(bind_address).unwrap()
     };
 
-    runtime.block_on(async { http_server.run().await.map_err(anyhow::Error::from) })
+    runtime.block_on(async {
+        if let Some(grpc_conf) = &conf.grpc {
+            tokio::select! {
+                result = http_server.run() => result.map_err(anyhow::Error::from),
+                result = grpc::serve(grpc_service, &conf.ip, grpc_conf, conf.tls.as_ref()) => result,
+            }
+        } else {
+            http_server.run().await.map_err(anyhow::Error::from)
+        }
+    })
 }
@@ -1,5 +1,6 @@
 pub mod api;
 pub mod configuration;
+pub mod grpc;
 pub mod security;
 pub mod service;
 pub mod storage;
@@ -1,17 +1,309 @@
 use actix_protobuf::ProtoBuf;
-use actix_web::web::{Data, ReqData};
-use actix_web::Responder;
-use media_gateway_common::model::Media;
+use actix_web::web::{Bytes, Data, Payload, PayloadConfig, ReqData};
+use actix_web::{HttpRequest, HttpResponse};
+use actix_ws::Message;
+use log::{debug, error, warn};
+use media_gateway_common::model::{Media, MediaBatch, MediaRequest, MediaResponse, MediaStatus};
+use mime::Mime;
 use tokio::sync::Mutex;
 
+use crate::server::configuration::WebSocketConfiguration;
 use crate::server::service::gateway::GatewayService;
+use crate::server::service::overload::{OverloadDetector, PendingRequest};
 use crate::server::service::user::UserData;
 
+/// A content type of request bodies
+const PROTOBUF_CONTENT_TYPE: &str = "application/protobuf";
+
+/// Returns settings of request bodies of [`gateway`] and [`gateway_batch`]: the maximum size of
+/// the decompressed body and the content type required by ProtoBuf extractor.
+pub fn payload_config(max_payload_size: usize) -> PayloadConfig {
+    PayloadConfig::new(max_payload_size).mimetype(PROTOBUF_CONTENT_TYPE.parse::<Mime>().unwrap())
+}
+
+// The body is extracted as bytes (not ProtoBuf) to be decompressed according to Content-Encoding
+// header with the decompressed size and the content type checked according to PayloadConfig
+// (see payload_config).
 pub async fn gateway(
     service: Data<Mutex<GatewayService>>,
-    media: ProtoBuf<Media>,
+    overload_detector: Option<Data<OverloadDetector>>,
+    body: Bytes,
     user_data: Option<ReqData<UserData>>,
-) -> impl Responder {
+) -> HttpResponse {
+    let _pending_request = match enter(&overload_detector) {
+        Ok(pending_request) => pending_request,
+        Err(response) => return response,
+    };
+    let media = match Media::from_proto(&body) {
+        Ok(media) => media,
+        Err(e) => {
+            debug!("Invalid message: {:?}", e);
+            return HttpResponse::BadRequest().finish();
+        }
+    };
     let gateway_service = service.lock().await;
-    gateway_service.process(media, user_data)
+    gateway_service.process(ProtoBuf(media), user_data)
+}
+
+pub async fn gateway_batch(
+    service: Data<Mutex<GatewayService>>,
+    overload_detector: Option<Data<OverloadDetector>>,
+    body: Bytes,
+    user_data: Option<ReqData<UserData>>,
+) -> HttpResponse {
+    let _pending_request = match enter(&overload_detector) {
+        Ok(pending_request) => pending_request,
+        Err(response) => return response,
+    };
+    let batch = match MediaBatch::from_proto(&body) {
+        Ok(batch) => batch,
+        Err(e) => {
+            debug!("Invalid batch: {:?}", e);
+            return HttpResponse::BadRequest().finish();
+        }
+    };
+    let gateway_service = service.lock().await;
+    gateway_service.process_batch(ProtoBuf(batch), user_data)
+}
+
+// Requests waiting for the gateway service are counted as pending. If the server is overloaded
+// the response to reject the request is returned.
+fn enter(
+    overload_detector: &Option<Data<OverloadDetector>>,
+) -> Result<Option<PendingRequest<'_>>, HttpResponse> {
+    match overload_detector {
+        Some(overload_detector) => match overload_detector.enter() {
+            Some(pending_request) => Ok(Some(pending_request)),
+            None => Err(overload_detector.overloaded_response()),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Processes a message streamed over WebSocket or gRPC. Each message is counted as a pending
+/// request and rejected with [`MediaStatus::Overloaded`] if the server is overloaded.
+pub async fn process_streamed(
+    service: &Mutex<GatewayService>,
+    overload_detector: Option<&OverloadDetector>,
+    media: &Media,
+    user_data: Option<&UserData>,
+) -> MediaStatus {
+    let _pending_request = match overload_detector.map(|e| e.enter()) {
+        Some(None) => return MediaStatus::Overloaded,
+        pending_request => pending_request,
+    };
+    service.lock().await.process_media(media, user_data)
+}
+
+pub async fn gateway_ws(
+    request: HttpRequest,
+    body: Payload,
+    service: Data<Mutex<GatewayService>>,
+    overload_detector: Option<Data<OverloadDetector>>,
+    configuration: Data<WebSocketConfiguration>,
+    user_data: Option<ReqData<UserData>>,
+) -> actix_web::Result<HttpResponse> {
+    let (response, mut session, stream) = actix_ws::handle(&request, body)?;
+    let mut stream = stream.max_frame_size(configuration.max_frame_size);
+    let user_data = user_data.map(|e| e.into_inner());
+
+    actix_web::rt::spawn(async move {
+        debug!("WebSocket session is started");
+        let close_reason = loop {
+            let message = match stream.recv().await {
+                Some(Ok(message)) => message,
+                Some(Err(e)) => {
+                    warn!("WebSocket protocol error: {:?}", e);
+                    break None;
+                }
+                None => break None,
+            };
+            match message {
+                Message::Binary(bytes) => {
+                    let response = match MediaRequest::from_proto(&bytes) {
+                        Ok(MediaRequest {
+                            id,
+                            media: Some(media),
+                        }) => {
+                            let status = process_streamed(
+                                &service,
+                                overload_detector.as_deref(),
+                                &media,
+                                user_data.as_ref(),
+                            )
+                            .await;
+                            MediaResponse {
+                                id,
+                                status: status as i32,
+                            }
+                        }
+                        Ok(MediaRequest { id, media: None }) => MediaResponse {
+                            id,
+                            status: MediaStatus::BadRequest as i32,
+                        },
+                        Err(e) => {
+                            warn!("Invalid WebSocket request: {:?}", e);
+                            break None;
+                        }
+                    };
+                    let body = match response.to_proto() {
+                        Ok(body) => body,
+                        Err(e) => {
+                            error!("Failed to serialize a WebSocket response: {:?}", e);
+                            break None;
+                        }
+                    };
+                    if session.binary(body).await.is_err() {
+                        break None;
+                    }
+                }
+                Message::Ping(bytes) => {
+                    if session.pong(&bytes).await.is_err() {
+                        break None;
+                    }
+                }
+                Message::Close(reason) => break reason,
+                _ => {}
+            }
+        };
+        let _ = session.close(close_reason).await;
+        debug!("WebSocket session is stopped");
+    });
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::time::Duration;
+
+    use actix_web::http::header::{CONTENT_ENCODING, CONTENT_TYPE};
+    use actix_web::http::StatusCode;
+    use actix_web::web::{scope, Data};
+    use actix_web::{test, web, App};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use savant_core::message::Message;
+    use savant_core::transport::zeromq::{SyncWriter, WriterConfigBuilder};
+    use tokio::sync::Mutex;
+
+    use media_gateway_common::model::{Media, MediaStatus};
+
+    use crate::server::api::{gateway, payload_config, process_streamed, PROTOBUF_CONTENT_TYPE};
+    use crate::server::service::gateway::GatewayService;
+    use crate::server::service::overload::OverloadDetector;
+
+    const MAX_PAYLOAD_SIZE: usize = 262_144;
+
+    #[actix_web::test]
+    async fn gateway_gzip() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&new_body(1)).unwrap();
+        let body = encoder.finish().unwrap();
+
+        let status = post(PROTOBUF_CONTENT_TYPE, Some("gzip"), body).await;
+
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn gateway_zstd() {
+        let body = zstd::encode_all(new_body(1).as_slice(), 0).unwrap();
+
+        let status = post(PROTOBUF_CONTENT_TYPE, Some("zstd"), body).await;
+
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn gateway_too_large() {
+        let status = post(PROTOBUF_CONTENT_TYPE, None, new_body(MAX_PAYLOAD_SIZE)).await;
+
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[actix_web::test]
+    async fn gateway_decompressed_too_large() {
+        let body = zstd::encode_all(new_body(MAX_PAYLOAD_SIZE).as_slice(), 0).unwrap();
+        assert!(body.len() < MAX_PAYLOAD_SIZE);
+
+        let status = post(PROTOBUF_CONTENT_TYPE, Some("zstd"), body).await;
+
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[actix_web::test]
+    async fn gateway_invalid_content_type() {
+        let status = post("application/json", None, new_body(1)).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn process_streamed_overloaded() {
+        let service = Mutex::new(new_service());
+        let media = Media::from_proto(&new_body(1)).unwrap();
+        let overload_detector = OverloadDetector::new(
+            Some(1),
+            None,
+            Duration::from_secs(10),
+            StatusCode::SERVICE_UNAVAILABLE,
+            Duration::from_secs(1),
+        );
+
+        let status = process_streamed(&service, Some(&overload_detector), &media, None).await;
+        assert_eq!(status, MediaStatus::Success);
+
+        let _pending_request = overload_detector.enter().unwrap();
+        let status = process_streamed(&service, Some(&overload_detector), &media, None).await;
+        assert_eq!(status, MediaStatus::Overloaded);
+    }
+
+    async fn post(content_type: &str, encoding: Option<&str>, body: Vec<u8>) -> StatusCode {
+        let app = test::init_service(
+            App::new().service(
+                scope("/")
+                    .app_data(Data::new(Mutex::new(new_service())))
+                    .app_data(payload_config(MAX_PAYLOAD_SIZE))
+                    .route("", web::post().to(gateway)),
+            ),
+        )
+        .await;
+        let mut request = test::TestRequest::post()
+            .uri("/")
+            .insert_header((CONTENT_TYPE, content_type));
+        if let Some(encoding) = encoding {
+            request = request.insert_header((CONTENT_ENCODING, encoding));
+        }
+        test::call_service(&app, request.set_payload(body).to_request())
+            .await
+            .status()
+    }
+
+    fn new_service() -> GatewayService {
+        let writer = SyncWriter::new(
+            &WriterConfigBuilder::default()
+                .url(&format!(
+                    "pub+bind:ipc:///tmp/test{}",
+                    rand::random::<u16>()
+                ))
+                .unwrap()
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+        writer.is_started();
+        GatewayService::new(writer, None, None)
+    }
+
+    fn new_body(data_size: usize) -> Vec<u8> {
+        let message = Message::unknown("message".to_string());
+        let media = Media {
+            message: Some(savant_protobuf::generated::Message::from(&message)),
+            topic: "topic".as_bytes().to_vec(),
+            data: vec![vec![0; data_size]],
+        };
+        media.to_proto().unwrap()
+    }
 }
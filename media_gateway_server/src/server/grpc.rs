@@ -0,0 +1,308 @@
+use std::fs;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::http::header::HeaderValue;
+use actix_web::web::Data;
+use actix_web_httpauth::extractors::basic::BasicAuth;
+use actix_web_httpauth::headers::authorization::{Basic, Scheme};
+use anyhow::{anyhow, bail};
+use log::{debug, warn};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, Mutex};
+use tokio::time::sleep;
+use tokio_rustls::rustls::pki_types::{CertificateDer, CertificateRevocationListDer};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{RootCertStore, ServerConfig};
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status, Streaming};
+
+use media_gateway_common::configuration::Credentials;
+use media_gateway_common::grpc::gateway_server::{Gateway, GatewayServer};
+use media_gateway_common::model::{MediaRequest, MediaResponse, MediaStatus};
+
+use crate::server::api::process_streamed;
+use crate::server::configuration::{GrpcConfiguration, ServerTlsConfiguration};
+use crate::server::security::quarantine::AuthQuarantine;
+use crate::server::security::{check_basic_auth, BasicAuthCheckResult, BasicAuthError};
+use crate::server::service::cache::Cache;
+use crate::server::service::crypto::PasswordService;
+use crate::server::service::gateway::GatewayService;
+use crate::server::service::overload::OverloadDetector;
+use crate::server::service::user::{UserData, UserService};
+
+const RESPONSE_CHANNEL_SIZE: usize = 16;
+/// The maximum number of accepted TLS connections waiting to be served
+const ACCEPT_CHANNEL_SIZE: usize = 16;
+/// A delay before the next attempt to accept a connection after an error (e.g. too many open
+/// files)
+const ACCEPT_ERROR_DELAY: Duration = Duration::from_millis(100);
+
+/// Services to check basic authentication of gRPC streams. The same instances as for HTTP
+/// endpoints are used.
+pub struct GrpcAuthServices {
+    pub user_service: Data<UserService>,
+    pub password_service: Data<Box<dyn PasswordService + Sync + Send>>,
+    pub basic_auth_check_result_cache: Data<Cache<Credentials, BasicAuthCheckResult>>,
+    pub basic_auth_quarantine: Data<Box<dyn AuthQuarantine + Sync + Send>>,
+}
+
+/// A gRPC service that accepts a bidirectional stream of
+/// [`MediaRequest`](media_gateway_common::model::MediaRequest) and responds with
+/// [`MediaResponse`](media_gateway_common::model::MediaResponse) for each request. If the server
+/// is overloaded requests are answered with
+/// [`MediaStatus::Overloaded`](media_gateway_common::model::MediaStatus::Overloaded).
+pub struct GrpcGatewayService {
+    gateway_service: Data<Mutex<GatewayService>>,
+    overload_detector: Option<Data<OverloadDetector>>,
+    auth_services: Option<GrpcAuthServices>,
+}
+
+impl GrpcGatewayService {
+    pub fn new(
+        gateway_service: Data<Mutex<GatewayService>>,
+        overload_detector: Option<Data<OverloadDetector>>,
+        auth_services: Option<GrpcAuthServices>,
+    ) -> Self {
+        Self {
+            gateway_service,
+            overload_detector,
+            auth_services,
+        }
+    }
+
+    fn authenticate<T>(&self, request: &Request<T>) -> Result<Option<UserData>, Status> {
+        let auth_services = match &self.auth_services {
+            Some(auth_services) => auth_services,
+            None => return Ok(None),
+        };
+        let credentials = request
+            .metadata()
+            .get("authorization")
+            .and_then(|e| HeaderValue::from_bytes(e.as_encoded_bytes()).ok())
+            .and_then(|e| Basic::parse(&e).ok())
+            .map(BasicAuth::from)
+            .ok_or_else(|| Status::unauthenticated(""))?;
+        match check_basic_auth(
+            &credentials,
+            &auth_services.user_service,
+            auth_services.password_service.get_ref().as_ref(),
+            &auth_services.basic_auth_check_result_cache,
+            auth_services.basic_auth_quarantine.get_ref().as_ref(),
+        ) {
+            Ok(user_data) => Ok(Some(user_data)),
+            Err(BasicAuthError::Unauthorized) => Err(Status::unauthenticated("")),
+            Err(BasicAuthError::Internal) => Err(Status::internal("")),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Gateway for GrpcGatewayService {
+    type ForwardStream = ReceiverStream<Result<MediaResponse, Status>>;
+
+    async fn forward(
+        &self,
+        request: Request<Streaming<MediaRequest>>,
+    ) -> Result<Response<Self::ForwardStream>, Status> {
+        let user_data = self.authenticate(&request)?;
+        let mut requests = request.into_inner();
+        let gateway_service = self.gateway_service.clone();
+        let overload_detector = self.overload_detector.clone();
+        let (sender, receiver) = mpsc::channel(RESPONSE_CHANNEL_SIZE);
+
+        tokio::spawn(async move {
+            debug!("gRPC stream is started");
+            loop {
+                let request = match requests.message().await {
+                    Ok(Some(request)) => request,
+                    Ok(None) => break,
+                    Err(e) => {
+                        warn!("gRPC stream error: {:?}", e);
+                        break;
+                    }
+                };
+                let status = match &request.media {
+                    Some(media) => {
+                        process_streamed(
+                            &gateway_service,
+                            overload_detector.as_deref(),
+                            media,
+                            user_data.as_ref(),
+                        )
+                        .await
+                    }
+                    None => MediaStatus::BadRequest,
+                };
+                let response = MediaResponse {
+                    id: request.id,
+                    status: status as i32,
+                };
+                if sender.send(Ok(response)).await.is_err() {
+                    break;
+                }
+            }
+            debug!("gRPC stream is stopped");
+        });
+
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+}
+
+/// Runs a gRPC server with [`GrpcGatewayService`].
+///
+/// Client certificates are verified against certificates in the lookup hash directory. If CRLs
+/// are enabled certificates of the whole chain are checked against CRLs (`<hash>.r<n>` files) in
+/// the same directory and a certificate with unknown revocation status is rejected.
+pub async fn serve(
+    service: GrpcGatewayService,
+    ip: &str,
+    configuration: &GrpcConfiguration,
+    tls_configuration: Option<&ServerTlsConfiguration>,
+) -> anyhow::Result<()> {
+    let address = SocketAddr::new(ip.parse::<IpAddr>()?, configuration.port);
+    let router = Server::builder().add_service(
+        GatewayServer::new(service).max_decoding_message_size(configuration.max_message_size),
+    );
+
+    match tls_configuration {
+        Some(ssl_conf) => {
+            let acceptor = TlsAcceptor::from(Arc::new(new_tls_config(ssl_conf)?));
+            let listener = TcpListener::bind(address).await?;
+            let (sender, receiver) = mpsc::channel(ACCEPT_CHANNEL_SIZE);
+            tokio::spawn(accept(listener, acceptor, sender));
+            router
+                .serve_with_incoming(ReceiverStream::new(receiver))
+                .await?;
+        }
+        None => router.serve(address).await?,
+    }
+    Ok(())
+}
+
+/// Accepts connections and performs TLS handshakes concurrently until the server is stopped.
+async fn accept(
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+    sender: mpsc::Sender<io::Result<TlsStream<TcpStream>>>,
+) {
+    while !sender.is_closed() {
+        let (stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                warn!("Error while accepting gRPC connection: {:?}", e);
+                sleep(ACCEPT_ERROR_DELAY).await;
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            match acceptor.accept(stream).await {
+                Ok(stream) => {
+                    let _ = sender.send(Ok(stream)).await;
+                }
+                Err(e) => warn!("Error while TLS handshake with {}: {:?}", peer, e),
+            }
+        });
+    }
+}
+
+fn new_tls_config(ssl_conf: &ServerTlsConfiguration) -> anyhow::Result<ServerConfig> {
+    let chain = fs::read(&ssl_conf.identity.certificate)?;
+    let chain = rustls_pemfile::certs(&mut chain.as_slice())
+        .collect::<Result<Vec<CertificateDer>, io::Error>>()?;
+    let key = fs::read(&ssl_conf.identity.key)?;
+    let key = rustls_pemfile::private_key(&mut key.as_slice())?
+        .ok_or_else(|| anyhow!("No private key in {}", ssl_conf.identity.key))?;
+
+    let builder = ServerConfig::builder();
+    let builder = match &ssl_conf.peers {
+        Some(peer_tls_conf) => {
+            let path = &peer_tls_conf.lookup_hash_directory;
+            let certificates = read_hash_directory(path, "")?;
+            let mut roots = RootCertStore::empty();
+            for certificate in rustls_pemfile::certs(&mut certificates.as_slice()) {
+                roots.add(certificate?)?;
+            }
+            let mut verifier_builder = WebPkiClientVerifier::builder(Arc::new(roots));
+            if peer_tls_conf.crl_enabled {
+                let crls = read_hash_directory(path, "r")?;
+                let crls = rustls_pemfile::crls(&mut crls.as_slice())
+                    .collect::<Result<Vec<CertificateRevocationListDer>, io::Error>>()?;
+                verifier_builder = verifier_builder.with_crls(crls);
+            }
+            builder.with_client_cert_verifier(verifier_builder.build()?)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut tls_config = builder.with_single_cert(chain, key)?;
+    tls_config.alpn_protocols = vec![b"h2".to_vec()];
+    Ok(tls_config)
+}
+
+/// Reads and concatenates all files `<hash>.<prefix><n>` in the lookup hash directory:
+/// certificates with the empty prefix or CRLs with `r` prefix.
+fn read_hash_directory(path: &str, prefix: &str) -> anyhow::Result<Vec<u8>> {
+    let mut content = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let is_matching = file_name
+            .to_str()
+            .and_then(|e| e.rsplit_once('.'))
+            .and_then(|(_, extension)| extension.strip_prefix(prefix))
+            .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()));
+        if is_matching {
+            content.extend(fs::read(entry.path())?);
+            content.push(b'\n');
+        }
+    }
+    if content.is_empty() {
+        bail!(
+            "No {} in {}",
+            if prefix.is_empty() {
+                "certificates"
+            } else {
+                "CRLs"
+            },
+            path
+        );
+    }
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::server::grpc::read_hash_directory;
+
+    #[test]
+    fn read_certificates_and_crls() {
+        let path = std::env::temp_dir().join(format!("hash-{}", rand::random::<u64>()));
+        fs::create_dir(&path).unwrap();
+        for (name, content) in [
+            ("1a2b3c4d.0", "certificate"),
+            ("1a2b3c4d.r0", "crl"),
+            ("readme.txt", "text"),
+        ] {
+            fs::write(path.join(name), content).unwrap();
+        }
+        let directory = path.to_str().unwrap();
+
+        let certificates = read_hash_directory(directory, "").unwrap();
+        let crls = read_hash_directory(directory, "r").unwrap();
+
+        fs::remove_dir_all(&path).unwrap();
+        assert_eq!(certificates, b"certificate\n");
+        assert_eq!(crls, b"crl\n");
+        assert!(read_hash_directory(directory, "").is_err());
+    }
+}
@@ -10,7 +10,7 @@ use media_gateway_common::configuration::Credentials;
 use crate::server::security::quarantine::AuthQuarantine;
 use crate::server::service::cache::Cache;
 use crate::server::service::crypto::PasswordService;
-use crate::server::service::user::UserService;
+use crate::server::service::user::{UserData, UserService};
 
 pub mod quarantine;
 
@@ -93,25 +93,58 @@ pub async fn basic_auth_validator(
     }
     let basic_auth_quarantine = basic_auth_quarantine.unwrap();
 
+    match check_basic_auth(
+        &credentials,
+        user_service,
+        password_service.get_ref().as_ref(),
+        basic_auth_check_result_cache,
+        basic_auth_quarantine.get_ref().as_ref(),
+    ) {
+        Ok(user_data) => {
+            req.extensions_mut().insert(user_data);
+            Ok(req)
+        }
+        Err(BasicAuthError::Unauthorized) => Err((actix_web::error::ErrorUnauthorized(""), req)),
+        Err(BasicAuthError::Internal) => Err((actix_web::error::ErrorInternalServerError(""), req)),
+    }
+}
+
+/// An error of basic authentication check.
+#[derive(Debug, PartialEq, Eq)]
+pub enum BasicAuthError {
+    /// Credentials are invalid or the user is in quarantine
+    Unauthorized,
+    /// Credentials cannot be checked
+    Internal,
+}
+
+/// Checks credentials for basic authentication. Returns user data if credentials are valid.
+pub fn check_basic_auth(
+    credentials: &BasicAuth,
+    user_service: &UserService,
+    password_service: &(dyn PasswordService + Sync + Send),
+    basic_auth_check_result_cache: &Cache<Credentials, BasicAuthCheckResult>,
+    basic_auth_quarantine: &(dyn AuthQuarantine + Sync + Send),
+) -> Result<UserData, BasicAuthError> {
     if basic_auth_quarantine.in_quarantine(credentials.user_id()) {
-        return Err((actix_web::error::ErrorUnauthorized(""), req));
+        return Err(BasicAuthError::Unauthorized);
     }
 
     let password = credentials.password();
     if password.is_none() {
-        return Err((actix_web::error::ErrorUnauthorized(""), req));
+        return Err(BasicAuthError::Unauthorized);
     }
 
-    let credentials = to_credentials(&credentials).unwrap();
+    let credentials = to_credentials(credentials).unwrap();
     let user_data_result = user_service.get(credentials.username.as_str());
     match user_data_result {
         Err(e) => {
             error!("Error while retrieving user data: {:?}", e);
-            Err((actix_web::error::ErrorInternalServerError(""), req))
+            Err(BasicAuthError::Internal)
         }
         Ok(None) => {
             basic_auth_quarantine.register_failure(credentials.username.as_str());
-            Err((actix_web::error::ErrorUnauthorized(""), req))
+            Err(BasicAuthError::Unauthorized)
         }
         Ok(Some(user_data)) => {
             let cache_result = basic_auth_check_result_cache.get(&credentials);
@@ -119,11 +152,10 @@ pub async fn basic_auth_validator(
                 Some(e) if e.password_hash == user_data.password_hash => {
                     if e.valid {
                         basic_auth_quarantine.register_success(credentials.username.as_str());
-                        req.extensions_mut().insert(user_data);
-                        Ok(req)
+                        Ok(user_data)
                     } else {
                         basic_auth_quarantine.register_failure(credentials.username.as_str());
-                        Err((actix_web::error::ErrorUnauthorized(""), req))
+                        Err(BasicAuthError::Unauthorized)
                     }
                 }
                 _ => {
@@ -138,7 +170,7 @@ pub async fn basic_auth_validator(
                                 credentials,
                                 BasicAuthCheckResult::invalid(user_data.password_hash.clone()),
                             );
-                            Err((actix_web::error::ErrorUnauthorized(""), req))
+                            Err(BasicAuthError::Unauthorized)
                         }
                         Ok(true) => {
                             basic_auth_quarantine.register_success(credentials.username.as_str());
@@ -146,8 +178,7 @@ pub async fn basic_auth_validator(
                                 credentials,
                                 BasicAuthCheckResult::valid(user_data.password_hash.clone()),
                             );
-                            req.extensions_mut().insert(user_data);
-                            Ok(req)
+                            Ok(user_data)
                         }
                         Ok(false) => {
                             basic_auth_quarantine.register_failure(credentials.username.as_str());
@@ -155,7 +186,7 @@ pub async fn basic_auth_validator(
                                 credentials,
                                 BasicAuthCheckResult::invalid(user_data.password_hash.clone()),
                             );
-                            Err((actix_web::error::ErrorUnauthorized(""), req))
+                            Err(BasicAuthError::Unauthorized)
                         }
                     }
                 }
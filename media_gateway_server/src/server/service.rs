@@ -1,4 +1,5 @@
 pub mod cache;
 pub mod crypto;
 pub mod gateway;
+pub mod overload;
 pub mod user;
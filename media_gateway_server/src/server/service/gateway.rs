@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use actix_protobuf::ProtoBuf;
 use actix_web::web::ReqData;
 use actix_web::HttpResponse;
@@ -5,10 +7,11 @@ use log::{debug, error, info};
 use savant_core::message::Message;
 use savant_core::transport::zeromq::{SyncWriter, WriterResult};
 
-use media_gateway_common::model::Media;
+use media_gateway_common::model::{Media, MediaBatch, MediaBatchResult, MediaStatus};
 use media_gateway_common::statistics::StatisticsService;
 
 use crate::server::configuration::GatewayConfiguration;
+use crate::server::service::overload::OverloadDetector;
 use crate::server::service::user::UserData;
 
 const STAT_STAGE_NAME: &str = "server-relay";
@@ -16,32 +19,73 @@ const STAT_STAGE_NAME: &str = "server-relay";
 pub struct GatewayService {
     writer: SyncWriter,
     statistics_service: Option<StatisticsService>,
+    overload_detector: Option<Arc<OverloadDetector>>,
 }
 
 impl GatewayService {
-    pub fn new(writer: SyncWriter, statistics_service: Option<StatisticsService>) -> Self {
+    pub fn new(
+        writer: SyncWriter,
+        statistics_service: Option<StatisticsService>,
+        overload_detector: Option<Arc<OverloadDetector>>,
+    ) -> Self {
         Self {
             writer,
             statistics_service,
+            overload_detector,
         }
     }
+
+    /// Returns the detector that is fed with results of processing of messages.
+    pub fn overload_detector(&self) -> Option<Arc<OverloadDetector>> {
+        self.overload_detector.clone()
+    }
+
     pub fn process(
         &self,
         media: ProtoBuf<Media>,
         user_data: Option<ReqData<UserData>>,
     ) -> HttpResponse {
+        let status = self.process_media(&media, user_data.as_deref());
+        to_http_response(status)
+    }
+
+    pub fn process_batch(
+        &self,
+        batch: ProtoBuf<MediaBatch>,
+        user_data: Option<ReqData<UserData>>,
+    ) -> HttpResponse {
+        let user_data = user_data.as_deref();
+        let result = MediaBatchResult {
+            statuses: batch
+                .items
+                .iter()
+                .map(|media| self.process_media(media, user_data) as i32)
+                .collect(),
+        };
+        match result.to_proto() {
+            Ok(body) => HttpResponse::Ok()
+                .content_type("application/protobuf")
+                .body(body),
+            Err(e) => {
+                error!("Failed to serialize a batch result: {:?}", e);
+                HttpResponse::InternalServerError().finish()
+            }
+        }
+    }
+
+    pub fn process_media(&self, media: &Media, user_data: Option<&UserData>) -> MediaStatus {
         let topic_result = std::str::from_utf8(&media.topic);
         if topic_result.is_err() {
-            return HttpResponse::BadRequest().finish();
+            return MediaStatus::BadRequest;
         }
         let topic = topic_result.unwrap();
 
         if media.message.is_none() {
-            return HttpResponse::BadRequest().finish();
+            return MediaStatus::BadRequest;
         }
         let message_result = Message::try_from(media.message.as_ref().unwrap());
         if message_result.is_err() {
-            return HttpResponse::BadRequest().finish();
+            return MediaStatus::BadRequest;
         }
         let id = match self.statistics_service.as_ref() {
             Some(service) => match service.register_message_start() {
@@ -71,7 +115,7 @@ impl GatewayService {
                     .unwrap()
                     .matches(&message.meta().routing_labels)
             {
-                return HttpResponse::Unauthorized().finish();
+                return MediaStatus::Unauthorized;
             }
         }
 
@@ -82,14 +126,14 @@ impl GatewayService {
             .collect::<Vec<&[u8]>>();
 
         let result = self.writer.send_message(topic, &message, &data);
-        let response = match result {
-            Ok(WriterResult::SendTimeout) => HttpResponse::GatewayTimeout().finish(),
-            Ok(WriterResult::AckTimeout(_)) => HttpResponse::BadGateway().finish(),
-            Ok(WriterResult::Ack { .. }) => HttpResponse::Ok().finish(),
-            Ok(WriterResult::Success { .. }) => HttpResponse::Ok().finish(),
+        let status = match result {
+            Ok(WriterResult::SendTimeout) => MediaStatus::SendTimeout,
+            Ok(WriterResult::AckTimeout(_)) => MediaStatus::AckTimeout,
+            Ok(WriterResult::Ack { .. }) => MediaStatus::Success,
+            Ok(WriterResult::Success { .. }) => MediaStatus::Success,
             Err(e) => {
                 error!("Failed to send a message: {:?}", e);
-                HttpResponse::InternalServerError().finish()
+                MediaStatus::InternalError
             }
         };
         if let Some(stat_id) = id {
@@ -102,7 +146,22 @@ impl GatewayService {
                 log::warn!("Error while ending message statistics: {:?}", e)
             }
         }
-        response
+        if let Some(overload_detector) = self.overload_detector.as_ref() {
+            overload_detector.register(status);
+        }
+        status
+    }
+}
+
+fn to_http_response(status: MediaStatus) -> HttpResponse {
+    match status {
+        MediaStatus::Success => HttpResponse::Ok().finish(),
+        MediaStatus::SendTimeout => HttpResponse::GatewayTimeout().finish(),
+        MediaStatus::AckTimeout => HttpResponse::BadGateway().finish(),
+        MediaStatus::BadRequest => HttpResponse::BadRequest().finish(),
+        MediaStatus::Unauthorized => HttpResponse::Unauthorized().finish(),
+        MediaStatus::InternalError => HttpResponse::InternalServerError().finish(),
+        MediaStatus::Overloaded => HttpResponse::ServiceUnavailable().finish(),
     }
 }
 
@@ -119,7 +178,15 @@ impl TryFrom<&GatewayConfiguration> for GatewayService {
         } else {
             None
         };
-        Ok(GatewayService::new(writer, statistics_service))
+        let overload_detector = match &configuration.overload {
+            Some(overload_config) => Some(Arc::new(OverloadDetector::try_from(overload_config)?)),
+            None => None,
+        };
+        Ok(GatewayService::new(
+            writer,
+            statistics_service,
+            overload_detector,
+        ))
     }
 }
 
@@ -148,7 +215,7 @@ mod tests {
         ReaderConfigBuilder, ReaderResult, SyncReader, SyncWriter, WriterConfigBuilder,
     };
 
-    use media_gateway_common::model::Media;
+    use media_gateway_common::model::{Media, MediaBatch, MediaBatchResult, MediaStatus};
 
     use crate::server::service::gateway::GatewayService;
     use crate::server::service::user::UserData;
@@ -232,6 +299,40 @@ mod tests {
         assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
     }
 
+    #[test]
+    fn process_batch() {
+        let (message, media) = new_message_and_media();
+        let ipc = new_ipc();
+        let service = new_service_with_url(format!("pub+bind:{}", ipc).as_str());
+        let reader = new_reader(format!("sub+connect:{}", ipc).as_str());
+        let invalid_media = Media {
+            message: None,
+            topic: "topic".as_bytes().to_vec(),
+            data: vec![],
+        };
+        let batch = MediaBatch {
+            items: vec![invalid_media, media.clone()],
+        };
+
+        // timeout to connect writer and reader
+        thread::sleep(Duration::from_secs(1));
+
+        let response = service.process_batch(ProtoBuf(batch), None);
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = futures::executor::block_on(actix_web::body::to_bytes(response.into_body()))
+            .expect("response body failure");
+        let result = MediaBatchResult::from_proto(&body).expect("batch result failure");
+        assert_eq!(
+            result.statuses().collect::<Vec<MediaStatus>>(),
+            vec![MediaStatus::BadRequest, MediaStatus::Success]
+        );
+
+        let reader_result = reader.receive();
+        check_reader_result_message(&reader_result, &message, &media.topic, &media.data);
+        reader.shutdown().expect("reader shutdown failure");
+    }
+
     #[test]
     fn process_no_user_labels() {
         process_labels(None, StatusCode::OK)
@@ -283,6 +384,7 @@ mod tests {
         GatewayService {
             writer,
             statistics_service: None,
+            overload_detector: None,
         }
     }
 
@@ -0,0 +1,247 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use anyhow::bail;
+use log::warn;
+
+use media_gateway_common::model::MediaStatus;
+
+use crate::server::configuration::{OverloadConfiguration, OverloadStatus};
+
+const DEFAULT_WINDOW: Duration = Duration::from_secs(10);
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+/// The minimum number of results in the window to calculate the send timeout rate
+const MIN_RESULTS: u64 = 10;
+
+/// Detects whether the server is overloaded by the number of pending requests or the rate of
+/// send timeouts in the recent window. If the server is overloaded new requests are answered
+/// with 429 or 503 status and `Retry-After` header.
+pub struct OverloadDetector {
+    max_pending_requests: Option<usize>,
+    max_send_timeout_rate: Option<f64>,
+    window: Duration,
+    status: StatusCode,
+    retry_after: Duration,
+    pending_requests: AtomicUsize,
+    results: Mutex<ResultWindows>,
+}
+
+/// Counts of results in the current and the previous windows.
+struct ResultWindows {
+    started: Instant,
+    current: ResultCounts,
+    previous: ResultCounts,
+}
+
+#[derive(Default, Clone, Copy)]
+struct ResultCounts {
+    total: u64,
+    send_timeouts: u64,
+}
+
+/// A request that is being processed. The number of pending requests is decremented on drop.
+pub struct PendingRequest<'a>(&'a AtomicUsize);
+
+impl Drop for PendingRequest<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl OverloadDetector {
+    pub fn new(
+        max_pending_requests: Option<usize>,
+        max_send_timeout_rate: Option<f64>,
+        window: Duration,
+        status: StatusCode,
+        retry_after: Duration,
+    ) -> Self {
+        Self {
+            max_pending_requests,
+            max_send_timeout_rate,
+            window,
+            status,
+            retry_after,
+            pending_requests: AtomicUsize::new(0),
+            results: Mutex::new(ResultWindows {
+                started: Instant::now(),
+                current: ResultCounts::default(),
+                previous: ResultCounts::default(),
+            }),
+        }
+    }
+
+    /// Registers a new request. Returns [`None`] if the server is overloaded and the request
+    /// should be rejected.
+    pub fn enter(&self) -> Option<PendingRequest<'_>> {
+        let pending_requests = self.pending_requests.fetch_add(1, Ordering::SeqCst) + 1;
+        let pending_request = PendingRequest(&self.pending_requests);
+        if let Some(max_pending_requests) = self.max_pending_requests {
+            if pending_requests > max_pending_requests {
+                warn!(
+                    "Server is overloaded: {} pending requests",
+                    pending_requests
+                );
+                return None;
+            }
+        }
+        if let Some(max_send_timeout_rate) = self.max_send_timeout_rate {
+            let send_timeout_rate = self.send_timeout_rate();
+            if send_timeout_rate > max_send_timeout_rate {
+                warn!(
+                    "Server is overloaded: send timeout rate is {:.2}",
+                    send_timeout_rate
+                );
+                return None;
+            }
+        }
+        Some(pending_request)
+    }
+
+    /// Registers the result of processing of a message.
+    pub fn register(&self, status: MediaStatus) {
+        let mut results = self.results.lock().unwrap();
+        self.rotate(&mut results);
+        results.current.total += 1;
+        if status == MediaStatus::SendTimeout {
+            results.current.send_timeouts += 1;
+        }
+    }
+
+    /// Returns a response for a rejected request.
+    pub fn overloaded_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status)
+            .insert_header((
+                RETRY_AFTER,
+                self.retry_after.as_secs_f64().ceil().max(1.0) as u64,
+            ))
+            .finish()
+    }
+
+    fn send_timeout_rate(&self) -> f64 {
+        let mut results = self.results.lock().unwrap();
+        self.rotate(&mut results);
+        let total = results.current.total + results.previous.total;
+        if total < MIN_RESULTS {
+            return 0.0;
+        }
+        (results.current.send_timeouts + results.previous.send_timeouts) as f64 / total as f64
+    }
+
+    fn rotate(&self, results: &mut ResultWindows) {
+        let elapsed = results.started.elapsed();
+        if elapsed < self.window {
+            return;
+        }
+        results.previous = if elapsed < self.window * 2 {
+            results.current
+        } else {
+            ResultCounts::default()
+        };
+        results.current = ResultCounts::default();
+        results.started = Instant::now();
+    }
+}
+
+impl TryFrom<&OverloadConfiguration> for OverloadDetector {
+    type Error = anyhow::Error;
+
+    fn try_from(configuration: &OverloadConfiguration) -> Result<Self, Self::Error> {
+        if configuration.max_pending_requests.is_none()
+            && configuration.max_send_timeout_rate.is_none()
+        {
+            bail!("Invalid overload: neither max_pending_requests nor max_send_timeout_rate");
+        }
+        if let Some(max_send_timeout_rate) = configuration.max_send_timeout_rate {
+            if !(0.0..1.0).contains(&max_send_timeout_rate) {
+                bail!(
+                    "Invalid overload max_send_timeout_rate: {}",
+                    max_send_timeout_rate
+                );
+            }
+        }
+        let window = configuration.window.unwrap_or(DEFAULT_WINDOW);
+        if window.is_zero() {
+            bail!("Invalid overload window: 0");
+        }
+        let status = match configuration.status {
+            Some(OverloadStatus::TooManyRequests) => StatusCode::TOO_MANY_REQUESTS,
+            Some(OverloadStatus::ServiceUnavailable) | None => StatusCode::SERVICE_UNAVAILABLE,
+        };
+        Ok(OverloadDetector::new(
+            configuration.max_pending_requests,
+            configuration.max_send_timeout_rate,
+            window,
+            status,
+            configuration.retry_after.unwrap_or(DEFAULT_RETRY_AFTER),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use actix_web::http::header::RETRY_AFTER;
+    use actix_web::http::StatusCode;
+
+    use media_gateway_common::model::MediaStatus;
+
+    use crate::server::service::overload::OverloadDetector;
+
+    #[test]
+    fn max_pending_requests() {
+        let detector = new_detector(Some(2), None);
+
+        let first = detector.enter();
+        let second = detector.enter();
+        assert!(first.is_some());
+        assert!(second.is_some());
+        assert!(detector.enter().is_none());
+
+        drop(first);
+        assert!(detector.enter().is_some());
+    }
+
+    #[test]
+    fn max_send_timeout_rate() {
+        let detector = new_detector(None, Some(0.5));
+
+        for _ in 0..10 {
+            detector.register(MediaStatus::Success);
+        }
+        assert!(detector.enter().is_some());
+
+        for _ in 0..12 {
+            detector.register(MediaStatus::SendTimeout);
+        }
+        assert!(detector.enter().is_none());
+    }
+
+    #[test]
+    fn overloaded_response() {
+        let detector = new_detector(Some(1), None);
+
+        let response = detector.overloaded_response();
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "2");
+    }
+
+    fn new_detector(
+        max_pending_requests: Option<usize>,
+        max_send_timeout_rate: Option<f64>,
+    ) -> OverloadDetector {
+        OverloadDetector::new(
+            max_pending_requests,
+            max_send_timeout_rate,
+            Duration::from_secs(60),
+            StatusCode::TOO_MANY_REQUESTS,
+            Duration::from_millis(1500),
+        )
+    }
+}